@@ -1,175 +1,550 @@
 use crate::context::{Context, Error};
+use crate::esolang::{self, Budget, EsolangInterpreter};
 use std::collections::VecDeque;
-use std::time::{Duration, Instant};
 
+mod debugger;
+pub mod golf;
+
+/// Run Brainfuck code and take part in golf challenges
+#[poise::command(
+    slash_command,
+    subcommands("run", "golf::submit", "golf::leaderboard", "golf::challenge"),
+    subcommand_required
+)]
+pub async fn brainfuck(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Run Brainfuck code
+#[allow(clippy::too_many_arguments)]
 #[poise::command(slash_command)]
-pub async fn brainfuck(
+pub async fn run(
     ctx: Context<'_>,
     #[description = "code"] code: String,
     #[description = "inputs"] inputs_str: Option<String>,
     #[description = "ascii_codes"] ascii_codes_str: Option<String>,
+    #[description = "Cell width (default 8 bits)"] cell_width: Option<CellWidth>,
+    #[description = "Number of cells on the tape (default 30000)"]
+    #[min = 1]
+    #[max = 1000000]
+    tape_size: Option<usize>,
+    #[description = "What happens at the tape ends (default wrap)"] tape_mode: Option<TapeMode>,
+    #[description = "What ',' does when input runs out (default error)"] eof: Option<EofBehavior>,
+    #[description = "Step through the program, '#' sets a breakpoint"] debug: Option<bool>,
 ) -> Result<(), Error> {
-    let inputs: Vec<u8> = match (inputs_str, ascii_codes_str) {
-        (Some(_), Some(_)) => {
-            ctx.say("Both 'inputs' and 'ascii_codes' were provided. Please provide only one.")
-                .await?;
+    let dialect = Dialect {
+        cell_width: cell_width.unwrap_or_default(),
+        tape_size: tape_size.unwrap_or(TAPE_SIZE).clamp(1, MAX_TAPE_SIZE),
+        tape_mode: tape_mode.unwrap_or_default(),
+        eof: eof.unwrap_or_default(),
+    };
+
+    let interpreter = Brainfuck { dialect };
+    let inputs = match esolang::parse_inputs(&interpreter, inputs_str, ascii_codes_str) {
+        Ok(inputs) => inputs,
+        Err(e) => {
+            ctx.say(e).await?;
             return Ok(());
         }
-        (Some(input_str), None) => input_str.into_bytes(),
-        (None, Some(ascii_codes)) => ascii_codes
-            .split_whitespace()
-            .filter_map(|s| s.parse::<u8>().ok())
-            .collect(),
-        (None, None) => Vec::new(),
     };
 
-    match interpret_brainfuck(code, inputs) {
-        Ok(result) => {
-            let output = if result.is_empty() {
-                "The program produced no output.".to_string()
-            } else {
-                result
-            };
-
-            let truncated_output = if output.len() > 1900 {
-                format!("{}... (output truncated)", &output[..1900])
-            } else {
-                output
-            };
-
-            ctx.say(format!("Output:\n```\n{}\n```", truncated_output))
-                .await?;
+    if debug.unwrap_or(false) {
+        return debugger::debug(ctx, code, inputs, dialect).await;
+    }
+
+    esolang::respond(ctx, Box::new(interpreter), code, inputs).await
+}
+
+const TAPE_SIZE: usize = 30000;
+const MAX_TAPE_SIZE: usize = 1_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, poise::ChoiceParameter)]
+pub enum CellWidth {
+    #[default]
+    #[name = "8 bits"]
+    Bits8,
+    #[name = "16 bits"]
+    Bits16,
+    #[name = "32 bits"]
+    Bits32,
+}
+
+impl CellWidth {
+    /// Bit mask of the values a cell can hold
+    fn mask(self) -> u32 {
+        match self {
+            CellWidth::Bits8 => 0xFF,
+            CellWidth::Bits16 => 0xFFFF,
+            CellWidth::Bits32 => 0xFFFF_FFFF,
         }
-        Err((error, partial_output)) => {
-            let output_message = if partial_output.is_empty() {
-                "No output was produced before the program was terminated.".to_string()
-            } else {
-                format!(
-                    "Partial output before termination:\n```\n{}\n```",
-                    if partial_output.len() > 1800 {
-                        format!("{}... (output truncated)", &partial_output[..1800])
-                    } else {
-                        partial_output
-                    }
-                )
-            };
-            ctx.say(format!(
-                "Error executing Brainfuck code: {}\n\n{}",
-                error, output_message
-            ))
-            .await?;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, poise::ChoiceParameter)]
+pub enum TapeMode {
+    /// Moving off one end of the tape continues at the other end
+    #[default]
+    #[name = "wrap around"]
+    Wrap,
+    /// Moving off either end of the tape is an error
+    #[name = "error at the ends"]
+    Bounded,
+    /// The tape grows to the right as needed, moving left of the first cell is an error
+    #[name = "grow to the right"]
+    Grow,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, poise::ChoiceParameter)]
+pub enum EofBehavior {
+    #[default]
+    #[name = "error"]
+    Error,
+    #[name = "leave cell unchanged"]
+    Unchanged,
+    #[name = "set cell to 0"]
+    Zero,
+    #[name = "set cell to -1"]
+    MinusOne,
+}
+
+/// The Brainfuck variant a program is run under
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Dialect {
+    pub cell_width: CellWidth,
+    /// Initial number of cells; with `TapeMode::Grow` the tape may grow up to `MAX_TAPE_SIZE`
+    pub tape_size: usize,
+    pub tape_mode: TapeMode,
+    pub eof: EofBehavior,
+}
+
+impl Default for Dialect {
+    fn default() -> Self {
+        Dialect {
+            cell_width: CellWidth::default(),
+            tape_size: TAPE_SIZE,
+            tape_mode: TapeMode::default(),
+            eof: EofBehavior::default(),
         }
     }
-    Ok(())
 }
 
-const MAX_STEPS: usize = 5_000_000;
-const MAX_RUNTIME: Duration = Duration::from_secs(5);
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Brainfuck {
+    pub dialect: Dialect,
+}
+
+impl EsolangInterpreter for Brainfuck {
+    fn name(&self) -> &'static str {
+        "Brainfuck"
+    }
 
-fn interpret_brainfuck(code: String, inputs: Vec<u8>) -> Result<String, (String, String)> {
-    let mut memory = vec![0u8; 30000];
-    let mut pointer = 0;
-    let mut output = String::new();
-    let mut input_queue: VecDeque<u8> = inputs.into_iter().collect();
-    let mut code_pointer = 0;
+    fn byte_input(&self) -> bool {
+        self.dialect.cell_width == CellWidth::Bits8
+    }
+
+    fn max_input(&self) -> u32 {
+        self.dialect.cell_width.mask()
+    }
+
+    fn run(
+        &self,
+        code: &str,
+        inputs: Vec<u32>,
+        mut budget: Budget,
+    ) -> Result<Vec<u8>, (String, Vec<u8>)> {
+        interpret_brainfuck(code.to_string(), inputs, &self.dialect, &mut budget)
+    }
+}
+
+/// A single instruction of the compiled Brainfuck program.
+///
+/// `cost` on the enclosing `Instr` is the number of source characters the op
+/// stands for, so the step limit keeps counting source-level instructions.
+#[derive(Debug, Clone, PartialEq)]
+enum Op {
+    /// Folded run of `+` and `-`, as a wrapping delta
+    Add(u32),
+    /// Folded run of `>` and `<`; the tape bounds are checked at the end of the run
+    Move(isize),
+    Output,
+    Input,
+    /// `[`, holding the index of the matching `]`
+    JumpIfZero(usize),
+    /// `]`, holding the index of the matching `[`
+    JumpIfNonZero(usize),
+    /// `[-]` or `[+]`
+    Clear {
+        dec: bool,
+    },
+    /// Copy/multiply loop such as `[->+>++<<]`: adds `factor` times the
+    /// iteration count to the cell at each `offset`, then clears the current cell
+    MulLoop {
+        dec: bool,
+        targets: Vec<(isize, u32)>,
+    },
+    /// `#`, only emitted for the debugger
+    Breakpoint,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Instr {
+    op: Op,
+    cost: usize,
+    /// Position of the first source character of the op
+    pos: usize,
+}
+
+/// Compile Brainfuck source into a list of ops with precomputed jump targets.
+///
+/// In debug mode nothing is folded, so every op is exactly one source
+/// instruction, and `#` becomes a breakpoint.
+fn compile(code: &str, debug: bool) -> Result<Vec<Instr>, String> {
     let code_chars: Vec<char> = code.chars().collect();
-    let mut bracket_stack: Vec<usize> = Vec::new();
+    let mut program: Vec<Instr> = Vec::new();
+    // (source position, op index) of every open `[`
+    let mut bracket_stack: Vec<(usize, usize)> = Vec::new();
 
-    for (i, &c) in code_chars.iter().enumerate() {
-        match c {
-            '[' => bracket_stack.push(i),
+    let mut i = 0;
+    while i < code_chars.len() {
+        let pos = i;
+        match code_chars[i] {
+            '+' | '-' => {
+                let mut delta = 0u32;
+                let mut cost = 0;
+                while i < code_chars.len() && matches!(code_chars[i], '+' | '-') {
+                    delta = if code_chars[i] == '+' {
+                        delta.wrapping_add(1)
+                    } else {
+                        delta.wrapping_sub(1)
+                    };
+                    cost += 1;
+                    i += 1;
+                    if debug {
+                        break;
+                    }
+                }
+                program.push(Instr {
+                    op: Op::Add(delta),
+                    cost,
+                    pos,
+                });
+                continue;
+            }
+            '>' | '<' => {
+                let mut delta = 0isize;
+                let mut cost = 0;
+                while i < code_chars.len() && matches!(code_chars[i], '>' | '<') {
+                    delta += if code_chars[i] == '>' { 1 } else { -1 };
+                    cost += 1;
+                    i += 1;
+                    if debug {
+                        break;
+                    }
+                }
+                program.push(Instr {
+                    op: Op::Move(delta),
+                    cost,
+                    pos,
+                });
+                continue;
+            }
+            '.' => program.push(Instr {
+                op: Op::Output,
+                cost: 1,
+                pos,
+            }),
+            ',' => program.push(Instr {
+                op: Op::Input,
+                cost: 1,
+                pos,
+            }),
+            '[' => {
+                bracket_stack.push((i, program.len()));
+                // Patched with the real target once the matching `]` is found
+                program.push(Instr {
+                    op: Op::JumpIfZero(0),
+                    cost: 1,
+                    pos,
+                });
+            }
             ']' => {
-                if bracket_stack.pop().is_none() {
-                    return Err((
-                        format!("Unmatched closing bracket at position {}", i),
-                        output,
-                    ));
+                let (_, open) = bracket_stack
+                    .pop()
+                    .ok_or_else(|| format!("Unmatched closing bracket at position {}", i))?;
+                let idiom = if debug {
+                    None
+                } else {
+                    fold_loop(&program[open + 1..])
+                };
+                if let Some(op) = idiom {
+                    let body_cost = program[open + 1..].iter().map(|i| i.cost).sum();
+                    let open_pos = program[open].pos;
+                    program.truncate(open);
+                    program.push(Instr {
+                        op,
+                        cost: body_cost,
+                        pos: open_pos,
+                    });
+                } else {
+                    let close = program.len();
+                    program[open].op = Op::JumpIfZero(close);
+                    program.push(Instr {
+                        op: Op::JumpIfNonZero(open),
+                        cost: 1,
+                        pos,
+                    });
                 }
             }
+            '#' if debug => program.push(Instr {
+                op: Op::Breakpoint,
+                cost: 0,
+                pos,
+            }),
             _ => {}
         }
+        i += 1;
     }
-    if !bracket_stack.is_empty() {
-        return Err((
-            format!(
-                "Unmatched opening bracket at position {}",
-                bracket_stack.pop().unwrap()
-            ),
-            output,
+
+    if let Some((position, _)) = bracket_stack.pop() {
+        return Err(format!(
+            "Unmatched opening bracket at position {}",
+            position
         ));
     }
 
-    bracket_stack.clear();
+    Ok(program)
+}
+
+/// Recognise clear and copy/multiply loops from the ops of a loop body.
+///
+/// The body must only add and move, return to its starting cell and change
+/// that cell by exactly one per iteration.
+fn fold_loop(body: &[Instr]) -> Option<Op> {
+    let mut offset = 0isize;
+    let mut deltas: Vec<(isize, u32)> = Vec::new();
+
+    for instr in body {
+        match instr.op {
+            Op::Add(delta) => match deltas.iter_mut().find(|(o, _)| *o == offset) {
+                Some((_, d)) => *d = d.wrapping_add(delta),
+                None => deltas.push((offset, delta)),
+            },
+            Op::Move(delta) => offset += delta,
+            _ => return None,
+        }
+    }
+    if offset != 0 {
+        return None;
+    }
+
+    let step = deltas
+        .iter()
+        .find(|(o, _)| *o == 0)
+        .map(|(_, d)| *d)
+        .unwrap_or(0);
+    let dec = match step {
+        1 => false,
+        u32::MAX => true,
+        _ => return None,
+    };
+    let targets: Vec<(isize, u32)> = deltas
+        .into_iter()
+        .filter(|(o, d)| *o != 0 && *d != 0)
+        .collect();
+
+    if targets.is_empty() {
+        Some(Op::Clear { dec })
+    } else {
+        Some(Op::MulLoop { dec, targets })
+    }
+}
 
-    let start_time = Instant::now();
-    let mut steps = 0;
-    while code_pointer < code_chars.len() {
-        if start_time.elapsed() > MAX_RUNTIME {
-            return Err(("Execution time limit exceeded".to_string(), output));
+/// Run `code` to completion, charging its steps to `budget` so several runs
+/// can share one limit
+pub(crate) fn interpret_brainfuck(
+    code: String,
+    inputs: Vec<u32>,
+    dialect: &Dialect,
+    budget: &mut Budget,
+) -> Result<Vec<u8>, (String, Vec<u8>)> {
+    let program = compile(&code, false).map_err(|e| (e, Vec::new()))?;
+    let mut machine = Machine::new(program, inputs, *dialect, std::mem::take(budget));
+    let result = machine.run(usize::MAX, false);
+    *budget = machine.budget;
+    match result {
+        Ok(()) => Ok(machine.output),
+        Err(e) => Err((e, machine.output)),
+    }
+}
+
+/// Interpreter state for a compiled program, which can be run to the end or
+/// advanced a few ops at a time by the debugger
+struct Machine {
+    program: Vec<Instr>,
+    dialect: Dialect,
+    memory: Vec<u32>,
+    pointer: usize,
+    input_queue: VecDeque<u32>,
+    output: Vec<u8>,
+    /// Index of the next op to execute
+    pc: usize,
+    budget: Budget,
+}
+
+impl Machine {
+    fn new(program: Vec<Instr>, inputs: Vec<u32>, dialect: Dialect, budget: Budget) -> Self {
+        Machine {
+            program,
+            dialect,
+            memory: vec![0u32; dialect.tape_size],
+            pointer: 0,
+            input_queue: inputs.into_iter().collect(),
+            output: Vec::new(),
+            pc: 0,
+            budget,
         }
+    }
+
+    fn is_finished(&self) -> bool {
+        self.pc >= self.program.len()
+    }
 
-        if steps >= MAX_STEPS {
-            return Err(("Instruction limit exceeded".to_string(), output));
+    /// Execute up to `max_ops` ops, stopping early at the end of the program
+    /// or, if `stop_at_breakpoint` is set, in front of the next breakpoint
+    fn run(&mut self, max_ops: usize, stop_at_breakpoint: bool) -> Result<(), String> {
+        let mut ops = 0;
+        while !self.is_finished() && ops < max_ops {
+            if stop_at_breakpoint && ops > 0 && self.program[self.pc].op == Op::Breakpoint {
+                break;
+            }
+            ops += 1;
+            self.step()?;
         }
+        Ok(())
+    }
+
+    /// Execute the op at `pc`
+    fn step(&mut self) -> Result<(), String> {
+        let mask = self.dialect.cell_width.mask();
+        let instr = &self.program[self.pc];
+        let cost = match instr.op {
+            // The `[` runs once, then every iteration runs the body and the `]`
+            Op::Clear { dec } | Op::MulLoop { dec, .. } => {
+                loop_iterations(self.memory[self.pointer], dec, mask)
+                    .saturating_mul(instr.cost + 1)
+                    .saturating_add(1)
+            }
+            _ => instr.cost,
+        };
+        self.budget.charge(cost)?;
 
-        steps += 1;
-
-        match code_chars[code_pointer] {
-            '>' => pointer = (pointer + 1) % memory.len(),
-            '<' => pointer = (pointer - 1 + memory.len()) % memory.len(),
-            '+' => memory[pointer] = memory[pointer].wrapping_add(1),
-            '-' => memory[pointer] = memory[pointer].wrapping_sub(1),
-            '.' => output.push(memory[pointer] as char),
-            ',' => {
-                if let Some(input) = input_queue.pop_front() {
-                    memory[pointer] = (input & 0xFF) as u8;
+        let memory = &mut self.memory;
+        let pointer = self.pointer;
+        match &instr.op {
+            Op::Add(delta) => memory[pointer] = memory[pointer].wrapping_add(*delta) & mask,
+            Op::Move(delta) => {
+                self.pointer = tape_index(memory, pointer, *delta, self.dialect.tape_mode)?
+            }
+            Op::Output => {
+                if self.dialect.cell_width == CellWidth::Bits8 {
+                    self.output.push(memory[pointer] as u8);
                 } else {
-                    return Err(("Not enough input values provided".to_string(), output));
+                    // Wider cells hold code points, written out as UTF-8
+                    let c = char::from_u32(memory[pointer]).unwrap_or('\u{FFFD}');
+                    self.output
+                        .extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
                 }
             }
-            '[' => {
-                if memory[pointer] == 0 {
-                    let mut bracket_count = 1;
-                    while bracket_count > 0 {
-                        code_pointer += 1;
-                        if code_pointer >= code_chars.len() {
-                            return Err(("Unmatched opening bracket".to_string(), output));
-                        }
-                        match code_chars[code_pointer] {
-                            '[' => bracket_count += 1,
-                            ']' => bracket_count -= 1,
-                            _ => {}
-                        }
+            Op::Input => match self.input_queue.pop_front() {
+                Some(input) => memory[pointer] = input & mask,
+                None => match self.dialect.eof {
+                    EofBehavior::Error => {
+                        return Err("Not enough input values provided".to_string());
                     }
-                } else {
-                    bracket_stack.push(code_pointer);
+                    EofBehavior::Unchanged => {}
+                    EofBehavior::Zero => memory[pointer] = 0,
+                    EofBehavior::MinusOne => memory[pointer] = mask,
+                },
+            },
+            Op::JumpIfZero(target) => {
+                if memory[pointer] == 0 {
+                    self.pc = *target;
                 }
             }
-            ']' => {
+            Op::JumpIfNonZero(target) => {
                 if memory[pointer] != 0 {
-                    code_pointer = *bracket_stack.last().unwrap();
-                } else {
-                    bracket_stack.pop();
+                    self.pc = *target;
                 }
             }
-            _ => {}
+            Op::Clear { .. } => memory[pointer] = 0,
+            Op::MulLoop { dec, targets } => {
+                let iterations = loop_iterations(memory[pointer], *dec, mask) as u32;
+                // A loop that never runs never moves the pointer either
+                if iterations == 0 {
+                    self.pc += 1;
+                    return Ok(());
+                }
+                for (offset, factor) in targets {
+                    let cell = tape_index(memory, pointer, *offset, self.dialect.tape_mode)?;
+                    memory[cell] =
+                        memory[cell].wrapping_add(factor.wrapping_mul(iterations)) & mask;
+                }
+                memory[pointer] = 0;
+            }
+            Op::Breakpoint => {}
+        }
+        self.pc += 1;
+        Ok(())
+    }
+}
+
+/// Number of iterations a loop stepping its cell by one needs to reach zero
+fn loop_iterations(value: u32, dec: bool, mask: u32) -> usize {
+    if dec {
+        value as usize
+    } else {
+        (value.wrapping_neg() & mask) as usize
+    }
+}
+
+/// Resolve the cell `delta` cells away from `pointer`, growing the tape if the dialect allows it
+fn tape_index(
+    memory: &mut Vec<u32>,
+    pointer: usize,
+    delta: isize,
+    mode: TapeMode,
+) -> Result<usize, String> {
+    let len = memory.len() as isize;
+    let target = pointer as isize + delta;
+    match mode {
+        TapeMode::Wrap => Ok(target.rem_euclid(len) as usize),
+        _ if target < 0 => Err("Pointer moved past the left end of the tape".to_string()),
+        TapeMode::Bounded if target >= len => {
+            Err("Pointer moved past the right end of the tape".to_string())
+        }
+        TapeMode::Grow if target >= len => {
+            if target as usize >= MAX_TAPE_SIZE {
+                return Err("Tape size limit exceeded".to_string());
+            }
+            memory.resize(target as usize + 1, 0);
+            Ok(target as usize)
         }
-        code_pointer += 1;
+        _ => Ok(target as usize),
     }
-    Ok(output)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::esolang::MAX_STEPS;
 
     #[test]
     fn test_hello_world() {
         let code = String::from("++++++++++[>+++++++>++++++++++>+++>+<<<<-]>++.>+.+++++++..+++.>++.<<+++++++++++++++.>.+++.------.--------.>+.>.");
         let inputs = vec![];
         assert_eq!(
-            interpret_brainfuck(code, inputs),
-            Ok(String::from("Hello World!\n"))
+            interpret_brainfuck(code, inputs, &Dialect::default(), &mut Budget::default()),
+            Ok(b"Hello World!\n".to_vec())
         );
     }
 
@@ -178,8 +553,8 @@ mod tests {
         let code = String::from(",[.,]");
         let inputs = vec![72, 101, 108, 108, 111, 33, 0];
         assert_eq!(
-            interpret_brainfuck(code, inputs),
-            Ok(String::from("Hello!"))
+            interpret_brainfuck(code, inputs, &Dialect::default(), &mut Budget::default()),
+            Ok(b"Hello!".to_vec())
         );
     }
 
@@ -187,7 +562,10 @@ mod tests {
     fn test_addition() {
         let code = String::from(",>,[-<+>]<.");
         let inputs = vec![3, 5];
-        assert_eq!(interpret_brainfuck(code, inputs), Ok(String::from("\u{8}")));
+        assert_eq!(
+            interpret_brainfuck(code, inputs, &Dialect::default(), &mut Budget::default()),
+            Ok(b"\x08".to_vec())
+        );
     }
 
     #[test]
@@ -195,11 +573,8 @@ mod tests {
         let code = String::from(",>,<.");
         let inputs = vec![65];
         assert_eq!(
-            interpret_brainfuck(code, inputs),
-            Err((
-                String::from("Not enough input values provided"),
-                String::from("")
-            ))
+            interpret_brainfuck(code, inputs, &Dialect::default(), &mut Budget::default()),
+            Err((String::from("Not enough input values provided"), Vec::new()))
         );
     }
 
@@ -208,10 +583,10 @@ mod tests {
         let code = String::from("[");
         let inputs = vec![];
         assert_eq!(
-            interpret_brainfuck(code, inputs),
+            interpret_brainfuck(code, inputs, &Dialect::default(), &mut Budget::default()),
             Err((
                 String::from("Unmatched opening bracket at position 0"),
-                String::from("")
+                Vec::new()
             ))
         );
     }
@@ -221,10 +596,10 @@ mod tests {
         let code = String::from("]");
         let inputs = vec![];
         assert_eq!(
-            interpret_brainfuck(code, inputs),
+            interpret_brainfuck(code, inputs, &Dialect::default(), &mut Budget::default()),
             Err((
                 String::from("Unmatched closing bracket at position 0"),
-                String::from("")
+                Vec::new()
             ))
         );
     }
@@ -232,6 +607,272 @@ mod tests {
     fn test_nested_loops() {
         let code = String::from("++++[>++[>+<-]<-]>>.");
         let inputs = vec![];
-        assert_eq!(interpret_brainfuck(code, inputs), Ok(String::from("\u{8}")));
+        assert_eq!(
+            interpret_brainfuck(code, inputs, &Dialect::default(), &mut Budget::default()),
+            Ok(b"\x08".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_compile_folds_runs() {
+        let program = compile("+++-->><<<.", false).unwrap();
+        let ops: Vec<(Op, usize)> = program.into_iter().map(|i| (i.op, i.cost)).collect();
+        assert_eq!(
+            ops,
+            vec![(Op::Add(1), 5), (Op::Move(-1), 5), (Op::Output, 1)]
+        );
+    }
+
+    #[test]
+    fn test_compile_loop_idioms() {
+        let program = compile("[-][+][->+>++<<]", false).unwrap();
+        let ops: Vec<Op> = program.into_iter().map(|i| i.op).collect();
+        assert_eq!(
+            ops,
+            vec![
+                Op::Clear { dec: true },
+                Op::Clear { dec: false },
+                Op::MulLoop {
+                    dec: true,
+                    targets: vec![(1, 1), (2, 2)]
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compile_jump_targets() {
+        let program = compile("+[>.<-]", false).unwrap();
+        assert_eq!(program[1].op, Op::JumpIfZero(6));
+        assert_eq!(program[6].op, Op::JumpIfNonZero(1));
+    }
+
+    #[test]
+    fn test_multiply_loop() {
+        // 7 * 9 = 63 = '?'
+        let code = String::from("+++++++[->+++++++++<]>.");
+        assert_eq!(
+            interpret_brainfuck(code, vec![], &Dialect::default(), &mut Budget::default()),
+            Ok(b"?".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_clear_loop_wraps_upwards() {
+        let code = String::from("+++[+]+++++++++++++++++++++++++++++++++.");
+        assert_eq!(
+            interpret_brainfuck(code, vec![], &Dialect::default(), &mut Budget::default()),
+            Ok(b"!".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_pointer_wraps_left() {
+        let code = String::from("<+++++++++++++++++++++++++++++++++.>.");
+        assert_eq!(
+            interpret_brainfuck(code, vec![], &Dialect::default(), &mut Budget::default()),
+            Ok(b"!\x00".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_step_limit_counts_source_instructions() {
+        let code = "+".repeat(MAX_STEPS);
+        assert_eq!(
+            interpret_brainfuck(code, vec![], &Dialect::default(), &mut Budget::default()),
+            Ok(Vec::new())
+        );
+
+        let code = format!(".{}", "+".repeat(MAX_STEPS));
+        assert_eq!(
+            interpret_brainfuck(code, vec![], &Dialect::default(), &mut Budget::default()),
+            Err((String::from("Instruction limit exceeded"), b"\x00".to_vec()))
+        );
+    }
+
+    #[test]
+    fn test_infinite_loop_hits_limit() {
+        let code = String::from("+[]");
+        assert_eq!(
+            interpret_brainfuck(code, vec![], &Dialect::default(), &mut Budget::default()),
+            Err((String::from("Instruction limit exceeded"), Vec::new()))
+        );
+    }
+
+    fn dialect(cell_width: CellWidth, tape_mode: TapeMode, eof: EofBehavior) -> Dialect {
+        Dialect {
+            cell_width,
+            tape_mode,
+            eof,
+            ..Dialect::default()
+        }
+    }
+
+    #[test]
+    fn test_eof_error() {
+        let code = String::from(",.");
+        let d = dialect(CellWidth::Bits8, TapeMode::Wrap, EofBehavior::Error);
+        assert_eq!(
+            interpret_brainfuck(code, vec![], &d, &mut Budget::default()),
+            Err((String::from("Not enough input values provided"), Vec::new()))
+        );
+    }
+
+    #[test]
+    fn test_eof_unchanged() {
+        let code = String::from("+++++++++++++++++++++++++++++++++,.");
+        let d = dialect(CellWidth::Bits8, TapeMode::Wrap, EofBehavior::Unchanged);
+        assert_eq!(
+            interpret_brainfuck(code, vec![], &d, &mut Budget::default()),
+            Ok(b"!".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_eof_zero() {
+        // cat that stops at EOF when ',' stores 0
+        let code = String::from(",[.,]");
+        let d = dialect(CellWidth::Bits8, TapeMode::Wrap, EofBehavior::Zero);
+        assert_eq!(
+            interpret_brainfuck(code, vec![72, 105], &d, &mut Budget::default()),
+            Ok(b"Hi".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_eof_minus_one() {
+        // cat that stops at EOF when ',' stores -1
+        let code = String::from(",+[-.,+]");
+        let d = dialect(CellWidth::Bits16, TapeMode::Wrap, EofBehavior::MinusOne);
+        assert_eq!(
+            interpret_brainfuck(code, vec![72, 105], &d, &mut Budget::default()),
+            Ok(b"Hi".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_cell_width_8_wraps() {
+        let code = String::from("-.");
+        let d = dialect(CellWidth::Bits8, TapeMode::Wrap, EofBehavior::Error);
+        assert_eq!(
+            interpret_brainfuck(code, vec![], &d, &mut Budget::default()),
+            Ok(b"\xff".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_cell_width_16() {
+        // 16 * 16 + 65 = 321 = 'Ł', which only fits in cells wider than 8 bits
+        let code = String::from("++++++++++++++++[->++++++++++++++++<]>+++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++.");
+        let d = dialect(CellWidth::Bits8, TapeMode::Wrap, EofBehavior::Error);
+        assert_eq!(
+            interpret_brainfuck(code.clone(), vec![], &d, &mut Budget::default()),
+            Ok(b"A".to_vec())
+        );
+
+        let d = dialect(CellWidth::Bits16, TapeMode::Wrap, EofBehavior::Error);
+        assert_eq!(
+            interpret_brainfuck(code, vec![], &d, &mut Budget::default()),
+            Ok("\u{141}".as_bytes().to_vec())
+        );
+    }
+
+    #[test]
+    fn test_cell_width_16_wraps() {
+        let code = String::from(",[-]-[-].+[+].");
+        let d = dialect(CellWidth::Bits16, TapeMode::Wrap, EofBehavior::Error);
+        assert_eq!(
+            interpret_brainfuck(code.clone(), vec![0x1234], &d, &mut Budget::default()),
+            Ok(b"\x00\x00".to_vec())
+        );
+
+        let code = String::from("-.");
+        assert_eq!(
+            interpret_brainfuck(code, vec![], &d, &mut Budget::default()),
+            Ok("\u{ffff}".as_bytes().to_vec())
+        );
+    }
+
+    #[test]
+    fn test_cell_width_32() {
+        let code = String::from(",.");
+        let d = dialect(CellWidth::Bits32, TapeMode::Wrap, EofBehavior::Error);
+        assert_eq!(
+            interpret_brainfuck(code, vec![0x1F980], &d, &mut Budget::default()),
+            Ok("\u{1F980}".as_bytes().to_vec())
+        );
+
+        // Clearing a 32-bit cell counting up from 1 takes far more than MAX_STEPS
+        let code = String::from("+[+]");
+        assert_eq!(
+            interpret_brainfuck(code, vec![], &d, &mut Budget::default()),
+            Err((String::from("Instruction limit exceeded"), Vec::new()))
+        );
+    }
+
+    #[test]
+    fn test_tape_bounded() {
+        let d = dialect(CellWidth::Bits8, TapeMode::Bounded, EofBehavior::Error);
+        assert_eq!(
+            interpret_brainfuck(String::from(".<"), vec![], &d, &mut Budget::default()),
+            Err((
+                String::from("Pointer moved past the left end of the tape"),
+                b"\x00".to_vec()
+            ))
+        );
+
+        let d = Dialect { tape_size: 3, ..d };
+        assert_eq!(
+            interpret_brainfuck(String::from(">>>"), vec![], &d, &mut Budget::default()),
+            Err((
+                String::from("Pointer moved past the right end of the tape"),
+                Vec::new()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_skipped_multiply_loop_at_left_edge() {
+        // The copy loop starts on a zero cell, so it never looks left
+        for mode in [TapeMode::Bounded, TapeMode::Grow] {
+            let d = dialect(CellWidth::Bits8, mode, EofBehavior::Error);
+            assert_eq!(
+                interpret_brainfuck(String::from("[<+>-]+."), vec![], &d, &mut Budget::default()),
+                Ok(b"\x01".to_vec())
+            );
+        }
+    }
+
+    #[test]
+    fn test_tape_grow() {
+        let d = Dialect {
+            tape_size: 1,
+            ..dialect(CellWidth::Bits8, TapeMode::Grow, EofBehavior::Error)
+        };
+        assert_eq!(
+            interpret_brainfuck(
+                String::from("+++[->>>+++++++++++<<<]>>>."),
+                vec![],
+                &d,
+                &mut Budget::default()
+            ),
+            Ok(b"!".to_vec())
+        );
+        assert_eq!(
+            interpret_brainfuck(String::from("<"), vec![], &d, &mut Budget::default()),
+            Err((
+                String::from("Pointer moved past the left end of the tape"),
+                Vec::new()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_high_bytes_are_not_reencoded() {
+        let code = String::from("-.");
+        assert_eq!(
+            interpret_brainfuck(code, vec![], &Dialect::default(), &mut Budget::default()),
+            Ok(vec![0xFF])
+        );
     }
 }
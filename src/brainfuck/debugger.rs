@@ -0,0 +1,308 @@
+use super::{compile, Dialect, Machine};
+use crate::context::{Context, Error};
+use crate::esolang::Budget;
+use poise::serenity_prelude as serenity;
+use std::time::Duration;
+
+// The session ends when no button has been pressed for this long
+const SESSION_TIMEOUT: Duration = Duration::from_secs(600);
+// Cells shown on each side of the data pointer
+const TAPE_WINDOW: usize = 6;
+// Source characters shown on each side of the instruction pointer
+const CODE_WINDOW: usize = 24;
+const MAX_OUTPUT_SHOWN: usize = 500;
+
+enum State {
+    Paused,
+    Finished,
+    Failed(String),
+}
+
+/// Run `code` under the step debugger, driven by buttons on the reply
+pub async fn debug(
+    ctx: Context<'_>,
+    code: String,
+    inputs: Vec<u32>,
+    dialect: Dialect,
+) -> Result<(), Error> {
+    let program = match compile(&code, true) {
+        Ok(program) => program,
+        Err(e) => {
+            ctx.say(format!("Error executing Brainfuck code: {}", e))
+                .await?;
+            return Ok(());
+        }
+    };
+    let source: Vec<char> = code.chars().collect();
+    let mut machine = Machine::new(program, inputs, dialect, Budget::default());
+    let mut state = if machine.is_finished() {
+        State::Finished
+    } else {
+        State::Paused
+    };
+
+    let ctx_id = ctx.id();
+    let step_id = format!("{}step", ctx_id);
+    let step100_id = format!("{}step100", ctx_id);
+    let breakpoint_id = format!("{}breakpoint", ctx_id);
+    let continue_id = format!("{}continue", ctx_id);
+    let buttons = vec![serenity::CreateActionRow::Buttons(vec![
+        serenity::CreateButton::new(&step_id).label("Step"),
+        serenity::CreateButton::new(&step100_id).label("Step ×100"),
+        serenity::CreateButton::new(&breakpoint_id).label("Run to #"),
+        serenity::CreateButton::new(&continue_id)
+            .label("Continue")
+            .style(serenity::ButtonStyle::Success),
+    ])];
+
+    let reply = poise::CreateReply::default().embed(render(&machine, &source, &state));
+    let handle = if matches!(state, State::Paused) {
+        ctx.send(reply.components(buttons)).await?
+    } else {
+        ctx.send(reply).await?
+    };
+    if !matches!(state, State::Paused) {
+        return Ok(());
+    }
+
+    // Match whole ids, another invocation's id can start with this one's
+    let ids = [
+        step_id.clone(),
+        step100_id.clone(),
+        breakpoint_id.clone(),
+        continue_id.clone(),
+    ];
+    while let Some(press) = serenity::ComponentInteractionCollector::new(ctx)
+        .filter({
+            let ids = ids.clone();
+            move |press| ids.contains(&press.data.custom_id)
+        })
+        .timeout(SESSION_TIMEOUT)
+        .await
+    {
+        if press.user.id != ctx.author().id {
+            let message = serenity::CreateInteractionResponseMessage::new()
+                .content("Only the person debugging this program can step it")
+                .ephemeral(true);
+            press
+                .create_response(
+                    ctx.serenity_context(),
+                    serenity::CreateInteractionResponse::Message(message),
+                )
+                .await?;
+            continue;
+        }
+        let (max_ops, stop_at_breakpoint) = if press.data.custom_id == step_id {
+            (1, false)
+        } else if press.data.custom_id == step100_id {
+            (100, false)
+        } else if press.data.custom_id == breakpoint_id {
+            (usize::MAX, true)
+        } else if press.data.custom_id == continue_id {
+            (usize::MAX, false)
+        } else {
+            continue;
+        };
+
+        let result = if max_ops == usize::MAX {
+            // Unbounded runs go through the sandbox like any other program
+            let ticket = match ctx.data().sandbox.admit(ctx.author().id) {
+                Ok(ticket) => ticket,
+                Err(e) => {
+                    let message = serenity::CreateInteractionResponseMessage::new()
+                        .content(e.to_string())
+                        .ephemeral(true);
+                    press
+                        .create_response(
+                            ctx.serenity_context(),
+                            serenity::CreateInteractionResponse::Message(message),
+                        )
+                        .await?;
+                    continue;
+                }
+            };
+            let mut running = machine;
+            let job = ticket
+                .run(move |cancel| {
+                    running.budget.watch(cancel);
+                    let result = running.run(max_ops, stop_at_breakpoint);
+                    (running, result)
+                })
+                .await;
+            match job {
+                Ok((resumed, result)) => {
+                    machine = resumed;
+                    result
+                }
+                Err(e) => {
+                    // The machine was lost with the job, so the session cannot go on
+                    let message = serenity::CreateInteractionResponseMessage::new()
+                        .content(format!("Error executing Brainfuck code: {}", e))
+                        .components(Vec::new());
+                    press
+                        .create_response(
+                            ctx.serenity_context(),
+                            serenity::CreateInteractionResponse::UpdateMessage(message),
+                        )
+                        .await?;
+                    return Ok(());
+                }
+            }
+        } else {
+            machine.run(max_ops, stop_at_breakpoint)
+        };
+
+        state = match result {
+            Err(e) => State::Failed(e),
+            Ok(()) if machine.is_finished() => State::Finished,
+            Ok(()) => State::Paused,
+        };
+        let mut message = serenity::CreateInteractionResponseMessage::new()
+            .embed(render(&machine, &source, &state));
+        if !matches!(state, State::Paused) {
+            message = message.components(Vec::new());
+        }
+        press
+            .create_response(
+                ctx.serenity_context(),
+                serenity::CreateInteractionResponse::UpdateMessage(message),
+            )
+            .await?;
+        if !matches!(state, State::Paused) {
+            return Ok(());
+        }
+    }
+
+    // Timed out, the session state is dropped so disable the buttons
+    handle
+        .edit(
+            ctx,
+            poise::CreateReply::default()
+                .embed(render(&machine, &source, &state))
+                .components(Vec::new()),
+        )
+        .await?;
+    Ok(())
+}
+
+fn render(machine: &Machine, source: &[char], state: &State) -> serenity::CreateEmbed {
+    let status = match state {
+        State::Paused => "Paused".to_string(),
+        State::Finished => "Finished".to_string(),
+        State::Failed(e) => format!("Error: {}", e),
+    };
+    let output = if machine.output.is_empty() {
+        "(none)".to_string()
+    } else {
+        let text = String::from_utf8_lossy(&machine.output);
+        let chars: Vec<char> = text.chars().collect();
+        let shown: String = chars[chars.len().saturating_sub(MAX_OUTPUT_SHOWN)..]
+            .iter()
+            .collect();
+        format!("```\n{}\n```", shown.replace("```", "`\u{200b}``"))
+    };
+
+    serenity::CreateEmbed::new()
+        .title("Brainfuck debugger")
+        .field(
+            "Code",
+            format!("```\n{}\n```", code_window(machine, source)),
+            false,
+        )
+        .field("Tape", format!("```\n{}\n```", tape_window(machine)), false)
+        .field("Output", output, false)
+        .footer(serenity::CreateEmbedFooter::new(format!(
+            "{} | steps: {}",
+            status,
+            machine.budget.steps()
+        )))
+}
+
+/// The source around the next instruction, with a caret under it
+fn code_window(machine: &Machine, source: &[char]) -> String {
+    let pos = match machine.program.get(machine.pc) {
+        Some(instr) => instr.pos,
+        None => source.len(),
+    };
+    let start = pos.saturating_sub(CODE_WINDOW);
+    let end = (pos + CODE_WINDOW + 1).min(source.len());
+    let line: String = source[start..end]
+        .iter()
+        .map(|&c| if c.is_whitespace() { ' ' } else { c })
+        .collect();
+    format!("{}\n{}^", line, " ".repeat(pos - start))
+}
+
+/// Cells around the data pointer with their indices, the current cell marked with a caret
+fn tape_window(machine: &Machine) -> String {
+    let start = machine.pointer.saturating_sub(TAPE_WINDOW);
+    let end = (machine.pointer + TAPE_WINDOW + 1).min(machine.memory.len());
+    let width = (start..end)
+        .map(|i| i.to_string().len().max(machine.memory[i].to_string().len()))
+        .max()
+        .unwrap_or(1)
+        + 1;
+
+    let mut indices = String::new();
+    let mut values = String::new();
+    let mut marker = String::new();
+    for i in start..end {
+        indices.push_str(&format!("{:>width$}", i));
+        values.push_str(&format!("{:>width$}", machine.memory[i]));
+        let mark = if i == machine.pointer { "^" } else { "" };
+        marker.push_str(&format!("{:>width$}", mark));
+    }
+    format!("{}\n{}\n{}", indices, values, marker.trim_end())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn machine(code: &str) -> Machine {
+        Machine::new(
+            compile(code, true).unwrap(),
+            vec![],
+            Dialect::default(),
+            Budget::default(),
+        )
+    }
+
+    #[test]
+    fn test_debug_compile_does_not_fold() {
+        let mut m = machine("+++[-]");
+        m.run(3, false).unwrap();
+        assert_eq!(m.memory[0], 3);
+        assert_eq!(m.budget.steps(), 3);
+    }
+
+    #[test]
+    fn test_run_to_breakpoint() {
+        let mut m = machine("++#>+#+");
+        m.run(usize::MAX, true).unwrap();
+        assert_eq!(m.program[m.pc].pos, 2);
+        m.run(usize::MAX, true).unwrap();
+        assert_eq!(m.program[m.pc].pos, 5);
+        assert_eq!((m.pointer, m.memory[1]), (1, 1));
+        m.run(usize::MAX, true).unwrap();
+        assert!(m.is_finished());
+    }
+
+    #[test]
+    fn test_code_window() {
+        let mut m = machine("+>\n-");
+        let source: Vec<char> = "+>\n-".chars().collect();
+        m.run(2, false).unwrap();
+        assert_eq!(code_window(&m, &source), "+> -\n   ^");
+    }
+
+    #[test]
+    fn test_tape_window() {
+        let mut m = machine(">>++++++++++<");
+        m.run(usize::MAX, false).unwrap();
+        assert_eq!(
+            tape_window(&m),
+            "  0  1  2  3  4  5  6  7\n  0  0 10  0  0  0  0  0\n     ^"
+        );
+    }
+}
@@ -0,0 +1,492 @@
+use super::{interpret_brainfuck, Dialect};
+use crate::context::{Context, Error};
+use crate::esolang::Budget;
+use crate::store::JsonStore;
+use poise::serenity_prelude as serenity;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+pub const CHALLENGES_FILE: &str = "challenges.json";
+const LEADERBOARD_SIZE: usize = 10;
+const MAX_CASES: usize = 25;
+const MAX_NAME_LENGTH: usize = 32;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Case {
+    pub input: String,
+    pub expected: String,
+}
+
+/// A user's best accepted submission
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    pub user: u64,
+    pub name: String,
+    pub length: usize,
+    pub steps: usize,
+    pub code: String,
+}
+
+impl Entry {
+    /// Shorter code wins, fewer steps break ties
+    fn score(&self) -> (usize, usize) {
+        (self.length, self.steps)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Challenge {
+    pub description: String,
+    pub cases: Vec<Case>,
+    /// Best entry of every user, best first
+    pub entries: Vec<Entry>,
+}
+
+impl Challenge {
+    /// Keep `entry` if it beats the user's previous best, returning whether it did
+    fn record(&mut self, entry: Entry) -> bool {
+        if let Some(i) = self.entries.iter().position(|e| e.user == entry.user) {
+            if self.entries[i].score() <= entry.score() {
+                return false;
+            }
+            self.entries.remove(i);
+        }
+        let at = self.entries.partition_point(|e| e.score() <= entry.score());
+        self.entries.insert(at, entry);
+        true
+    }
+
+    /// Keep `entry` like `record`, unless the cases changed from `judged`,
+    /// the ones it passed: its leaderboard was reset since
+    fn record_judged(&mut self, entry: Entry, judged: &[Case]) -> Result<bool, String> {
+        if self.cases != judged {
+            return Err(
+                "The test cases changed while your solution was being judged, submit it again"
+                    .to_string(),
+            );
+        }
+        Ok(self.record(entry))
+    }
+
+    /// 1-based leaderboard position of `user`
+    fn rank(&self, user: u64) -> Option<usize> {
+        self.entries
+            .iter()
+            .position(|e| e.user == user)
+            .map(|i| i + 1)
+    }
+}
+
+type GuildChallenges = BTreeMap<String, Challenge>;
+
+/// Challenges of every guild
+pub type Challenges = JsonStore<GuildChallenges>;
+
+fn not_found(name: &str) -> String {
+    format!("There is no challenge named '{}'", name)
+}
+
+/// Number of Brainfuck instructions in `code`; comments are free
+fn code_length(code: &str) -> usize {
+    code.chars().filter(|c| "+-<>[].,".contains(*c)).count()
+}
+
+/// Expand `\n`, `\t` and `\\`, since slash command options are single-line
+fn unescape(text: &str) -> String {
+    let mut result = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some('\\') => result.push('\\'),
+            Some(other) => result.extend(['\\', other]),
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+/// Run `code` against every case, returning the total steps taken or why it
+/// failed. All cases share `budget`, so the step limit covers the whole run.
+fn judge(code: &str, cases: &[Case], mut budget: Budget) -> Result<usize, String> {
+    for (i, case) in cases.iter().enumerate() {
+        let inputs = case.input.bytes().map(u32::from).collect();
+        let output =
+            interpret_brainfuck(code.to_string(), inputs, &Dialect::default(), &mut budget)
+                .map_err(|(e, _)| format!("Case {} failed: {}", i + 1, e))?;
+        if output != case.expected.as_bytes() {
+            return Err(format!(
+                "Case {} failed: expected {:?}, got {:?}",
+                i + 1,
+                case.expected,
+                String::from_utf8_lossy(&output)
+            ));
+        }
+    }
+    Ok(budget.steps())
+}
+
+/// Submit a solution to a golf challenge
+#[poise::command(slash_command, guild_only)]
+pub async fn submit(
+    ctx: Context<'_>,
+    #[description = "Challenge name"] challenge: String,
+    #[description = "code"] code: String,
+) -> Result<(), Error> {
+    let Some(guild) = ctx.guild_id() else {
+        return Ok(());
+    };
+    let challenges = &ctx.data().challenges;
+    let cases = match challenges.view(guild, |c| c.get(&challenge).map(|c| c.cases.clone())) {
+        Some(cases) if cases.is_empty() => {
+            ctx.say(format!("'{}' has no test cases yet", challenge))
+                .await?;
+            return Ok(());
+        }
+        Some(cases) => cases,
+        None => {
+            ctx.say(not_found(&challenge)).await?;
+            return Ok(());
+        }
+    };
+
+    let case_count = cases.len();
+    let judged = cases.clone();
+    let job_code = code.clone();
+    let job = move |cancel| judge(&job_code, &cases, Budget::new(cancel));
+    let steps = match ctx.data().sandbox.run(ctx.author().id, job).await {
+        Ok(Ok(steps)) => steps,
+        Ok(Err(e)) => {
+            ctx.say(e).await?;
+            return Ok(());
+        }
+        Err(e) => {
+            ctx.say(format!("Error executing Brainfuck code: {}", e))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let user = ctx.author().id.get();
+    let entry = Entry {
+        user,
+        name: ctx.author().name.clone(),
+        length: code_length(&code),
+        steps,
+        code,
+    };
+    let (length, steps) = entry.score();
+    let recorded = challenges.update(guild, |c| {
+        let challenge = c.get_mut(&challenge).ok_or_else(|| not_found(&challenge))?;
+        let improved = challenge.record_judged(entry, &judged)?;
+        // Recording always leaves an entry for the user
+        let rank = challenge.rank(user).unwrap_or(1);
+        Ok((improved, challenge.entries[rank - 1].score(), rank))
+    });
+    let message = match recorded {
+        Ok((true, _, rank)) => format!(
+            "Passed all {} cases with {} characters in {} steps. New personal best, you are #{}!",
+            case_count, length, steps, rank
+        ),
+        Ok((false, (best_length, best_steps), rank)) => format!(
+            "Passed all {} cases with {} characters in {} steps. Your best is still {} characters in {} steps (#{}).",
+            case_count, length, steps, best_length, best_steps, rank
+        ),
+        Err(e) => e,
+    };
+    ctx.say(message).await?;
+    Ok(())
+}
+
+/// Show the best solutions to a golf challenge
+#[poise::command(slash_command, guild_only)]
+pub async fn leaderboard(
+    ctx: Context<'_>,
+    #[description = "Challenge name"] challenge: String,
+) -> Result<(), Error> {
+    let Some(guild) = ctx.guild_id() else {
+        return Ok(());
+    };
+    let embed = ctx.data().challenges.view(guild, |c| {
+        c.get(&challenge).map(|c| leaderboard_embed(&challenge, c))
+    });
+    match embed {
+        Some(embed) => ctx.send(poise::CreateReply::default().embed(embed)).await?,
+        None => ctx.say(not_found(&challenge)).await?,
+    };
+    Ok(())
+}
+
+fn leaderboard_embed(name: &str, challenge: &Challenge) -> serenity::CreateEmbed {
+    let standings = if challenge.entries.is_empty() {
+        "No solutions yet".to_string()
+    } else {
+        challenge
+            .entries
+            .iter()
+            .take(LEADERBOARD_SIZE)
+            .enumerate()
+            .map(|(i, e)| {
+                format!(
+                    "{}. {} — {} characters, {} steps",
+                    i + 1,
+                    e.name,
+                    e.length,
+                    e.steps
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    serenity::CreateEmbed::new()
+        .title(format!("{} leaderboard", name))
+        .description(&challenge.description)
+        .field("Standings", standings, false)
+        .footer(serenity::CreateEmbedFooter::new(
+            "Shorter code wins, fewer steps break ties",
+        ))
+}
+
+/// Manage golf challenges
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands("create", "add_case", "delete", "list"),
+    subcommand_required
+)]
+pub async fn challenge(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Create a golf challenge
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+pub async fn create(
+    ctx: Context<'_>,
+    #[description = "Challenge name"] name: String,
+    #[description = "What the program should do"] description: String,
+) -> Result<(), Error> {
+    let Some(guild) = ctx.guild_id() else {
+        return Ok(());
+    };
+    let name = name.trim().to_string();
+    let result = if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+        Err(format!(
+            "Challenge names must be 1 to {} characters long",
+            MAX_NAME_LENGTH
+        ))
+    } else {
+        ctx.data().challenges.update(guild, |c| {
+            if c.contains_key(&name) {
+                return Err(format!("A challenge named '{}' already exists", name));
+            }
+            c.insert(
+                name.clone(),
+                Challenge {
+                    description,
+                    ..Challenge::default()
+                },
+            );
+            Ok(format!(
+                "Created '{}', add test cases with `/brainfuck challenge add-case`",
+                name
+            ))
+        })
+    };
+    ctx.say(result.unwrap_or_else(|e| e)).await?;
+    Ok(())
+}
+
+/// Add a test case to a golf challenge; use \n for newlines
+#[poise::command(
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    rename = "add-case"
+)]
+pub async fn add_case(
+    ctx: Context<'_>,
+    #[description = "Challenge name"] challenge: String,
+    #[description = "Input fed to the program"] input: Option<String>,
+    #[description = "Output the program must produce"] expected: String,
+) -> Result<(), Error> {
+    let Some(guild) = ctx.guild_id() else {
+        return Ok(());
+    };
+    let case = Case {
+        input: unescape(&input.unwrap_or_default()),
+        expected: unescape(&expected),
+    };
+    let result = ctx.data().challenges.update(guild, |c| {
+        let c = c.get_mut(&challenge).ok_or_else(|| not_found(&challenge))?;
+        if c.cases.len() >= MAX_CASES {
+            return Err(format!("Challenges can have at most {} cases", MAX_CASES));
+        }
+        c.cases.push(case);
+        // Earlier solutions were not checked against the new case
+        c.entries.clear();
+        Ok(format!(
+            "'{}' now has {} cases, its leaderboard was reset",
+            challenge,
+            c.cases.len()
+        ))
+    });
+    ctx.say(result.unwrap_or_else(|e| e)).await?;
+    Ok(())
+}
+
+/// Delete a golf challenge and its leaderboard
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+pub async fn delete(
+    ctx: Context<'_>,
+    #[description = "Challenge name"] challenge: String,
+) -> Result<(), Error> {
+    let Some(guild) = ctx.guild_id() else {
+        return Ok(());
+    };
+    let result = ctx.data().challenges.update(guild, |c| {
+        c.remove(&challenge)
+            .map(|_| format!("Deleted '{}'", challenge))
+            .ok_or_else(|| not_found(&challenge))
+    });
+    ctx.say(result.unwrap_or_else(|e| e)).await?;
+    Ok(())
+}
+
+/// List the golf challenges of this server
+#[poise::command(slash_command, guild_only)]
+pub async fn list(ctx: Context<'_>) -> Result<(), Error> {
+    let Some(guild) = ctx.guild_id() else {
+        return Ok(());
+    };
+    let lines: Vec<String> = ctx.data().challenges.view(guild, |c| {
+        c.iter()
+            .map(|(name, c)| {
+                format!(
+                    "**{}** ({} cases, {} solvers): {}",
+                    name,
+                    c.cases.len(),
+                    c.entries.len(),
+                    c.description
+                )
+            })
+            .collect()
+    });
+    if lines.is_empty() {
+        ctx.say("There are no challenges yet").await?;
+    } else {
+        ctx.say(lines.join("\n")).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(user: u64, length: usize, steps: usize) -> Entry {
+        Entry {
+            user,
+            name: format!("user{}", user),
+            length,
+            steps,
+            code: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_code_length_ignores_comments() {
+        assert_eq!(code_length("+[->+<] add one\n."), 8);
+    }
+
+    #[test]
+    fn test_unescape() {
+        assert_eq!(unescape(r"a\nb\tc\\n\x\"), "a\nb\tc\\n\\x\\");
+    }
+
+    #[test]
+    fn test_judge() {
+        let cases = vec![
+            Case {
+                input: "a".to_string(),
+                expected: "b".to_string(),
+            },
+            Case {
+                input: "y".to_string(),
+                expected: "z".to_string(),
+            },
+        ];
+        assert_eq!(judge(",+.", &cases, Budget::default()), Ok(6));
+        assert_eq!(
+            judge(",.", &cases, Budget::default()),
+            Err("Case 1 failed: expected \"b\", got \"a\"".to_string())
+        );
+        assert_eq!(
+            judge(",,", &cases, Budget::default()),
+            Err("Case 1 failed: Not enough input values provided".to_string())
+        );
+    }
+
+    #[test]
+    fn test_record_keeps_best_per_user() {
+        let mut challenge = Challenge::default();
+        assert!(challenge.record(entry(1, 10, 50)));
+        assert!(challenge.record(entry(2, 8, 90)));
+        assert!(challenge.record(entry(3, 10, 40)));
+        // Worse than the previous best of user 1
+        assert!(!challenge.record(entry(1, 10, 60)));
+        assert!(challenge.record(entry(1, 7, 100)));
+
+        let order: Vec<u64> = challenge.entries.iter().map(|e| e.user).collect();
+        assert_eq!(order, vec![1, 2, 3]);
+        assert_eq!(challenge.rank(3), Some(3));
+        assert_eq!(challenge.rank(4), None);
+    }
+
+    #[test]
+    fn test_record_judged() {
+        let case = |input: &str| Case {
+            input: input.to_string(),
+            expected: input.to_string(),
+        };
+        let mut challenge = Challenge {
+            cases: vec![case("a")],
+            ..Challenge::default()
+        };
+        let judged = challenge.cases.clone();
+        assert_eq!(challenge.record_judged(entry(1, 4, 8), &judged), Ok(true));
+
+        // A case added while judging resets the leaderboard, which must stay empty
+        challenge.cases.push(case("b"));
+        challenge.entries.clear();
+        assert!(challenge.record_judged(entry(2, 3, 8), &judged).is_err());
+        assert!(challenge.entries.is_empty());
+    }
+
+    #[test]
+    fn test_challenges_persist() {
+        let path = std::env::temp_dir().join(format!("bytie-golf-{}.json", std::process::id()));
+        let guild = serenity::GuildId::new(7);
+        let challenges = Challenges::load(&path).unwrap();
+        challenges
+            .update(guild, |c| {
+                c.entry("echo".to_string())
+                    .or_default()
+                    .record(entry(1, 4, 8));
+                Ok(())
+            })
+            .unwrap();
+
+        let reloaded = Challenges::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            reloaded.view(guild, |c| c["echo"].entries.clone()),
+            vec![entry(1, 4, 8)]
+        );
+        assert!(reloaded.view(serenity::GuildId::new(8), |c| c.is_empty()));
+    }
+}
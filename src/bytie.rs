@@ -5,4 +5,4 @@ use crate::context::{Context, Error};
 pub async fn bytie(ctx: Context<'_>) -> Result<(), Error> {
     ctx.say("Hey, I am the Rust version of the Bytie bot, originally created as a Python bot during the worldwide COVID-19 pandemic.").await?;
     Ok(())
-}
\ No newline at end of file
+}
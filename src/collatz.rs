@@ -1,10 +1,21 @@
 use crate::context::{Context, Error};
+use crate::sequence::{abbreviate, sequence_reply};
+use num_bigint::BigUint;
+use num_traits::ToPrimitive;
 
-fn collatz_sequence(n: u64) -> Vec<u64> {
+mod range;
+
+const MAX_DIGITS: usize = 1000;
+// Starting values with more digits are left out of file names
+const MAX_NAMED_DIGITS: usize = 40;
+
+/// Collatz sequence from `n` down to 1, or an error if a step does not fit
+/// in 64 bits
+pub(crate) fn collatz_sequence(n: u64) -> Result<Vec<u64>, String> {
     let mut sequence: Vec<u64> = Vec::new();
 
     if n < 1 {
-        return sequence;
+        return Ok(sequence);
     }
 
     let mut x = n;
@@ -12,25 +23,144 @@ fn collatz_sequence(n: u64) -> Vec<u64> {
     sequence.push(x);
 
     while x > 1 {
-        x = if x % 2 == 0 { x / 2 } else { 3 * x + 1 };
+        x = if x.is_multiple_of(2) {
+            x / 2
+        } else {
+            x.checked_mul(3)
+                .and_then(|y| y.checked_add(1))
+                .ok_or_else(|| format!("3 × {} + 1 does not fit in 64 bits", x))?
+        };
         sequence.push(x);
     }
-    return sequence;
+    Ok(sequence)
+}
+
+/// Collatz sequence from `n` down to 1 in big integers, which cannot overflow
+pub(crate) fn big_collatz_sequence(n: &BigUint) -> Vec<BigUint> {
+    let mut sequence = Vec::new();
+    let one = BigUint::from(1u32);
+    if *n < one {
+        return sequence;
+    }
+    let mut x = n.clone();
+    sequence.push(x.clone());
+    while x > one {
+        x = if x.bit(0) { x * 3u32 + 1u32 } else { x >> 1 };
+        sequence.push(x.clone());
+    }
+    sequence
+}
+
+/// Sequence from `n`, computed in machine integers when they are wide enough
+fn trajectory(n: &BigUint) -> Vec<BigUint> {
+    match n.to_u64().map(collatz_sequence) {
+        Some(Ok(sequence)) => sequence.into_iter().map(BigUint::from).collect(),
+        _ => big_collatz_sequence(n),
+    }
+}
+
+/// How a Collatz sequence gets down to 1
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Summary {
+    /// Steps taken to reach 1
+    pub stopping_time: usize,
+    /// Steps taken from an odd number, by 3x + 1
+    pub odd_steps: usize,
+    /// Steps taken from an even number, by halving
+    pub even_steps: usize,
+}
+
+pub(crate) fn summarize(sequence: &[BigUint]) -> Option<Summary> {
+    let stopping_time = sequence.len().checked_sub(1)?;
+    let odd_steps = sequence[..stopping_time]
+        .iter()
+        .filter(|x| x.bit(0))
+        .count();
+    Some(Summary {
+        stopping_time,
+        odd_steps,
+        even_steps: stopping_time - odd_steps,
+    })
+}
+
+fn parse_start(n: &str) -> Result<BigUint, String> {
+    let digits = n.trim();
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Err("Give a positive integer written in decimal digits".to_string());
+    }
+    if digits.trim_start_matches('0').len() > MAX_DIGITS {
+        return Err(format!("Give a number with at most {} digits", MAX_DIGITS));
+    }
+    let n: BigUint = digits
+        .parse()
+        .map_err(|e| format!("Invalid number: {}", e))?;
+    if n == BigUint::from(0u32) {
+        return Err("Give a positive integer".to_string());
+    }
+    Ok(n)
+}
+
+/// Explore Collatz sequences
+#[poise::command(
+    slash_command,
+    subcommands("sequence", "range::range"),
+    subcommand_required
+)]
+pub async fn collatz(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
 }
 
 /// Returns the Collatz sequence for a given pozitive integer number
 #[poise::command(slash_command)]
-pub async fn collatz(
+pub async fn sequence(
     ctx: Context<'_>,
-    #[description = "Positive integer"] n: u64,
+    #[description = "Positive integer, as many digits as you like"] n: String,
+    #[description = "Only show the summary, without the chart and the sequence"] summary: Option<
+        bool,
+    >,
 ) -> Result<(), Error> {
-    let sequence = collatz_sequence(n);
-    let outstr = format!("Collatz sequence for {}: {:?}", n, sequence);
-    ctx.say(outstr).await?;
+    let n = match parse_start(&n) {
+        Ok(n) => n,
+        Err(e) => {
+            ctx.say(e).await?;
+            return Ok(());
+        }
+    };
+    let summary_only = summary.unwrap_or(false);
+    let job = move |_| collatz_reply(&n, &trajectory(&n), summary_only);
+    match ctx.data().sandbox.run(ctx.author().id, job).await {
+        Ok(reply) => ctx.send(reply).await?,
+        Err(e) => ctx.say(e.to_string()).await?,
+    };
     Ok(())
 }
 
-
+/// Summary of the sequence, with a chart of it and the sequence itself
+fn collatz_reply(n: &BigUint, sequence: &[BigUint], summary_only: bool) -> poise::CreateReply {
+    let Some(summary) = summarize(sequence) else {
+        return poise::CreateReply::default().content("Give a positive integer");
+    };
+    let fields = vec![
+        (
+            "Total stopping time",
+            format!("{} steps", summary.stopping_time),
+        ),
+        (
+            "Steps",
+            format!(
+                "{} odd (3x + 1), {} even (x / 2)",
+                summary.odd_steps, summary.even_steps
+            ),
+        ),
+    ];
+    let stem = if n.to_string().len() <= MAX_NAMED_DIGITS {
+        format!("collatz_{}", n)
+    } else {
+        "collatz".to_string()
+    };
+    let title = format!("Collatz sequence for {}", abbreviate(n));
+    sequence_reply(&title, &stem, sequence, fields, summary_only)
+}
 
 #[cfg(test)]
 mod tests {
@@ -38,10 +168,97 @@ mod tests {
 
     #[test]
     fn test_collatz_sequence() {
-        assert_eq!(collatz_sequence(0), Vec::<u64>::new());
-        assert_eq!(collatz_sequence(1), vec![1]);
-        assert_eq!(collatz_sequence(2), vec![2, 1]);
-        assert_eq!(collatz_sequence(3), vec![3, 10, 5, 16, 8, 4, 2, 1]);
-        assert_eq!(collatz_sequence(4), vec![4, 2, 1]);
+        assert_eq!(collatz_sequence(0), Ok(Vec::<u64>::new()));
+        assert_eq!(collatz_sequence(1), Ok(vec![1]));
+        assert_eq!(collatz_sequence(2), Ok(vec![2, 1]));
+        assert_eq!(collatz_sequence(3), Ok(vec![3, 10, 5, 16, 8, 4, 2, 1]));
+        assert_eq!(collatz_sequence(4), Ok(vec![4, 2, 1]));
+        assert_eq!(
+            collatz_sequence(u64::MAX),
+            Err(format!("3 × {} + 1 does not fit in 64 bits", u64::MAX))
+        );
+    }
+
+    #[test]
+    fn test_big_collatz_sequence() {
+        let expected: Vec<BigUint> = collatz_sequence(77031)
+            .unwrap()
+            .into_iter()
+            .map(BigUint::from)
+            .collect();
+        assert_eq!(big_collatz_sequence(&BigUint::from(77031u32)), expected);
+        assert!(big_collatz_sequence(&BigUint::from(0u32)).is_empty());
+
+        // Overflows on the first step, so it goes through big integers
+        let n = BigUint::from(u64::MAX);
+        let sequence = trajectory(&n);
+        assert_eq!(sequence[1], n * 3u32 + 1u32);
+        assert_eq!(sequence.last(), Some(&BigUint::from(1u32)));
+    }
+
+    #[test]
+    fn test_parse_start() {
+        assert_eq!(parse_start(" 27 "), Ok(BigUint::from(27u32)));
+        let big = "1".repeat(MAX_DIGITS);
+        assert_eq!(parse_start(&big).map(|n| n.to_string()), Ok(big.clone()));
+        assert!(parse_start(&format!("{}1", big)).is_err());
+        assert!(parse_start("0").is_err());
+        assert!(parse_start("-5").is_err());
+        assert!(parse_start("1e9").is_err());
+        assert!(parse_start("").is_err());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_summarize() {
+        assert_eq!(summarize(&[]), None);
+        assert_eq!(
+            summarize(&trajectory(&BigUint::from(27u32))),
+            Some(Summary {
+                stopping_time: 111,
+                odd_steps: 41,
+                even_steps: 70,
+            })
+        );
+        assert_eq!(
+            summarize(&[BigUint::from(1u32)]),
+            Some(Summary {
+                stopping_time: 0,
+                odd_steps: 0,
+                even_steps: 0,
+            })
+        );
+    }
+
+    #[test]
+    fn test_collatz_reply() {
+        let reply_for = |n: u64, summary_only| {
+            let n = BigUint::from(n);
+            collatz_reply(&n, &trajectory(&n), summary_only)
+        };
+        let reply = reply_for(3, false);
+        assert_eq!(
+            reply.content.as_deref(),
+            Some("Collatz sequence for 3: [3, 10, 5, 16, 8, 4, 2, 1]")
+        );
+        assert_eq!(reply.attachments.len(), 1);
+        assert_eq!(reply.attachments[0].filename, "collatz_3.png");
+
+        let reply = reply_for(77031, false);
+        assert_eq!(
+            reply.content.as_deref(),
+            Some("The sequence has 351 numbers and is attached.")
+        );
+        assert_eq!(reply.attachments.len(), 2);
+        assert_eq!(reply.attachments[1].filename, "collatz_77031.txt");
+
+        let reply = reply_for(77031, true);
+        assert_eq!(reply.content, None);
+        assert!(reply.attachments.is_empty());
+        assert_eq!(reply.embeds.len(), 1);
+
+        let n: BigUint = "9".repeat(400).parse().unwrap();
+        let reply = collatz_reply(&n, &trajectory(&n), false);
+        assert_eq!(reply.attachments.len(), 2);
+        assert_eq!(reply.attachments[1].filename, "collatz.txt");
+    }
+}
@@ -0,0 +1,240 @@
+use crate::context::{Context, Error};
+use crate::sandbox::CancelToken;
+use std::sync::atomic::{AtomicU16, AtomicU64, Ordering};
+
+// Largest range explored, so a search ends well within the sandbox timeout
+const MAX_RANGE_SIZE: u64 = 10_000_000;
+// Numbers a worker takes at a time
+const CHUNK_SIZE: u64 = 4096;
+const MAX_THREADS: usize = 8;
+
+/// Numbers of a range with the longest sequence and the highest peak, the
+/// smaller number winning ties
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Records {
+    /// A number and its stopping time
+    longest: (u64, u32),
+    /// A number and the highest value its sequence reaches
+    highest: (u64, u128),
+}
+
+impl Records {
+    fn merge(self, other: Records) -> Records {
+        Records {
+            longest: best(self.longest, other.longest),
+            highest: best(self.highest, other.highest),
+        }
+    }
+}
+
+fn best<T: Ord>(a: (u64, T), b: (u64, T)) -> (u64, T) {
+    if b.1 > a.1 || (b.1 == a.1 && b.0 < a.0) {
+        b
+    } else {
+        a
+    }
+}
+
+/// Stopping time and peak of `n`, which is in the range starting at `from`.
+///
+/// The walk ends early at a smaller number of the range whose stopping time
+/// is in `cache`. The peak is then only that of the walk so far, which is
+/// enough for the records: a higher peak further on is also the peak of the
+/// smaller number, which wins the tie.
+fn walk(n: u64, from: u64, cache: &[AtomicU16]) -> Result<(u32, u128), String> {
+    let start = u128::from(n);
+    let mut x = start;
+    let mut steps = 0;
+    let mut peak = x;
+    while x > 1 {
+        x = if x % 2 == 0 {
+            x / 2
+        } else {
+            x.checked_mul(3)
+                .and_then(|y| y.checked_add(1))
+                .ok_or_else(|| format!("The sequence of {} does not fit in 128 bits", n))?
+        };
+        steps += 1;
+        peak = peak.max(x);
+        if x < start && x >= u128::from(from) {
+            // Zero means not computed yet, otherwise one more than the stopping time
+            let known = cache[(x - u128::from(from)) as usize].load(Ordering::Relaxed);
+            if known > 0 {
+                return Ok((steps + u32::from(known) - 1, peak));
+            }
+        }
+    }
+    Ok((steps, peak))
+}
+
+/// Take chunks of the range until it is done, keeping the best records seen
+fn worker(
+    from: u64,
+    cache: &[AtomicU16],
+    next: &AtomicU64,
+    cancel: &CancelToken,
+) -> Result<Option<Records>, String> {
+    let size = cache.len() as u64;
+    let mut records: Option<Records> = None;
+    loop {
+        if cancel.is_cancelled() {
+            return Err("Ran out of time, try a smaller range".to_string());
+        }
+        let start = next.fetch_add(CHUNK_SIZE, Ordering::Relaxed);
+        if start >= size {
+            return Ok(records);
+        }
+        for i in start..(start + CHUNK_SIZE).min(size) {
+            let n = from + i;
+            let (steps, peak) = walk(n, from, cache)?;
+            if let Ok(known) = u16::try_from(steps + 1) {
+                cache[i as usize].store(known, Ordering::Relaxed);
+            }
+            let found = Records {
+                longest: (n, steps),
+                highest: (n, peak),
+            };
+            records = Some(records.map_or(found, |r| r.merge(found)));
+        }
+    }
+}
+
+/// Record holders of every number from `from` to `to`, searched on up to
+/// `threads` threads sharing a cache of stopping times
+fn explore(from: u64, to: u64, threads: usize, cancel: &CancelToken) -> Result<Records, String> {
+    if from == 0 || from > to {
+        return Err(
+            "Give a range of positive integers, the first no larger than the last".to_string(),
+        );
+    }
+    if to - from >= MAX_RANGE_SIZE {
+        return Err(format!(
+            "Ranges can have at most {} numbers",
+            MAX_RANGE_SIZE
+        ));
+    }
+    let cache: Vec<AtomicU16> = (from..=to).map(|_| AtomicU16::new(0)).collect();
+    let next = AtomicU64::new(0);
+    let threads = threads.clamp(1, MAX_THREADS);
+    let found = std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| scope.spawn(|| worker(from, &cache, &next, cancel)))
+            .collect();
+        workers
+            .into_iter()
+            .map(|w| w.join().unwrap_or_else(|e| std::panic::resume_unwind(e)))
+            .collect::<Result<Vec<_>, String>>()
+    })?;
+    found
+        .into_iter()
+        .flatten()
+        .reduce(Records::merge)
+        .ok_or_else(|| "The range is empty".to_string())
+}
+
+/// Find the numbers of a range with the longest Collatz sequence and the
+/// highest peak
+#[poise::command(slash_command)]
+pub async fn range(
+    ctx: Context<'_>,
+    #[description = "First number of the range"]
+    #[min = 1]
+    from: u64,
+    #[description = "Last number of the range"]
+    #[min = 1]
+    to: u64,
+) -> Result<(), Error> {
+    let threads = ctx.data().sandbox.threads_per_job();
+    let job = move |cancel: CancelToken| explore(from, to, threads, &cancel);
+    let reply = match ctx.data().sandbox.run(ctx.author().id, job).await {
+        Ok(Ok(records)) => format!(
+            "From {} to {}:\n\
+             - Longest sequence: {} reaches 1 in {} steps\n\
+             - Highest peak: {} climbs to {}",
+            from, to, records.longest.0, records.longest.1, records.highest.0, records.highest.1
+        ),
+        Ok(Err(e)) => e,
+        Err(e) => e.to_string(),
+    };
+    ctx.say(reply).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collatz::collatz_sequence;
+
+    fn search(from: u64, to: u64) -> Records {
+        explore(from, to, MAX_THREADS, &CancelToken::default()).unwrap()
+    }
+
+    /// Records found by walking every sequence in full
+    fn naive(from: u64, to: u64) -> Records {
+        (from..=to)
+            .map(|n| {
+                let sequence = collatz_sequence(n).unwrap();
+                Records {
+                    longest: (n, sequence.len() as u32 - 1),
+                    highest: (n, u128::from(*sequence.iter().max().unwrap())),
+                }
+            })
+            .reduce(Records::merge)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_known_records() {
+        assert_eq!(
+            search(1, 10),
+            Records {
+                longest: (9, 19),
+                highest: (7, 52),
+            }
+        );
+        assert_eq!(
+            search(1, 100),
+            Records {
+                longest: (97, 118),
+                highest: (27, 9232),
+            }
+        );
+        assert_eq!(search(1, 100_000).longest, (77031, 350));
+        assert_eq!(
+            search(1, 1),
+            Records {
+                longest: (1, 0),
+                highest: (1, 1),
+            }
+        );
+    }
+
+    #[test]
+    fn test_matches_full_walks() {
+        for (from, to) in [(1, 5000), (700, 9000), (123_456, 130_000)] {
+            assert_eq!(search(from, to), naive(from, to), "{}..={}", from, to);
+        }
+    }
+
+    #[test]
+    fn test_large_numbers() {
+        // 3n + 1 of these no longer fits in 64 bits
+        let from = u64::MAX - 10;
+        let records = search(from, u64::MAX);
+        assert!(records.highest.1 > u128::from(u64::MAX));
+    }
+
+    #[test]
+    fn test_limits() {
+        let cancel = CancelToken::default();
+        assert!(explore(0, 10, MAX_THREADS, &cancel).is_err());
+        assert!(explore(10, 9, MAX_THREADS, &cancel).is_err());
+        assert!(explore(1, MAX_RANGE_SIZE + 1, MAX_THREADS, &cancel).is_err());
+
+        cancel.cancel();
+        assert_eq!(
+            explore(1, 1000, MAX_THREADS, &cancel),
+            Err("Ran out of time, try a smaller range".to_string())
+        );
+    }
+}
@@ -1,3 +1,20 @@
-pub struct Data {} // User data, which is stored and accessible in all command invocations
+use crate::brainfuck::golf::Challenges;
+use crate::dice::fair::DiceRng;
+use crate::dice::history::History;
+use crate::initiative::Trackers;
+use crate::lisp::macros::Macros;
+use crate::lisp::session::Sessions;
+use crate::sandbox::Sandbox;
+
+// User data, which is stored and accessible in all command invocations
+pub struct Data {
+    pub sandbox: Sandbox,
+    pub challenges: Challenges,
+    pub dice_rng: DiceRng,
+    pub dice_history: History,
+    pub initiative: Trackers,
+    pub lisp_sessions: Sessions,
+    pub macros: Macros,
+}
 pub type Error = Box<dyn std::error::Error + Send + Sync>;
 pub type Context<'a> = poise::Context<'a, Data, Error>;
@@ -1,39 +1,177 @@
 use crate::context::{Context, Error};
 use poise::serenity_prelude as serenity;
 
-// Draw a random number between 1 and 6
-fn get_random_dice_number() -> u8 {
-    let dice_id = rand::random::<u8>() % 6 + 1;
-    return dice_id;
+pub mod fair;
+pub mod history;
+mod image;
+pub mod notation;
+mod stats;
+
+const DICE_IMAGE: &str = "dice.png";
+// Longest breakdown an embed description can hold
+const MAX_BREAKDOWN_LENGTH: usize = 4096;
+
+/// Roll dice
+#[poise::command(
+    slash_command,
+    subcommands("roll", "commit", "verify", "stats::stats"),
+    subcommand_required
+)]
+pub async fn dice(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
 }
 
-// Get the URL of the dice image
-fn getdiceurl(dice_id: u8) -> String {
-    let dice_url: String = match dice_id {
-        1 => "https://upload.wikimedia.org/wikipedia/commons/c/c5/Dice-1.png".to_string(),
-        2 => "https://upload.wikimedia.org/wikipedia/commons/1/18/Dice-2.png".to_string(),
-        3 => "https://upload.wikimedia.org/wikipedia/commons/7/70/Dice-3.png".to_string(),
-        4 => "https://upload.wikimedia.org/wikipedia/commons/a/a9/Dice-4.png".to_string(),
-        5 => "https://upload.wikimedia.org/wikipedia/commons/6/6c/Dice-5.png".to_string(),
-        6 => "https://upload.wikimedia.org/wikipedia/commons/5/5c/Dice-6.png".to_string(),
-        _ => "It's best not to push your luck too far.".to_string(),
+/// Rolls dice, like 4d6kh3, 3d6!, 2d6r1, 10d10>=7 or 4dF; a single d6 by default
+#[poise::command(slash_command)]
+pub async fn roll(
+    ctx: Context<'_>,
+    #[description = "Dice notation, like 4d6kh3 or 1d8+1d6+3"] expression: Option<String>,
+    #[description = "Seed to reproduce a roll"] seed: Option<u64>,
+    #[description = "Your part of a provably fair roll, see /dice commit"] client_seed: Option<
+        String,
+    >,
+) -> Result<(), Error> {
+    let expression = match notation::parse(expression.as_deref().unwrap_or("1d6")) {
+        Ok(expression) => expression,
+        Err(e) => {
+            ctx.say(format!("Invalid dice: {}", e)).await?;
+            return Ok(());
+        }
     };
 
-    // Append a question mark and a random number at the end of the URL to prevent caching
-    let dice_url_r = format!("{}?{}", dice_url, rand::random::<u32>());
+    if seed.is_some() && client_seed.is_some() {
+        ctx.say("Give either a seed or a client seed, not both")
+            .await?;
+        return Ok(());
+    }
+    let dice = &ctx.data().dice_rng;
+    let fair = match seed {
+        Some(_) => None,
+        None => dice.reveal(ctx.author().id),
+    };
+    if fair.is_none() && client_seed.is_some() {
+        ctx.say("Get a commitment with `/dice commit` before giving a client seed")
+            .await?;
+        return Ok(());
+    }
+    let (roll, embed) = match (seed, fair) {
+        (Some(seed), _) => {
+            let roll = expression.roll(&mut fair::seeded_rng(seed));
+            let embed = roll_embed(&roll).footer(serenity::CreateEmbedFooter::new(format!(
+                "{} · seed {}",
+                expression, seed
+            )));
+            (roll, embed)
+        }
+        (None, Some(server_seed)) => {
+            let client_seed = client_seed.unwrap_or_default();
+            let roll = expression.roll(&mut server_seed.rng(&client_seed));
+            let embed = fair_embed(&expression, &roll, &server_seed, &client_seed);
+            (roll, embed)
+        }
+        (None, None) => {
+            let roll = dice.with(|rng| expression.roll(rng));
+            let embed =
+                roll_embed(&roll).footer(serenity::CreateEmbedFooter::new(expression.to_string()));
+            (roll, embed)
+        }
+    };
+
+    ctx.send(roll_reply(&roll, embed)).await?;
+    ctx.data().dice_history.record(history::Record::new(
+        ctx.author().id.get(),
+        ctx.author().name.clone(),
+        ctx.guild_id().map(|g| g.get()),
+        &expression,
+        &roll,
+        seed.is_some(),
+    ))?;
+    Ok(())
+}
 
-    return dice_url_r;
+/// Commit to a secret seed for your next roll, so you can check it was fair
+#[poise::command(slash_command)]
+pub async fn commit(ctx: Context<'_>) -> Result<(), Error> {
+    let commitment = ctx.data().dice_rng.commit(ctx.author().id);
+    ctx.say(format!(
+        "Commitment for your next roll: `{}`\n\
+         Roll with `/dice roll` and a client seed of your choice. The server seed is \
+         revealed afterwards, its SHA-256 must match this commitment.",
+        commitment
+    ))
+    .await?;
+    Ok(())
 }
 
-/// Rolls a dice
+/// Check a provably fair roll from its revealed seeds
 #[poise::command(slash_command)]
-pub async fn dice(ctx: Context<'_>) -> Result<(), Error> {
-    let dice_id: u8 = get_random_dice_number();
-    let image_url: String = getdiceurl(dice_id);
-    let title: String = format!("{}", dice_id);
-    let reply: poise::CreateReply = poise::CreateReply::default()
-        .content(String::new())
-        .embed(serenity::CreateEmbed::new().title(title).image(image_url));
-    ctx.send(reply).await?;
+pub async fn verify(
+    ctx: Context<'_>,
+    #[description = "Dice notation that was rolled"] expression: String,
+    #[description = "Revealed server seed"] server_seed: String,
+    #[description = "Client seed used for the roll"] client_seed: Option<String>,
+) -> Result<(), Error> {
+    let checked = notation::parse(&expression)
+        .map_err(|e| format!("Invalid dice: {}", e))
+        .and_then(|expression| Ok((expression, fair::ServerSeed::from_hex(&server_seed)?)));
+    let (expression, server_seed) = match checked {
+        Ok(checked) => checked,
+        Err(e) => {
+            ctx.say(e).await?;
+            return Ok(());
+        }
+    };
+    let client_seed = client_seed.unwrap_or_default();
+    let roll = expression.roll(&mut server_seed.rng(&client_seed));
+    let embed = fair_embed(&expression, &roll, &server_seed, &client_seed);
+    ctx.send(roll_reply(&roll, embed)).await?;
     Ok(())
 }
+
+/// Reply with `embed`, showing the dice as an attached image when they can
+/// be drawn
+fn roll_reply(roll: &notation::Roll, embed: serenity::CreateEmbed) -> poise::CreateReply {
+    match image::roll_image(roll) {
+        Ok(Some(png)) => poise::CreateReply::default()
+            .attachment(serenity::CreateAttachment::bytes(png, DICE_IMAGE))
+            .embed(embed.attachment(DICE_IMAGE)),
+        Ok(None) => poise::CreateReply::default().embed(embed),
+        Err(e) => poise::CreateReply::default().embed(embed.field("Image", e, false)),
+    }
+}
+
+fn roll_embed(roll: &notation::Roll) -> serenity::CreateEmbed {
+    let mut breakdown = roll
+        .terms
+        .iter()
+        .map(|t| t.breakdown())
+        .collect::<Vec<_>>()
+        .join("\n");
+    if breakdown.chars().count() > MAX_BREAKDOWN_LENGTH {
+        breakdown = breakdown.chars().take(MAX_BREAKDOWN_LENGTH - 1).collect();
+        breakdown.push('…');
+    }
+    serenity::CreateEmbed::new()
+        .title(format!("🎲 {}", roll.total))
+        .description(breakdown)
+}
+
+fn fair_embed(
+    expression: &notation::Expression,
+    roll: &notation::Roll,
+    server_seed: &fair::ServerSeed,
+    client_seed: &str,
+) -> serenity::CreateEmbed {
+    roll_embed(roll)
+        .field(
+            "Commitment",
+            format!("`{}`", server_seed.commitment()),
+            false,
+        )
+        .field("Server seed", format!("`{}`", server_seed.hex()), false)
+        .field("Client seed", format!("`{}`", client_seed), false)
+        .footer(serenity::CreateEmbedFooter::new(format!(
+            "{} · provably fair",
+            expression
+        )))
+}
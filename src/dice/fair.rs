@@ -0,0 +1,140 @@
+use poise::serenity_prelude as serenity;
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Secret the bot commits to before a provably fair roll
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ServerSeed([u8; 32]);
+
+impl ServerSeed {
+    pub fn from_hex(hex: &str) -> Result<Self, String> {
+        let invalid = || "A server seed is 64 hexadecimal digits".to_string();
+        let hex = hex.trim();
+        if hex.len() != 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+        let mut bytes = [0; 32];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).map_err(|_| invalid())?;
+        }
+        Ok(ServerSeed(bytes))
+    }
+
+    pub fn hex(&self) -> String {
+        to_hex(&self.0)
+    }
+
+    /// SHA-256 of the seed, published before rolling
+    pub fn commitment(&self) -> String {
+        to_hex(&Sha256::digest(self.0))
+    }
+
+    /// Generator for a roll, mixing in the player's seed so neither side
+    /// alone decides the outcome
+    pub fn rng(&self, client_seed: &str) -> ChaCha20Rng {
+        let mut hasher = Sha256::new();
+        hasher.update(self.0);
+        hasher.update(client_seed.as_bytes());
+        ChaCha20Rng::from_seed(hasher.finalize().into())
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Generator that gives the same rolls for the same seed on every machine
+pub fn seeded_rng(seed: u64) -> ChaCha20Rng {
+    ChaCha20Rng::seed_from_u64(seed)
+}
+
+/// Source of every dice roll, plus the commitments of pending provably
+/// fair rolls
+pub struct DiceRng {
+    rng: Mutex<ChaCha20Rng>,
+    commitments: Mutex<HashMap<serenity::UserId, ServerSeed>>,
+}
+
+impl Default for DiceRng {
+    fn default() -> Self {
+        Self::new(ChaCha20Rng::from_entropy())
+    }
+}
+
+impl DiceRng {
+    pub fn new(rng: ChaCha20Rng) -> Self {
+        DiceRng {
+            rng: Mutex::new(rng),
+            commitments: Mutex::default(),
+        }
+    }
+
+    /// Run `f` with the shared generator
+    pub fn with<T>(&self, f: impl FnOnce(&mut ChaCha20Rng) -> T) -> T {
+        f(&mut self.rng.lock().unwrap())
+    }
+
+    /// Pick a server seed for the next roll of `user`, replacing any pending
+    /// one, and return its commitment
+    pub fn commit(&self, user: serenity::UserId) -> String {
+        let mut seed = [0; 32];
+        self.with(|rng| rng.fill_bytes(&mut seed));
+        let seed = ServerSeed(seed);
+        self.commitments.lock().unwrap().insert(user, seed);
+        seed.commitment()
+    }
+
+    /// Take the pending server seed of `user`, so it is used only once
+    pub fn reveal(&self, user: serenity::UserId) -> Option<ServerSeed> {
+        self.commitments.lock().unwrap().remove(&user)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dice::notation;
+
+    #[test]
+    fn test_commitment() {
+        let seed = ServerSeed([0; 32]);
+        assert_eq!(
+            seed.commitment(),
+            "66687aadf862bd776c8fc18b8e9f8e20089714856ee233b3902a591d0d5f2925"
+        );
+        assert_eq!(ServerSeed::from_hex(&seed.hex()), Ok(seed));
+        assert!(ServerSeed::from_hex("abc").is_err());
+        assert!(ServerSeed::from_hex(&"g".repeat(64)).is_err());
+    }
+
+    #[test]
+    fn test_commit_and_reveal() {
+        let dice = DiceRng::new(seeded_rng(1));
+        let user = serenity::UserId::new(1);
+        let commitment = dice.commit(user);
+        let seed = dice.reveal(user).unwrap();
+        assert_eq!(seed.commitment(), commitment);
+        assert_eq!(dice.reveal(user), None);
+    }
+
+    #[test]
+    fn test_rolls_are_reproducible() {
+        let expression = notation::parse("10d20").unwrap();
+        let first = DiceRng::new(seeded_rng(42)).with(|rng| expression.roll(rng));
+        let second = DiceRng::new(seeded_rng(42)).with(|rng| expression.roll(rng));
+        assert_eq!(first, second);
+
+        let seed = ServerSeed([7; 32]);
+        assert_eq!(
+            expression.roll(&mut seed.rng("player")),
+            expression.roll(&mut seed.rng("player"))
+        );
+        assert_ne!(
+            expression.roll(&mut seed.rng("player")),
+            expression.roll(&mut seed.rng("other player"))
+        );
+    }
+}
@@ -0,0 +1,171 @@
+use super::notation::{Expression, Faces, Roll, Term};
+use crate::context::Error;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const HISTORY_FILE: &str = "dice_history.jsonl";
+
+/// Every value drawn for one kind of die, including rerolled ones
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Draws {
+    pub faces: Faces,
+    pub values: Vec<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    pub user: u64,
+    pub name: String,
+    pub guild: Option<u64>,
+    pub expression: String,
+    pub total: i64,
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    /// Rolled from a seed the user picked, so left out of statistics
+    pub seeded: bool,
+    pub draws: Vec<Draws>,
+}
+
+impl Record {
+    pub fn new(
+        user: u64,
+        name: String,
+        guild: Option<u64>,
+        expression: &Expression,
+        roll: &Roll,
+        seeded: bool,
+    ) -> Self {
+        let draws = roll
+            .terms
+            .iter()
+            .filter_map(|t| match &t.term {
+                Term::Dice(dice) => Some(Draws {
+                    faces: dice.faces,
+                    values: t
+                        .dice
+                        .iter()
+                        .flat_map(|d| d.rerolled.iter().chain([&d.value]))
+                        .copied()
+                        .collect(),
+                }),
+                Term::Constant(_) => None,
+            })
+            .collect();
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        Record {
+            user,
+            name,
+            guild,
+            expression: expression.to_string(),
+            total: roll.total,
+            timestamp,
+            seeded,
+            draws,
+        }
+    }
+}
+
+/// Every roll made, appended to a file with one JSON record per line
+pub struct History {
+    path: PathBuf,
+    records: Mutex<Vec<Record>>,
+}
+
+impl History {
+    pub fn load(path: impl Into<PathBuf>) -> Result<Self, Error> {
+        let path = path.into();
+        let records = match std::fs::read_to_string(&path) {
+            // A crash can leave the last line cut short: skip what does not
+            // parse, and end the line so the next record starts on its own
+            Ok(lines) => {
+                if !lines.is_empty() && !lines.ends_with('\n') {
+                    std::fs::OpenOptions::new()
+                        .append(true)
+                        .open(&path)?
+                        .write_all(b"\n")?;
+                }
+                lines
+                    .lines()
+                    .filter_map(|line| serde_json::from_str(line).ok())
+                    .collect()
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(History {
+            path,
+            records: Mutex::new(records),
+        })
+    }
+
+    pub fn record(&self, record: Record) -> Result<(), Error> {
+        let mut records = self.records.lock().unwrap();
+        let mut line = serde_json::to_string(&record)?;
+        line.push('\n');
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?
+            .write_all(line.as_bytes())?;
+        records.push(record);
+        Ok(())
+    }
+
+    pub fn view<T>(&self, f: impl FnOnce(&[Record]) -> T) -> T {
+        f(&self.records.lock().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dice::notation;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_record_keeps_every_draw() {
+        let expression = notation::parse("4d6r1+2+1dF").unwrap();
+        let roll = expression.roll(&mut StdRng::seed_from_u64(5));
+        let record = Record::new(1, "ada".to_string(), Some(2), &expression, &roll, false);
+        assert_eq!(record.expression, "4d6r1+2+1dF");
+        assert_eq!(record.total, roll.total);
+        assert_eq!(record.draws.len(), 2);
+        let rerolls: usize = roll.terms[0].dice.iter().map(|d| d.rerolled.len()).sum();
+        assert_eq!(record.draws[0].values.len(), 4 + rerolls);
+        assert_eq!(record.draws[1].faces, Faces::Fudge);
+    }
+
+    #[test]
+    fn test_history_persists() {
+        let path = std::env::temp_dir().join(format!("bytie-dice-{}.jsonl", std::process::id()));
+        let expression = notation::parse("2d20").unwrap();
+        let roll = expression.roll(&mut StdRng::seed_from_u64(1));
+        let record = Record::new(1, "ada".to_string(), None, &expression, &roll, true);
+
+        let history = History::load(&path).unwrap();
+        history.record(record.clone()).unwrap();
+        history.record(record.clone()).unwrap();
+        // As if the bot died halfway through writing a line
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        file.write_all(b"{\"user\":").unwrap();
+
+        let loaded = History::load(&path).unwrap();
+        assert_eq!(loaded.view(|r| r.len()), 2);
+        loaded.record(record.clone()).unwrap();
+        let loaded = History::load(&path).unwrap();
+        assert_eq!(
+            loaded.view(|r| r.to_vec()),
+            vec![record.clone(), record.clone(), record]
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,220 @@
+use super::notation::{Die, Faces, Roll, Term};
+use crate::render;
+use std::f32::consts::PI;
+use tiny_skia::{Color, FillRule, Path, PathBuilder, Pixmap, PixmapPaint, Rect, Stroke, Transform};
+
+const FACE_SIZE: u32 = 80;
+const PADDING: u32 = 8;
+const DICE_PER_ROW: usize = 10;
+// Rolls with more dice than this are only described in text
+const MAX_DRAWN_DICE: usize = 60;
+
+/// Regular polygon with a corner at `rotation` radians
+fn polygon(corners: usize, center: f32, radius: f32, rotation: f32) -> Option<Path> {
+    let mut path = PathBuilder::new();
+    for i in 0..corners {
+        let angle = rotation + 2.0 * PI * i as f32 / corners as f32;
+        let (x, y) = (center + radius * angle.cos(), center + radius * angle.sin());
+        if i == 0 {
+            path.move_to(x, y);
+        } else {
+            path.line_to(x, y);
+        }
+    }
+    path.close();
+    path.finish()
+}
+
+/// Silhouette of the die, where its label is centred and how tall the label
+/// may be, as a fraction of the face
+fn outline(faces: Faces) -> Option<(Path, f32, f32)> {
+    let center = FACE_SIZE as f32 / 2.0;
+    let radius = center - 4.0;
+    let up = -PI / 2.0;
+    match faces {
+        // The label sits low in a triangle, where it is widest
+        Faces::Sides(4) => Some((
+            polygon(3, center, radius, up)?,
+            center + radius * 0.12,
+            0.25,
+        )),
+        Faces::Sides(6) | Faces::Fudge => {
+            let inset = FACE_SIZE as f32 * 0.1;
+            let side = FACE_SIZE as f32 - 2.0 * inset;
+            let square = Rect::from_xywh(inset, inset, side, side)?;
+            Some((PathBuilder::from_rect(square), center, 0.35))
+        }
+        Faces::Sides(8) => Some((polygon(4, center, radius, up)?, center, 0.3)),
+        Faces::Sides(10) => {
+            let mut path = PathBuilder::new();
+            path.move_to(center, center - radius);
+            path.line_to(center + radius, center - radius * 0.1);
+            path.line_to(center, center + radius);
+            path.line_to(center - radius, center - radius * 0.1);
+            path.close();
+            Some((path.finish()?, center, 0.3))
+        }
+        Faces::Sides(12) => Some((
+            polygon(5, center, radius, up)?,
+            center + radius * 0.05,
+            0.35,
+        )),
+        Faces::Sides(20) => Some((polygon(6, center, radius, up)?, center, 0.35)),
+        Faces::Sides(_) => Some((
+            PathBuilder::from_circle(center, center, radius)?,
+            center,
+            0.35,
+        )),
+    }
+}
+
+fn label(faces: Faces, value: i64) -> String {
+    match (faces, value) {
+        (Faces::Fudge, 1) => "+".to_string(),
+        (Faces::Fudge, -1) => "-".to_string(),
+        (Faces::Fudge, _) => " ".to_string(),
+        (Faces::Sides(_), value) => value.to_string(),
+    }
+}
+
+/// One die showing its value; dropped dice are greyed out, successes green
+/// and dice that exploded get an orange rim
+pub fn die_face(faces: Faces, die: &Die) -> Option<Pixmap> {
+    let mut pixmap = Pixmap::new(FACE_SIZE, FACE_SIZE)?;
+    let (shape, label_y, label_height) = outline(faces)?;
+    let (fill, ink) = match (die.kept, die.success) {
+        (false, _) => (
+            render::color(0xd0, 0xd0, 0xd0),
+            render::color(0x90, 0x90, 0x90),
+        ),
+        (true, true) => (
+            render::color(0xb9, 0xf6, 0xca),
+            render::color(0x1b, 0x5e, 0x20),
+        ),
+        (true, false) => (
+            render::color(0xff, 0xff, 0xff),
+            render::color(0x21, 0x21, 0x21),
+        ),
+    };
+    let rim = if die.exploded {
+        render::color(0xff, 0x8f, 0x00)
+    } else {
+        ink
+    };
+    pixmap.fill_path(
+        &shape,
+        &render::paint(fill),
+        FillRule::Winding,
+        Transform::identity(),
+        None,
+    );
+    let stroke = Stroke {
+        width: if die.exploded { 5.0 } else { 3.0 },
+        ..Stroke::default()
+    };
+    pixmap.stroke_path(
+        &shape,
+        &render::paint(rim),
+        &stroke,
+        Transform::identity(),
+        None,
+    );
+
+    let text = label(faces, die.value);
+    let size = FACE_SIZE as f32;
+    let scale = (size * 0.45 / render::text_width(&text, 1.0))
+        .min(size * label_height / render::GLYPH_HEIGHT as f32);
+    render::draw_text(&mut pixmap, &text, size / 2.0, label_y, scale, ink);
+    Some(pixmap)
+}
+
+/// PNG of every die in `roll`, in rows, or `None` if there are no dice or
+/// too many to draw
+pub fn roll_image(roll: &Roll) -> Result<Option<Vec<u8>>, String> {
+    let dice: Vec<(Faces, &Die)> = roll
+        .terms
+        .iter()
+        .filter_map(|t| match &t.term {
+            Term::Dice(dice) => Some(t.dice.iter().map(move |d| (dice.faces, d))),
+            Term::Constant(_) => None,
+        })
+        .flatten()
+        .collect();
+    if dice.is_empty() || dice.len() > MAX_DRAWN_DICE {
+        return Ok(None);
+    }
+
+    let columns = dice.len().min(DICE_PER_ROW);
+    let rows = dice.len().div_ceil(columns);
+    let extent = |n: usize| n as u32 * (FACE_SIZE + PADDING) + PADDING;
+    let mut image =
+        Pixmap::new(extent(columns), extent(rows)).ok_or("Could not create the image")?;
+    image.fill(Color::TRANSPARENT);
+    for (i, (faces, die)) in dice.into_iter().enumerate() {
+        let face = die_face(faces, die).ok_or("Could not draw a die")?;
+        let (x, y) = (extent(i % columns), extent(i / columns));
+        image.draw_pixmap(
+            x as i32,
+            y as i32,
+            face.as_ref(),
+            &PixmapPaint::default(),
+            Transform::identity(),
+            None,
+        );
+    }
+    render::encode_png(&image).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dice::notation;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn die(value: i64) -> Die {
+        Die {
+            value,
+            rerolled: vec![],
+            exploded: false,
+            kept: true,
+            success: false,
+        }
+    }
+
+    fn roll(expression: &str) -> Roll {
+        notation::parse(expression)
+            .unwrap()
+            .roll(&mut StdRng::seed_from_u64(1))
+    }
+
+    #[test]
+    fn test_faces_are_drawn() {
+        for faces in [4, 6, 8, 10, 12, 20, 100].map(Faces::Sides) {
+            let face = die_face(faces, &die(3)).unwrap();
+            let center = face.pixel(FACE_SIZE / 2, FACE_SIZE / 2).unwrap();
+            assert_eq!(center.alpha(), 255, "{:?} has no fill", faces);
+            assert_eq!(face.pixel(0, 0).unwrap().alpha(), 0);
+        }
+        assert!(die_face(Faces::Fudge, &die(-1)).is_some());
+        assert_eq!(label(Faces::Fudge, 0), " ");
+    }
+
+    #[test]
+    fn test_roll_image_layout() {
+        let png = roll_image(&roll("12d6+2")).unwrap().unwrap();
+        let image = Pixmap::decode_png(&png).unwrap();
+        assert_eq!(image.width(), 10 * (FACE_SIZE + PADDING) + PADDING);
+        assert_eq!(image.height(), 2 * (FACE_SIZE + PADDING) + PADDING);
+
+        let png = roll_image(&roll("1d20")).unwrap().unwrap();
+        let image = Pixmap::decode_png(&png).unwrap();
+        assert_eq!(image.width(), FACE_SIZE + 2 * PADDING);
+    }
+
+    #[test]
+    fn test_roll_image_skips_unsuitable_rolls() {
+        assert_eq!(roll_image(&roll("5")), Ok(None));
+        assert_eq!(roll_image(&roll("100d6")), Ok(None));
+    }
+}
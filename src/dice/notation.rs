@@ -0,0 +1,772 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+const MAX_TERMS: usize = 20;
+const MAX_DICE: u32 = 100;
+const MAX_SIDES: u32 = 1000;
+const MAX_CONSTANT: i64 = 1_000_000;
+// Dice added by explosions, per term
+const MAX_EXPLOSIONS: usize = 100;
+// Rerolls of a single die; reaching it keeps the last value
+const MAX_REROLLS: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Equal,
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+}
+
+/// A test against a die, like the `>=7` of `10d10>=7`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Comparison {
+    pub op: Op,
+    pub value: i64,
+}
+
+impl Comparison {
+    pub fn matches(&self, value: i64) -> bool {
+        match self.op {
+            Op::Equal => value == self.value,
+            Op::Greater => value > self.value,
+            Op::GreaterOrEqual => value >= self.value,
+            Op::Less => value < self.value,
+            Op::LessOrEqual => value <= self.value,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Faces {
+    Sides(u32),
+    /// Fudge dice, with faces -1, 0 and +1
+    Fudge,
+}
+
+impl Faces {
+    pub fn values(&self) -> std::ops::RangeInclusive<i64> {
+        match self {
+            Faces::Sides(sides) => 1..=*sides as i64,
+            Faces::Fudge => -1..=1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Keep {
+    Highest(u32),
+    Lowest(u32),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dice {
+    pub count: u32,
+    pub faces: Faces,
+    /// Roll an extra die whenever one shows its highest face
+    pub explode: bool,
+    /// Roll again while a die matches
+    pub reroll: Option<Comparison>,
+    pub keep: Option<Keep>,
+    /// Count the kept dice that match instead of adding them up
+    pub success: Option<Comparison>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Term {
+    Constant(i64),
+    Dice(Dice),
+}
+
+/// Terms added together, each with its sign
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expression {
+    pub terms: Vec<(bool, Term)>,
+}
+
+/// One die as it landed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Die {
+    pub value: i64,
+    /// Values thrown away by rerolls, in order
+    pub rerolled: Vec<i64>,
+    /// Whether this die caused another to be rolled
+    pub exploded: bool,
+    pub kept: bool,
+    pub success: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TermRoll {
+    pub negative: bool,
+    pub term: Term,
+    pub dice: Vec<Die>,
+    pub value: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Roll {
+    pub terms: Vec<TermRoll>,
+    pub total: i64,
+}
+
+/// Parse dice notation such as `4d6kh3`, `3d6!`, `1d8+1d6+3`, `2d6r1`,
+/// `10d10>=7` or `4dF`. Whitespace is ignored.
+pub fn parse(notation: &str) -> Result<Expression, String> {
+    let chars: Vec<char> = notation.chars().filter(|c| !c.is_whitespace()).collect();
+    let mut parser = Parser { chars, at: 0 };
+    let expression = parser.expression()?;
+    expression.validate()?;
+    Ok(expression)
+}
+
+struct Parser {
+    chars: Vec<char>,
+    at: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.at).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        let found = self
+            .peek()
+            .is_some_and(|next| next.eq_ignore_ascii_case(&c));
+        if found {
+            self.at += 1;
+        }
+        found
+    }
+
+    fn error(&self, expected: &str) -> String {
+        match self.peek() {
+            Some(c) => format!(
+                "Expected {} at position {}, found '{}'",
+                expected,
+                self.at + 1,
+                c
+            ),
+            None => format!("Expected {} at the end", expected),
+        }
+    }
+
+    fn expression(&mut self) -> Result<Expression, String> {
+        let mut terms = vec![(false, self.term()?)];
+        while let Some(c) = self.peek() {
+            let negative = match c {
+                '+' => false,
+                '-' => true,
+                _ => return Err(self.error("'+' or '-'")),
+            };
+            self.at += 1;
+            terms.push((negative, self.term()?));
+        }
+        Ok(Expression { terms })
+    }
+
+    fn number(&mut self) -> Option<Result<u32, String>> {
+        let start = self.at;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.at += 1;
+        }
+        if start == self.at {
+            return None;
+        }
+        let digits: String = self.chars[start..self.at].iter().collect();
+        Some(
+            digits
+                .parse()
+                .map_err(|_| format!("{} is too large", digits)),
+        )
+    }
+
+    fn term(&mut self) -> Result<Term, String> {
+        let count = self.number().transpose()?;
+        if !self.eat('d') {
+            return match count {
+                Some(n) => Ok(Term::Constant(n as i64)),
+                None => Err(self.error("a number or dice")),
+            };
+        }
+        let faces = if self.eat('f') {
+            Faces::Fudge
+        } else if self.eat('%') {
+            Faces::Sides(100)
+        } else {
+            match self.number() {
+                Some(sides) => Faces::Sides(sides?),
+                None => return Err(self.error("the number of sides")),
+            }
+        };
+        let mut dice = Dice {
+            count: count.unwrap_or(1),
+            faces,
+            explode: false,
+            reroll: None,
+            keep: None,
+            success: None,
+        };
+        self.modifiers(&mut dice)?;
+        Ok(Term::Dice(dice))
+    }
+
+    fn modifiers(&mut self, dice: &mut Dice) -> Result<(), String> {
+        loop {
+            let at = self.at;
+            let repeated = |name: &str| format!("{} is given twice at position {}", name, at + 1);
+            if self.eat('k') {
+                if dice.keep.is_some() {
+                    return Err(repeated("Keep"));
+                }
+                let lowest = if self.eat('l') {
+                    true
+                } else {
+                    self.eat('h');
+                    false
+                };
+                let n = self.number().transpose()?.unwrap_or(1);
+                dice.keep = Some(if lowest {
+                    Keep::Lowest(n)
+                } else {
+                    Keep::Highest(n)
+                });
+            } else if self.eat('!') {
+                if dice.explode {
+                    return Err(repeated("Exploding"));
+                }
+                dice.explode = true;
+            } else if self.eat('r') {
+                if dice.reroll.is_some() {
+                    return Err(repeated("Reroll"));
+                }
+                let op = self.op().unwrap_or(Op::Equal);
+                dice.reroll = Some(self.comparison(op)?);
+            } else if let Some(op) = self.op() {
+                if dice.success.is_some() {
+                    return Err(repeated("Success counting"));
+                }
+                dice.success = Some(self.comparison(op)?);
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn op(&mut self) -> Option<Op> {
+        if self.eat('>') {
+            Some(if self.eat('=') {
+                Op::GreaterOrEqual
+            } else {
+                Op::Greater
+            })
+        } else if self.eat('<') {
+            Some(if self.eat('=') {
+                Op::LessOrEqual
+            } else {
+                Op::Less
+            })
+        } else if self.eat('=') {
+            Some(Op::Equal)
+        } else {
+            None
+        }
+    }
+
+    fn comparison(&mut self, op: Op) -> Result<Comparison, String> {
+        match self.number() {
+            Some(value) => Ok(Comparison {
+                op,
+                value: value? as i64,
+            }),
+            None => Err(self.error("a number to compare with")),
+        }
+    }
+}
+
+impl Expression {
+    fn validate(&self) -> Result<(), String> {
+        if self.terms.len() > MAX_TERMS {
+            return Err(format!("Use at most {} terms", MAX_TERMS));
+        }
+        for (_, term) in &self.terms {
+            match term {
+                Term::Constant(n) if *n > MAX_CONSTANT => {
+                    return Err(format!("Numbers can be at most {}", MAX_CONSTANT));
+                }
+                Term::Constant(_) => {}
+                Term::Dice(dice) => dice.validate()?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Roll every term
+    pub fn roll(&self, rng: &mut impl Rng) -> Roll {
+        let terms: Vec<TermRoll> = self
+            .terms
+            .iter()
+            .map(|(negative, term)| {
+                let (dice, value) = match term {
+                    Term::Constant(n) => (Vec::new(), *n),
+                    Term::Dice(d) => d.roll(rng),
+                };
+                TermRoll {
+                    negative: *negative,
+                    term: term.clone(),
+                    dice,
+                    value,
+                }
+            })
+            .collect();
+        let total = terms
+            .iter()
+            .map(|t| if t.negative { -t.value } else { t.value })
+            .sum();
+        Roll { terms, total }
+    }
+}
+
+impl Dice {
+    fn validate(&self) -> Result<(), String> {
+        if !(1..=MAX_DICE).contains(&self.count) {
+            return Err(format!("Roll between 1 and {} dice at a time", MAX_DICE));
+        }
+        if let Faces::Sides(sides) = self.faces {
+            if !(1..=MAX_SIDES).contains(&sides) {
+                return Err(format!("Dice can have 1 to {} sides", MAX_SIDES));
+            }
+        }
+        let mut faces = self.faces.values();
+        if self.explode && faces.clone().count() == 1 {
+            return Err(format!("{} always explodes", self));
+        }
+        if let Some(reroll) = self.reroll {
+            if faces.all(|face| reroll.matches(face)) {
+                return Err(format!("{} rerolls every face", self));
+            }
+        }
+        if let Some(Keep::Highest(n) | Keep::Lowest(n)) = self.keep {
+            if n == 0 || n > self.count {
+                return Err(format!("{} keeps between 1 and {} dice", self, self.count));
+            }
+        }
+        Ok(())
+    }
+
+    fn roll(&self, rng: &mut impl Rng) -> (Vec<Die>, i64) {
+        let faces = self.faces.values();
+        let highest = *faces.end();
+        let mut throw = || {
+            let mut die = Die {
+                value: rng.gen_range(faces.clone()),
+                rerolled: Vec::new(),
+                exploded: false,
+                kept: true,
+                success: false,
+            };
+            if let Some(reroll) = self.reroll {
+                while reroll.matches(die.value) && die.rerolled.len() < MAX_REROLLS {
+                    die.rerolled.push(die.value);
+                    die.value = rng.gen_range(faces.clone());
+                }
+            }
+            die
+        };
+
+        let mut dice: Vec<Die> = (0..self.count).map(|_| throw()).collect();
+        if self.explode {
+            let mut i = 0;
+            while i < dice.len() && dice.len() - (self.count as usize) < MAX_EXPLOSIONS {
+                if dice[i].value == highest {
+                    dice[i].exploded = true;
+                    dice.push(throw());
+                }
+                i += 1;
+            }
+        }
+
+        if let Some(keep) = self.keep {
+            let mut order: Vec<usize> = (0..dice.len()).collect();
+            let n = match keep {
+                Keep::Highest(n) => {
+                    order.sort_by_key(|&i| std::cmp::Reverse(dice[i].value));
+                    n
+                }
+                Keep::Lowest(n) => {
+                    order.sort_by_key(|&i| dice[i].value);
+                    n
+                }
+            };
+            for &i in order.iter().skip(n as usize) {
+                dice[i].kept = false;
+            }
+        }
+
+        let value = match self.success {
+            Some(success) => {
+                for die in dice.iter_mut().filter(|d| d.kept) {
+                    die.success = success.matches(die.value);
+                }
+                dice.iter().filter(|d| d.success).count() as i64
+            }
+            None => dice.iter().filter(|d| d.kept).map(|d| d.value).sum(),
+        };
+        (dice, value)
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Op::Equal => "=",
+            Op::Greater => ">",
+            Op::GreaterOrEqual => ">=",
+            Op::Less => "<",
+            Op::LessOrEqual => "<=",
+        })
+    }
+}
+
+impl fmt::Display for Faces {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Faces::Sides(sides) => write!(f, "d{}", sides),
+            Faces::Fudge => write!(f, "dF"),
+        }
+    }
+}
+
+impl fmt::Display for Dice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.count, self.faces)?;
+        if self.explode {
+            write!(f, "!")?;
+        }
+        match self.reroll {
+            Some(Comparison {
+                op: Op::Equal,
+                value,
+            }) => write!(f, "r{}", value)?,
+            Some(Comparison { op, value }) => write!(f, "r{}{}", op, value)?,
+            None => {}
+        }
+        match self.keep {
+            Some(Keep::Highest(n)) => write!(f, "kh{}", n)?,
+            Some(Keep::Lowest(n)) => write!(f, "kl{}", n)?,
+            None => {}
+        }
+        if let Some(Comparison { op, value }) = self.success {
+            write!(f, "{}{}", op, value)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Term {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Term::Constant(n) => write!(f, "{}", n),
+            Term::Dice(dice) => write!(f, "{}", dice),
+        }
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, (negative, term)) in self.terms.iter().enumerate() {
+            match (i, negative) {
+                (0, false) => {}
+                (_, true) => write!(f, "-")?,
+                (_, false) => write!(f, "+")?,
+            }
+            write!(f, "{}", term)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Die {
+    /// Rerolled values lead to the final one, dropped dice are struck out and
+    /// successes are bold
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut text = String::new();
+        for value in &self.rerolled {
+            text.push_str(&format!("{}→", value));
+        }
+        text.push_str(&self.value.to_string());
+        if self.exploded {
+            text.push('!');
+        }
+        if !self.kept {
+            write!(f, "~~{}~~", text)
+        } else if self.success {
+            write!(f, "**{}**", text)
+        } else {
+            write!(f, "{}", text)
+        }
+    }
+}
+
+impl TermRoll {
+    /// Line of the breakdown, e.g. `4d6kh3: [6, 5, ~~1~~, 4] = 15`
+    pub fn breakdown(&self) -> String {
+        let sign = if self.negative { "-" } else { "" };
+        let Term::Dice(dice) = &self.term else {
+            return format!("{}{}", sign, self.value);
+        };
+        let rolled = self
+            .dice
+            .iter()
+            .map(|d| d.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let unit = match (dice.success, self.value) {
+            (None, _) => "",
+            (Some(_), 1) => " success",
+            (Some(_), _) => " successes",
+        };
+        format!("{}{}: [{}] = {}{}", sign, dice, rolled, self.value, unit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn dice(notation: &str) -> Dice {
+        match parse(notation).unwrap().terms.remove(0).1 {
+            Term::Dice(dice) => dice,
+            term => panic!("{} is not dice", term),
+        }
+    }
+
+    fn rng() -> StdRng {
+        StdRng::seed_from_u64(7)
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            dice("4d6kh3"),
+            Dice {
+                count: 4,
+                faces: Faces::Sides(6),
+                explode: false,
+                reroll: None,
+                keep: Some(Keep::Highest(3)),
+                success: None,
+            }
+        );
+        assert_eq!(dice("2D20KL1").keep, Some(Keep::Lowest(1)));
+        assert_eq!(dice("d20k").keep, Some(Keep::Highest(1)));
+        assert!(dice("3d6!").explode);
+        assert_eq!(
+            dice("2d6r<3").reroll,
+            Some(Comparison {
+                op: Op::Less,
+                value: 3
+            })
+        );
+        assert_eq!(
+            dice("10d10>=7").success,
+            Some(Comparison {
+                op: Op::GreaterOrEqual,
+                value: 7
+            })
+        );
+        assert_eq!(dice("4dF").faces, Faces::Fudge);
+        assert_eq!(dice("d%").faces, Faces::Sides(100));
+        assert_eq!(parse("1d8 + 1d6 - 3").unwrap().to_string(), "1d8+1d6-3");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            parse("2d6x").unwrap_err(),
+            "Expected '+' or '-' at position 4, found 'x'"
+        );
+        assert_eq!(
+            parse("2d").unwrap_err(),
+            "Expected the number of sides at the end"
+        );
+        assert_eq!(
+            parse("").unwrap_err(),
+            "Expected a number or dice at the end"
+        );
+        assert_eq!(
+            parse("1d6+").unwrap_err(),
+            "Expected a number or dice at the end"
+        );
+        assert_eq!(
+            parse("3d6kh4").unwrap_err(),
+            "3d6kh4 keeps between 1 and 3 dice"
+        );
+        assert_eq!(parse("1d1!").unwrap_err(), "1d1! always explodes");
+        assert_eq!(parse("2d6r<7").unwrap_err(), "2d6r<7 rerolls every face");
+        assert_eq!(
+            parse("0d6").unwrap_err(),
+            "Roll between 1 and 100 dice at a time"
+        );
+        assert_eq!(parse("1d0").unwrap_err(), "Dice can have 1 to 1000 sides");
+        assert!(parse("2d6!!").unwrap_err().contains("twice"));
+        assert!(parse("99999999999").unwrap_err().contains("too large"));
+    }
+
+    #[test]
+    fn test_keep_and_success() {
+        let roll = parse("4d6kh3").unwrap().roll(&mut rng());
+        let dice = &roll.terms[0].dice;
+        assert_eq!(dice.iter().filter(|d| !d.kept).count(), 1);
+        let dropped = dice.iter().find(|d| !d.kept).unwrap().value;
+        assert!(dice.iter().all(|d| d.value >= dropped));
+        assert_eq!(
+            roll.total,
+            dice.iter().filter(|d| d.kept).map(|d| d.value).sum::<i64>()
+        );
+
+        let roll = parse("10d10>=7").unwrap().roll(&mut rng());
+        let successes = roll.terms[0].dice.iter().filter(|d| d.value >= 7).count();
+        assert_eq!(roll.total, successes as i64);
+    }
+
+    #[test]
+    fn test_explode_and_reroll() {
+        let roll = parse("20d6!").unwrap().roll(&mut rng());
+        let dice = &roll.terms[0].dice;
+        let exploded = dice.iter().filter(|d| d.exploded).count();
+        assert_eq!(dice.len(), 20 + exploded);
+        assert!(dice.iter().all(|d| d.exploded == (d.value == 6)));
+
+        let roll = parse("50d4r1").unwrap().roll(&mut rng());
+        assert!(roll.terms[0].dice.iter().all(|d| d.value != 1));
+        assert!(roll.terms[0]
+            .dice
+            .iter()
+            .all(|d| d.rerolled.iter().all(|&v| v == 1)));
+    }
+
+    #[test]
+    fn test_breakdown() {
+        let term = TermRoll {
+            negative: false,
+            term: Term::Dice(dice("3d6kh2")),
+            dice: [(1, true), (6, true), (4, false)]
+                .into_iter()
+                .map(|(value, kept)| Die {
+                    value,
+                    rerolled: if value == 4 { vec![1] } else { vec![] },
+                    exploded: false,
+                    kept,
+                    success: false,
+                })
+                .collect(),
+            value: 7,
+        };
+        assert_eq!(term.breakdown(), "3d6kh2: [1, 6, ~~1→4~~] = 7");
+        let constant = TermRoll {
+            negative: true,
+            term: Term::Constant(3),
+            dice: vec![],
+            value: 3,
+        };
+        assert_eq!(constant.breakdown(), "-3");
+    }
+
+    #[test]
+    fn test_distribution_is_uniform() {
+        let expression = parse("1d6").unwrap();
+        let mut rng = rng();
+        let mut counts = [0usize; 6];
+        for _ in 0..60_000 {
+            counts[expression.roll(&mut rng).total as usize - 1] += 1;
+        }
+        // Each face within 5% of the expected 10000
+        assert!(counts.iter().all(|&c| (9_500..=10_500).contains(&c)));
+    }
+
+    fn any_dice() -> impl Strategy<Value = Dice> {
+        (
+            1..=20u32,
+            prop_oneof![(2..=100u32).prop_map(Faces::Sides), Just(Faces::Fudge)],
+        )
+            .prop_flat_map(|(count, faces)| {
+                let keep = prop_oneof![
+                    Just(None),
+                    (1..=count).prop_map(|n| Some(Keep::Highest(n))),
+                    (1..=count).prop_map(|n| Some(Keep::Lowest(n))),
+                ];
+                let reroll = match faces {
+                    Faces::Sides(_) => prop::option::of(Just(Comparison {
+                        op: Op::Equal,
+                        value: 1,
+                    }))
+                    .boxed(),
+                    Faces::Fudge => Just(None).boxed(),
+                };
+                let success = prop::option::of((1..=10i64).prop_map(|value| Comparison {
+                    op: Op::GreaterOrEqual,
+                    value,
+                }));
+                (any::<bool>(), reroll, keep, success).prop_map(
+                    move |(explode, reroll, keep, success)| Dice {
+                        count,
+                        faces,
+                        explode,
+                        reroll,
+                        keep,
+                        success,
+                    },
+                )
+            })
+    }
+
+    proptest! {
+        #[test]
+        fn prop_display_parses_back(dice in any_dice()) {
+            let expression = Expression { terms: vec![(false, Term::Dice(dice))] };
+            prop_assert_eq!(parse(&expression.to_string()), Ok(expression));
+        }
+
+        #[test]
+        fn prop_plain_rolls_are_bounded(count in 1..=100u32, sides in 1..=1000u32, seed: u64) {
+            let roll = parse(&format!("{}d{}", count, sides))
+                .unwrap()
+                .roll(&mut StdRng::seed_from_u64(seed));
+            let (count, sides) = (count as i64, sides as i64);
+            prop_assert!((count..=count * sides).contains(&roll.total));
+        }
+
+        #[test]
+        fn prop_rolls_respect_modifiers(dice in any_dice(), seed: u64) {
+            let (faces, kept) = (dice.faces.values(), dice.keep);
+            let expression = Expression { terms: vec![(false, Term::Dice(dice.clone()))] };
+            let roll = expression.roll(&mut StdRng::seed_from_u64(seed));
+            let rolled = &roll.terms[0].dice;
+
+            prop_assert!(rolled.len() >= dice.count as usize);
+            prop_assert!(rolled.iter().all(|d| faces.contains(&d.value)));
+            if let Some(reroll) = dice.reroll {
+                prop_assert!(rolled.iter().all(|d| !reroll.matches(d.value)));
+            }
+            let kept_count = rolled.iter().filter(|d| d.kept).count();
+            match kept {
+                Some(Keep::Highest(n) | Keep::Lowest(n)) => prop_assert_eq!(kept_count, n as usize),
+                None => prop_assert_eq!(kept_count, rolled.len()),
+            }
+            match dice.success {
+                Some(_) => prop_assert!((0..=kept_count as i64).contains(&roll.total)),
+                None => prop_assert_eq!(
+                    roll.total,
+                    rolled.iter().filter(|d| d.kept).map(|d| d.value).sum::<i64>()
+                ),
+            }
+        }
+    }
+}
@@ -0,0 +1,516 @@
+use super::history::Record;
+use super::notation::Faces;
+use crate::context::{Context, Error};
+use poise::serenity_prelude as serenity;
+use std::collections::{BTreeMap, HashMap};
+
+// Rollers need this many dice to be ranked, so one lucky roll is not enough
+const MIN_RANKED_DRAWS: usize = 30;
+const LEADERBOARD_SIZE: usize = 3;
+const HISTOGRAM_WIDTH: usize = 16;
+// Dice with more faces are shown in this many buckets
+const HISTOGRAM_BUCKETS: usize = 10;
+// Fewer expected draws per face make the chi-square test unreliable
+const MIN_EXPECTED_PER_FACE: f64 = 5.0;
+const SUSPICIOUS_P_VALUE: f64 = 0.01;
+// Kinds of dice listed in a field, the most rolled ones
+const MAX_LISTED_DICE: usize = 10;
+// Discord rejects longer field values. The five fields stay within the
+// 6000 characters allowed for a whole embed along with the title and
+// description.
+const FIELD_LIMIT: usize = 1024;
+
+/// Where `value` falls among the faces, from 0 to 1 with 0.5 for an average
+/// draw
+fn percentile(faces: Faces, value: i64) -> f64 {
+    let values = faces.values();
+    let count = (values.end() - values.start() + 1) as f64;
+    ((value - values.start()) as f64 + 0.5) / count
+}
+
+/// How often each face of one kind of die came up
+#[derive(Debug, Clone, PartialEq)]
+struct Tally {
+    faces: Faces,
+    counts: Vec<usize>,
+}
+
+impl Tally {
+    fn new(faces: Faces) -> Self {
+        Tally {
+            faces,
+            counts: vec![0; faces.values().count()],
+        }
+    }
+
+    fn add(&mut self, value: i64) {
+        let index = value - self.faces.values().start();
+        if let Some(count) = usize::try_from(index)
+            .ok()
+            .and_then(|i| self.counts.get_mut(i))
+        {
+            *count += 1;
+        }
+    }
+
+    fn total(&self) -> usize {
+        self.counts.iter().sum()
+    }
+
+    fn mean(&self) -> f64 {
+        let start = *self.faces.values().start();
+        let sum: f64 = self
+            .counts
+            .iter()
+            .enumerate()
+            .map(|(i, &count)| (start + i as i64) as f64 * count as f64)
+            .sum();
+        sum / self.total() as f64
+    }
+
+    fn expected_mean(&self) -> f64 {
+        let values = self.faces.values();
+        (values.start() + values.end()) as f64 / 2.0
+    }
+
+    /// Pearson's test of the counts against a fair die, as the statistic,
+    /// degrees of freedom and p-value; `None` with too few draws
+    fn chi_square(&self) -> Option<(f64, usize, f64)> {
+        let faces = self.counts.len();
+        let expected = self.total() as f64 / faces as f64;
+        if faces < 2 || expected < MIN_EXPECTED_PER_FACE {
+            return None;
+        }
+        let statistic = self
+            .counts
+            .iter()
+            .map(|&count| (count as f64 - expected).powi(2) / expected)
+            .sum();
+        let freedom = faces - 1;
+        Some((statistic, freedom, chi_square_p_value(statistic, freedom)))
+    }
+
+    /// Bar chart of the counts, grouping faces when there are many
+    fn histogram(&self) -> String {
+        let start = *self.faces.values().start();
+        let size = self.counts.len().div_ceil(HISTOGRAM_BUCKETS).max(1);
+        let buckets: Vec<(String, usize)> = self
+            .counts
+            .chunks(size)
+            .enumerate()
+            .map(|(i, chunk)| {
+                let low = start + (i * size) as i64;
+                let high = low + chunk.len() as i64 - 1;
+                let label = if low == high {
+                    low.to_string()
+                } else {
+                    format!("{}-{}", low, high)
+                };
+                (label, chunk.iter().sum())
+            })
+            .collect();
+        let most = buckets.iter().map(|(_, n)| *n).max().unwrap_or(0).max(1);
+        let label_width = buckets.iter().map(|(l, _)| l.len()).max().unwrap_or(0);
+        buckets
+            .iter()
+            .map(|(label, count)| {
+                let bar = "█".repeat((count * HISTOGRAM_WIDTH).div_ceil(most));
+                format!("{:>w$} {} {}", label, bar, count, w = label_width)
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Probability of a chi-square statistic at least this large for a fair die
+fn chi_square_p_value(statistic: f64, freedom: usize) -> f64 {
+    upper_gamma(freedom as f64 / 2.0, statistic / 2.0)
+}
+
+/// Regularized upper incomplete gamma function Q(a, x)
+fn upper_gamma(a: f64, x: f64) -> f64 {
+    const EPSILON: f64 = 1e-12;
+    const TINY: f64 = 1e-300;
+    if x <= 0.0 {
+        return 1.0;
+    }
+    let scale = (-x + a * x.ln() - ln_gamma(a)).exp();
+    if x < a + 1.0 {
+        // Series for the lower function converges quickly here
+        let (mut term, mut sum, mut n) = (1.0 / a, 1.0 / a, a);
+        for _ in 0..1000 {
+            n += 1.0;
+            term *= x / n;
+            sum += term;
+            if term.abs() < sum.abs() * EPSILON {
+                break;
+            }
+        }
+        (1.0 - sum * scale).max(0.0)
+    } else {
+        // Continued fraction, evaluated with Lentz's method
+        let mut b = x + 1.0 - a;
+        let mut c = 1.0 / TINY;
+        let mut d = 1.0 / b;
+        let mut h = d;
+        for i in 1..1000 {
+            let an = -(i as f64) * (i as f64 - a);
+            b += 2.0;
+            d = an * d + b;
+            if d.abs() < TINY {
+                d = TINY;
+            }
+            c = b + an / c;
+            if c.abs() < TINY {
+                c = TINY;
+            }
+            d = 1.0 / d;
+            let delta = d * c;
+            h *= delta;
+            if (delta - 1.0).abs() < EPSILON {
+                break;
+            }
+        }
+        scale * h
+    }
+}
+
+/// Lanczos approximation of ln Γ(x) for x > 0
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 6] = [
+        76.18009172947146,
+        -86.50532032941677,
+        24.01409824083091,
+        -1.231739572450155,
+        0.1208650973866179e-2,
+        -0.5395239384953e-5,
+    ];
+    let tmp = x + 5.5;
+    let tmp = tmp - (x + 0.5) * tmp.ln();
+    let mut series = 1.000000000190015;
+    let mut y = x;
+    for c in COEFFICIENTS {
+        y += 1.0;
+        series += c / y;
+    }
+    -tmp + (2.5066282746310005 * series / x).ln()
+}
+
+/// Tallies of every kind of die in `records`, leaving out seeded rolls
+fn tallies<'a>(records: impl Iterator<Item = &'a Record>) -> Vec<Tally> {
+    let mut tallies: BTreeMap<Faces, Tally> = BTreeMap::new();
+    for draws in records.filter(|r| !r.seeded).flat_map(|r| &r.draws) {
+        let tally = tallies
+            .entry(draws.faces)
+            .or_insert_with(|| Tally::new(draws.faces));
+        for &value in &draws.values {
+            tally.add(value);
+        }
+    }
+    tallies.into_values().collect()
+}
+
+/// Mean percentile of every die drawn in `records` and how many there were
+fn luck<'a>(records: impl Iterator<Item = &'a Record>) -> (f64, usize) {
+    let (sum, count) = records
+        .filter(|r| !r.seeded)
+        .flat_map(|r| &r.draws)
+        .flat_map(|d| d.values.iter().map(|&v| percentile(d.faces, v)))
+        .fold((0.0, 0), |(sum, count), p| (sum + p, count + 1));
+    (if count == 0 { 0.5 } else { sum / count as f64 }, count)
+}
+
+/// Whether `record` counts towards the stats `viewer` sees in `guild`.
+/// Outside a guild that is only their own DM rolls, which stay private.
+fn visible(record: &Record, guild: Option<u64>, viewer: u64) -> bool {
+    record.guild == guild && (guild.is_some() || record.user == viewer)
+}
+
+/// Ranked rollers as (name, luck, dice), luckiest first
+fn leaderboard(records: &[Record]) -> Vec<(String, f64, usize)> {
+    let mut by_user: HashMap<u64, Vec<&Record>> = HashMap::new();
+    for record in records {
+        by_user.entry(record.user).or_default().push(record);
+    }
+    let mut ranked: Vec<(String, f64, usize)> = by_user
+        .into_values()
+        .filter_map(|rolls| {
+            let (luck, draws) = luck(rolls.iter().copied());
+            let name = rolls.last()?.name.clone();
+            (draws >= MIN_RANKED_DRAWS).then_some((name, luck, draws))
+        })
+        .collect();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    ranked
+}
+
+/// One line per kind of die, for the most rolled ones in face order, then
+/// how many kinds were left out
+fn listing(tallies: &[Tally], line: impl Fn(&Tally) -> String) -> String {
+    let mut listed: Vec<&Tally> = tallies.iter().collect();
+    listed.sort_by_key(|t| std::cmp::Reverse(t.total()));
+    let left_out = listed.len().saturating_sub(MAX_LISTED_DICE);
+    listed.truncate(MAX_LISTED_DICE);
+    listed.sort_by_key(|t| t.faces);
+    let mut lines: Vec<String> = listed.into_iter().map(line).collect();
+    if left_out > 0 {
+        lines.push(format!("and {} more", left_out));
+    }
+    field_value(&lines.join("\n"))
+}
+
+/// `text` cut to fit in an embed field
+fn field_value(text: &str) -> String {
+    if text.chars().count() <= FIELD_LIMIT {
+        return text.to_string();
+    }
+    let mut shown: String = text.chars().take(FIELD_LIMIT - 1).collect();
+    shown.push('…');
+    shown
+}
+
+fn averages(tallies: &[Tally]) -> String {
+    listing(tallies, |t| {
+        format!(
+            "{}: {} dice, mean {:.2} (expected {:.2})",
+            t.faces,
+            t.total(),
+            t.mean(),
+            t.expected_mean()
+        )
+    })
+}
+
+fn fairness(tallies: &[Tally]) -> String {
+    if tallies.is_empty() {
+        return "No rolls yet".to_string();
+    }
+    listing(tallies, |t| match t.chi_square() {
+        Some((statistic, freedom, p)) => format!(
+            "{}: χ² = {:.1} with {} df, p = {:.3}{}",
+            t.faces,
+            statistic,
+            freedom,
+            p,
+            if p < SUSPICIOUS_P_VALUE {
+                " ⚠️ suspicious"
+            } else {
+                ""
+            }
+        ),
+        None => format!("{}: not enough rolls yet", t.faces),
+    })
+}
+
+fn ranking(entries: &[(String, f64, usize)]) -> String {
+    entries
+        .iter()
+        .enumerate()
+        .map(|(i, (name, luck, draws))| {
+            format!(
+                "{}. {} — {:.1}% ({} dice)",
+                i + 1,
+                name,
+                luck * 100.0,
+                draws
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Show someone's dice statistics, whether the bot's dice are fair and
+/// who is luckiest here
+#[poise::command(slash_command)]
+pub async fn stats(
+    ctx: Context<'_>,
+    #[description = "Whose rolls to show, yourself by default"] user: Option<serenity::User>,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().map(|g| g.get());
+    let viewer = ctx.author().id.get();
+    if guild.is_none() && user.as_ref().is_some_and(|u| u.id.get() != viewer) {
+        ctx.say("Only your own stats can be shown outside a server")
+            .await?;
+        return Ok(());
+    }
+    let user = user.as_ref().unwrap_or_else(|| ctx.author());
+    let embed = ctx.data().dice_history.view(|records| {
+        let here: Vec<Record> = records
+            .iter()
+            .filter(|r| visible(r, guild, viewer))
+            .cloned()
+            .collect();
+        let theirs = || here.iter().filter(|r| r.user == user.id.get());
+
+        let rolls = theirs().count();
+        let (their_luck, draws) = luck(theirs());
+        let their_tallies = tallies(theirs());
+        let mut embed = serenity::CreateEmbed::new()
+            .title(format!("Dice stats for {}", user.name))
+            .description(format!(
+                "{} rolls, {} dice. Luck: {:.1}% (50% is average)",
+                rolls,
+                draws,
+                their_luck * 100.0
+            ));
+        if !their_tallies.is_empty() {
+            embed = embed.field("Averages", averages(&their_tallies), false);
+        }
+        if let Some(most) = their_tallies.iter().max_by_key(|t| t.total()) {
+            embed = embed.field(
+                format!("{} distribution", most.faces),
+                format!("```\n{}\n```", most.histogram()),
+                false,
+            );
+        }
+
+        // Only rolls this viewer may see, so private DM rolls stay out
+        let scope = if guild.is_some() {
+            "Bot fairness in this server"
+        } else {
+            "Bot fairness in your DMs"
+        };
+        embed = embed.field(scope, fairness(&tallies(here.iter())), false);
+        // A DM has only one roller to rank
+        let ranked = if guild.is_some() {
+            leaderboard(&here)
+        } else {
+            Vec::new()
+        };
+        if !ranked.is_empty() {
+            let unluckiest: Vec<_> = ranked
+                .iter()
+                .rev()
+                .take(LEADERBOARD_SIZE)
+                .cloned()
+                .collect();
+            embed = embed
+                .field(
+                    "Luckiest",
+                    field_value(&ranking(&ranked[..ranked.len().min(LEADERBOARD_SIZE)])),
+                    true,
+                )
+                .field("Unluckiest", field_value(&ranking(&unluckiest)), true);
+        }
+        embed
+    });
+    ctx.send(poise::CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dice::history::Draws;
+
+    fn record(user: u64, faces: Faces, values: Vec<i64>) -> Record {
+        Record {
+            user,
+            name: format!("user{}", user),
+            guild: Some(1),
+            expression: String::new(),
+            total: values.iter().sum(),
+            timestamp: 0,
+            seeded: false,
+            draws: vec![Draws { faces, values }],
+        }
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-3
+    }
+
+    #[test]
+    fn test_p_value() {
+        assert!(close(chi_square_p_value(2.0, 2), (-1.0f64).exp()));
+        assert!(close(chi_square_p_value(3.841, 1), 0.05));
+        assert!(close(chi_square_p_value(18.307, 10), 0.05));
+        assert!(close(chi_square_p_value(0.0, 5), 1.0));
+        assert!(chi_square_p_value(500.0, 5) < 1e-12);
+    }
+
+    #[test]
+    fn test_percentile() {
+        assert_eq!(percentile(Faces::Sides(2), 1), 0.25);
+        assert_eq!(percentile(Faces::Sides(2), 2), 0.75);
+        assert_eq!(percentile(Faces::Fudge, 0), 0.5);
+    }
+
+    #[test]
+    fn test_tally() {
+        let fair: Vec<i64> = (0..60).map(|i| i % 6 + 1).collect();
+        let tally = &tallies([record(1, Faces::Sides(6), fair)].iter())[0];
+        assert_eq!(tally.counts, vec![10; 6]);
+        assert_eq!(tally.mean(), 3.5);
+        assert_eq!(tally.expected_mean(), 3.5);
+        assert_eq!(tally.chi_square(), Some((0.0, 5, 1.0)));
+
+        let loaded = &tallies([record(1, Faces::Sides(6), vec![6; 60])].iter())[0];
+        assert!(loaded.chi_square().unwrap().2 < SUSPICIOUS_P_VALUE);
+        let few = &tallies([record(1, Faces::Sides(20), vec![1; 20])].iter())[0];
+        assert_eq!(few.chi_square(), None);
+    }
+
+    #[test]
+    fn test_seeded_rolls_are_ignored() {
+        let mut seeded = record(1, Faces::Sides(6), vec![6; 10]);
+        seeded.seeded = true;
+        assert!(tallies([&seeded].into_iter()).is_empty());
+        assert_eq!(luck([&seeded].into_iter()), (0.5, 0));
+    }
+
+    #[test]
+    fn test_histogram() {
+        let mut tally = Tally::new(Faces::Sides(4));
+        for value in [1, 1, 2, 4, 4, 4, 4] {
+            tally.add(value);
+        }
+        assert_eq!(
+            tally.histogram(),
+            "1 ████████ 2\n2 ████ 1\n3  0\n4 ████████████████ 4"
+        );
+        let d100 = Tally::new(Faces::Sides(100)).histogram();
+        assert_eq!(d100.lines().count(), 10);
+        assert!(d100.starts_with("  1-10  0"));
+    }
+
+    #[test]
+    fn test_leaderboard() {
+        let records = vec![
+            record(1, Faces::Sides(6), vec![6; 30]),
+            record(2, Faces::Sides(6), vec![1; 30]),
+            record(3, Faces::Sides(6), vec![3; 40]),
+            // Too few dice to be ranked
+            record(4, Faces::Sides(6), vec![6; 5]),
+        ];
+        let names: Vec<String> = leaderboard(&records).into_iter().map(|e| e.0).collect();
+        assert_eq!(names, vec!["user1", "user3", "user2"]);
+    }
+
+    #[test]
+    fn test_dm_rolls_stay_private() {
+        let mut dm = record(1, Faces::Sides(6), vec![6]);
+        dm.guild = None;
+        let guild = record(1, Faces::Sides(6), vec![6]);
+        assert!(visible(&dm, None, 1));
+        assert!(!visible(&dm, None, 2));
+        assert!(!visible(&dm, Some(1), 1));
+        assert!(visible(&guild, Some(1), 2));
+        assert!(!visible(&guild, None, 1));
+    }
+
+    #[test]
+    fn test_listings_fit_a_field() {
+        // Fifty kinds of dice, the d51 rolled most
+        let records: Vec<Record> = (2..52)
+            .map(|sides| record(1, Faces::Sides(sides), vec![1; sides as usize]))
+            .collect();
+        let tallies = tallies(records.iter());
+        for field in [averages(&tallies), fairness(&tallies)] {
+            assert!(field.chars().count() <= FIELD_LIMIT);
+            assert_eq!(field.lines().count(), MAX_LISTED_DICE + 1);
+            assert!(field.starts_with("d42: "));
+            assert!(field.ends_with("and 40 more"));
+        }
+    }
+}
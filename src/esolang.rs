@@ -0,0 +1,365 @@
+use crate::brainfuck::Brainfuck;
+use crate::context::{Context, Error};
+use crate::sandbox::CancelToken;
+use poise::serenity_prelude as serenity;
+use std::collections::VecDeque;
+
+mod befunge;
+mod ook;
+mod stack;
+mod whitespace;
+
+pub const MAX_STEPS: usize = 5_000_000;
+const MESSAGE_LIMIT: usize = 2000;
+// How many charges to accept between two checks for cancellation
+const CANCEL_CHECK_INTERVAL: usize = 1024;
+
+/// An interpreter that can be run from a slash command
+pub trait EsolangInterpreter: Send + Sync {
+    /// Language name used in replies
+    fn name(&self) -> &'static str;
+
+    /// Whether the `inputs` text is fed to the program as UTF-8 bytes
+    /// rather than as Unicode code points
+    fn byte_input(&self) -> bool {
+        false
+    }
+
+    /// Largest value accepted from `ascii_codes`
+    fn max_input(&self) -> u32 {
+        u32::MAX
+    }
+
+    /// Run `code` to completion within `budget`, returning its output, or the
+    /// error and the output produced before it
+    fn run(
+        &self,
+        code: &str,
+        inputs: Vec<u32>,
+        budget: Budget,
+    ) -> Result<Vec<u8>, (String, Vec<u8>)>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, poise::ChoiceParameter)]
+pub enum Language {
+    Brainfuck,
+    #[name = "Befunge-93"]
+    Befunge,
+    Whitespace,
+    #[name = "Ook!"]
+    Ook,
+    #[name = "Stack"]
+    Stack,
+}
+
+impl Language {
+    fn interpreter(self) -> Box<dyn EsolangInterpreter> {
+        match self {
+            Language::Brainfuck => Box::new(Brainfuck::default()),
+            Language::Befunge => Box::new(befunge::Befunge),
+            Language::Whitespace => Box::new(whitespace::Whitespace),
+            Language::Ook => Box::new(ook::Ook),
+            Language::Stack => Box::new(stack::Stack),
+        }
+    }
+}
+
+/// Run code in one of the supported esoteric languages
+#[poise::command(slash_command)]
+pub async fn esolang(
+    ctx: Context<'_>,
+    #[description = "Language"] language: Language,
+    #[description = "code"] code: String,
+    #[description = "inputs"] inputs_str: Option<String>,
+    #[description = "ascii_codes"] ascii_codes_str: Option<String>,
+) -> Result<(), Error> {
+    let interpreter = language.interpreter();
+    let inputs = match parse_inputs(interpreter.as_ref(), inputs_str, ascii_codes_str) {
+        Ok(inputs) => inputs,
+        Err(e) => {
+            ctx.say(e).await?;
+            return Ok(());
+        }
+    };
+    respond(ctx, interpreter, code, inputs).await
+}
+
+/// Turn the `inputs`/`ascii_codes` command options into the values fed to the program
+pub fn parse_inputs(
+    interpreter: &dyn EsolangInterpreter,
+    inputs_str: Option<String>,
+    ascii_codes_str: Option<String>,
+) -> Result<Vec<u32>, String> {
+    match (inputs_str, ascii_codes_str) {
+        (Some(_), Some(_)) => Err(
+            "Both 'inputs' and 'ascii_codes' were provided. Please provide only one.".to_string(),
+        ),
+        (Some(input_str), None) if interpreter.byte_input() => {
+            Ok(input_str.bytes().map(u32::from).collect())
+        }
+        (Some(input_str), None) => Ok(input_str.chars().map(u32::from).collect()),
+        (None, Some(ascii_codes)) => Ok(ascii_codes
+            .split_whitespace()
+            .filter_map(|s| s.parse::<u32>().ok())
+            .filter(|&code| code <= interpreter.max_input())
+            .collect()),
+        (None, None) => Ok(Vec::new()),
+    }
+}
+
+/// Run `code` in the sandbox and reply with its output or the error that stopped it
+pub async fn respond(
+    ctx: Context<'_>,
+    interpreter: Box<dyn EsolangInterpreter>,
+    code: String,
+    inputs: Vec<u32>,
+) -> Result<(), Error> {
+    let name = interpreter.name();
+    let job = move |cancel| interpreter.run(&code, inputs, Budget::new(cancel));
+    match ctx.data().sandbox.run(ctx.author().id, job).await {
+        Ok(result) => ctx.send(result_reply(name, result)).await?,
+        Err(e) => {
+            ctx.say(format!("Error executing {} code: {}", name, e))
+                .await?
+        }
+    };
+    Ok(())
+}
+
+fn result_reply(name: &str, result: Result<Vec<u8>, (String, Vec<u8>)>) -> poise::CreateReply {
+    match result {
+        Ok(output) if output.is_empty() => poise::CreateReply::default()
+            .content("Output:\n```\nThe program produced no output.\n```"),
+        Ok(output) => output_reply("Output", &output),
+        Err((error, partial_output)) if partial_output.is_empty() => {
+            poise::CreateReply::default().content(format!(
+                "Error executing {} code: {}\n\nNo output was produced before the program was terminated.",
+                name, error
+            ))
+        }
+        Err((error, partial_output)) => output_reply(
+            &format!(
+                "Error executing {} code: {}\n\nPartial output before termination",
+                name, error
+            ),
+            &partial_output,
+        ),
+    }
+}
+
+/// Show `output` under `header` in a code block, as text when it is valid
+/// UTF-8 and as a hex dump otherwise. Output that does not fit in a message
+/// is attached as a file instead.
+fn output_reply(header: &str, output: &[u8]) -> poise::CreateReply {
+    let (rendered, filename) = match std::str::from_utf8(output) {
+        Ok(text) => (text.to_string(), "output.txt"),
+        Err(_) => (hex_dump(output), "output.bin"),
+    };
+    let message = format!("{}:\n```\n{}\n```", header, rendered);
+    if message.chars().count() <= MESSAGE_LIMIT {
+        return poise::CreateReply::default().content(message);
+    }
+    poise::CreateReply::default()
+        .content(format!("{} ({} bytes) is attached.", header, output.len()))
+        .attachment(serenity::CreateAttachment::bytes(output.to_vec(), filename))
+}
+
+/// Format bytes like `xxd`: offset, sixteen hex bytes and their printable characters per line
+fn hex_dump(bytes: &[u8]) -> String {
+    bytes
+        .chunks(16)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+            let printable: String = chunk
+                .iter()
+                .map(|&b| {
+                    if b.is_ascii_graphic() || b == b' ' {
+                        b as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+            format!("{:08x}  {:<47}  {}", i * 16, hex.join(" "), printable)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Step limit shared by all interpreters, which also stops the program once
+/// the sandbox cancels it
+#[derive(Debug, Clone, Default)]
+pub struct Budget {
+    steps: usize,
+    charges: usize,
+    cancel: CancelToken,
+}
+
+impl Budget {
+    pub fn new(cancel: CancelToken) -> Self {
+        Budget {
+            cancel,
+            ..Budget::default()
+        }
+    }
+
+    /// Source-level instructions executed so far
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    /// Answer to `cancel` from now on, e.g. when a paused program resumes in a new job
+    pub fn watch(&mut self, cancel: CancelToken) {
+        self.cancel = cancel;
+    }
+
+    /// Account for `cost` instructions about to be executed
+    pub fn charge(&mut self, cost: usize) -> Result<(), String> {
+        if self.steps.saturating_add(cost) > MAX_STEPS {
+            return Err("Instruction limit exceeded".to_string());
+        }
+        self.steps += cost;
+        self.charges += 1;
+        if self.charges.is_multiple_of(CANCEL_CHECK_INTERVAL) && self.cancel.is_cancelled() {
+            return Err("Execution time limit exceeded".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Write a character-valued cell: values below 256 are written as a raw
+/// byte, larger ones as the UTF-8 encoding of that code point
+pub fn write_char(output: &mut Vec<u8>, value: i64) {
+    match u8::try_from(value) {
+        Ok(byte) => output.push(byte),
+        Err(_) => {
+            let c = u32::try_from(value)
+                .ok()
+                .and_then(char::from_u32)
+                .unwrap_or('\u{FFFD}');
+            output.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+        }
+    }
+}
+
+/// Read a decimal integer from the input, skipping anything before it
+pub fn read_number(input: &mut VecDeque<u32>) -> Option<i64> {
+    let is_digit = |c: u32| char::from_u32(c).is_some_and(|c| c.is_ascii_digit());
+    while let Some(&c) = input.front() {
+        if is_digit(c) || (c == '-' as u32 && input.get(1).is_some_and(|&d| is_digit(d))) {
+            break;
+        }
+        input.pop_front();
+    }
+    let negative = input.front() == Some(&('-' as u32));
+    if negative {
+        input.pop_front();
+    }
+    let mut value: Option<i64> = None;
+    while let Some(&c) = input.front() {
+        if !is_digit(c) {
+            break;
+        }
+        input.pop_front();
+        let digit = i64::from(c - '0' as u32);
+        value = Some(value.unwrap_or(0).saturating_mul(10).saturating_add(digit));
+    }
+    value.map(|v| if negative { -v } else { v })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex_dump() {
+        let bytes: Vec<u8> = (0x3E..0x50).chain([0xFF]).collect();
+        assert_eq!(
+            hex_dump(&bytes),
+            "00000000  3e 3f 40 41 42 43 44 45 46 47 48 49 4a 4b 4c 4d  >?@ABCDEFGHIJKLM\n\
+             00000010  4e 4f ff                                         NO."
+        );
+    }
+
+    #[test]
+    fn test_output_reply() {
+        let reply = output_reply("Output", b"Hi");
+        assert_eq!(reply.content.as_deref(), Some("Output:\n```\nHi\n```"));
+        assert!(reply.attachments.is_empty());
+
+        let reply = output_reply("Output", &[0xC3, 0x28]);
+        assert_eq!(
+            reply.content.as_deref(),
+            Some(
+                "Output:\n```\n00000000  c3 28                                            .(\n```"
+            )
+        );
+
+        let reply = output_reply("Output", &[b'A'; MESSAGE_LIMIT]);
+        assert_eq!(
+            reply.content.as_deref(),
+            Some("Output (2000 bytes) is attached.")
+        );
+        assert_eq!(reply.attachments.len(), 1);
+        assert_eq!(reply.attachments[0].filename, "output.txt");
+    }
+
+    #[test]
+    fn test_parse_inputs() {
+        let bf = Brainfuck::default();
+        assert_eq!(
+            parse_inputs(&bf, Some("é".to_string()), None),
+            Ok(vec![0xC3, 0xA9])
+        );
+        assert_eq!(
+            parse_inputs(&stack::Stack, Some("é".to_string()), None),
+            Ok(vec![0xE9])
+        );
+        assert_eq!(
+            parse_inputs(&bf, None, Some("72 x 105 300".to_string())),
+            Ok(vec![72, 105])
+        );
+        assert!(parse_inputs(&bf, Some(String::new()), Some(String::new())).is_err());
+    }
+
+    #[test]
+    fn test_budget_step_limit() {
+        let mut budget = Budget::default();
+        assert_eq!(budget.charge(MAX_STEPS), Ok(()));
+        assert_eq!(
+            budget.charge(1),
+            Err("Instruction limit exceeded".to_string())
+        );
+        assert_eq!(budget.steps(), MAX_STEPS);
+    }
+
+    #[test]
+    fn test_budget_cancel() {
+        let cancel = CancelToken::default();
+        let mut budget = Budget::new(cancel.clone());
+        cancel.cancel();
+        let result = (0..CANCEL_CHECK_INTERVAL).try_for_each(|_| budget.charge(1));
+        assert_eq!(result, Err("Execution time limit exceeded".to_string()));
+        assert_eq!(budget.steps(), CANCEL_CHECK_INTERVAL);
+    }
+
+    #[test]
+    fn test_write_char() {
+        let mut output = Vec::new();
+        write_char(&mut output, 65);
+        write_char(&mut output, 0xFF);
+        write_char(&mut output, 0x141);
+        write_char(&mut output, -1);
+        assert_eq!(output, [&[65, 0xFF][..], "Ł\u{FFFD}".as_bytes()].concat());
+    }
+
+    #[test]
+    fn test_read_number() {
+        let mut input: VecDeque<u32> = "x 12 -7-".chars().map(u32::from).collect();
+        assert_eq!(read_number(&mut input), Some(12));
+        assert_eq!(read_number(&mut input), Some(-7));
+        assert_eq!(read_number(&mut input), None);
+        assert!(input.is_empty());
+    }
+}
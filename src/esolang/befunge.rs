@@ -0,0 +1,266 @@
+use super::{read_number, write_char, Budget, EsolangInterpreter};
+use std::collections::VecDeque;
+
+const WIDTH: usize = 80;
+const HEIGHT: usize = 25;
+
+/// Befunge-93 on the standard 80x25 torus
+pub struct Befunge;
+
+impl EsolangInterpreter for Befunge {
+    fn name(&self) -> &'static str {
+        "Befunge-93"
+    }
+
+    fn run(
+        &self,
+        code: &str,
+        inputs: Vec<u32>,
+        budget: Budget,
+    ) -> Result<Vec<u8>, (String, Vec<u8>)> {
+        let mut output = Vec::new();
+        let grid = load(code).map_err(|e| (e, Vec::new()))?;
+        match execute(grid, inputs.into_iter().collect(), &mut output, budget) {
+            Ok(()) => Ok(output),
+            Err(e) => Err((e, output)),
+        }
+    }
+}
+
+/// Lay the source out on the playfield, padding short lines with spaces
+fn load(code: &str) -> Result<Vec<Vec<i64>>, String> {
+    let lines: Vec<&str> = code.lines().collect();
+    if lines.len() > HEIGHT {
+        return Err(format!("Program is taller than {} lines", HEIGHT));
+    }
+    let mut grid = vec![vec![' ' as i64; WIDTH]; HEIGHT];
+    for (y, line) in lines.iter().enumerate() {
+        let chars: Vec<char> = line.chars().collect();
+        if chars.len() > WIDTH {
+            return Err(format!("Line {} is wider than {} characters", y + 1, WIDTH));
+        }
+        for (x, c) in chars.into_iter().enumerate() {
+            grid[y][x] = c as i64;
+        }
+    }
+    Ok(grid)
+}
+
+fn execute(
+    mut grid: Vec<Vec<i64>>,
+    mut input: VecDeque<u32>,
+    output: &mut Vec<u8>,
+    mut budget: Budget,
+) -> Result<(), String> {
+    let mut stack: Vec<i64> = Vec::new();
+    let (mut x, mut y) = (0usize, 0usize);
+    let (mut dx, mut dy) = (1isize, 0isize);
+    let mut string_mode = false;
+    // Popping an empty stack yields 0 in Befunge
+    let pop = |stack: &mut Vec<i64>| stack.pop().unwrap_or(0);
+
+    loop {
+        budget.charge(1)?;
+        let cell = grid[y][x];
+        let mut skip = false;
+
+        if string_mode {
+            if cell == '"' as i64 {
+                string_mode = false;
+            } else {
+                stack.push(cell);
+            }
+        } else {
+            let Some(instruction) = u32::try_from(cell).ok().and_then(char::from_u32) else {
+                return Err(format!("Unknown instruction {} at ({}, {})", cell, x, y));
+            };
+            match instruction {
+                c @ '0'..='9' => stack.push(c as i64 - '0' as i64),
+                c @ ('+' | '-' | '*' | '/' | '%' | '`') => {
+                    let b = pop(&mut stack);
+                    let a = pop(&mut stack);
+                    stack.push(match c {
+                        '+' => a.wrapping_add(b),
+                        '-' => a.wrapping_sub(b),
+                        '*' => a.wrapping_mul(b),
+                        // Division by zero pushes 0 instead of asking the user
+                        '/' => a.checked_div(b).unwrap_or(0),
+                        '%' => a.checked_rem(b).unwrap_or(0),
+                        _ => (a > b) as i64,
+                    });
+                }
+                '!' => {
+                    let a = pop(&mut stack);
+                    stack.push((a == 0) as i64);
+                }
+                '>' => (dx, dy) = (1, 0),
+                '<' => (dx, dy) = (-1, 0),
+                '^' => (dx, dy) = (0, -1),
+                'v' => (dx, dy) = (0, 1),
+                '?' => {
+                    (dx, dy) = match rand::random::<u8>() % 4 {
+                        0 => (1, 0),
+                        1 => (-1, 0),
+                        2 => (0, -1),
+                        _ => (0, 1),
+                    }
+                }
+                '_' => {
+                    (dx, dy) = if pop(&mut stack) == 0 {
+                        (1, 0)
+                    } else {
+                        (-1, 0)
+                    }
+                }
+                '|' => {
+                    (dx, dy) = if pop(&mut stack) == 0 {
+                        (0, 1)
+                    } else {
+                        (0, -1)
+                    }
+                }
+                '"' => string_mode = true,
+                ':' => {
+                    let a = pop(&mut stack);
+                    stack.extend([a, a]);
+                }
+                '\\' => {
+                    let b = pop(&mut stack);
+                    let a = pop(&mut stack);
+                    stack.extend([b, a]);
+                }
+                '$' => {
+                    pop(&mut stack);
+                }
+                '.' => {
+                    let a = pop(&mut stack);
+                    output.extend_from_slice(format!("{} ", a).as_bytes());
+                }
+                ',' => write_char(output, pop(&mut stack)),
+                '#' => skip = true,
+                'g' => {
+                    let gy = pop(&mut stack);
+                    let gx = pop(&mut stack);
+                    stack.push(cell_at(gx, gy).map_or(0, |(gx, gy)| grid[gy][gx]));
+                }
+                'p' => {
+                    let py = pop(&mut stack);
+                    let px = pop(&mut stack);
+                    let value = pop(&mut stack);
+                    if let Some((px, py)) = cell_at(px, py) {
+                        grid[py][px] = value;
+                    }
+                }
+                '&' => stack.push(read_number(&mut input).unwrap_or(-1)),
+                '~' => stack.push(input.pop_front().map_or(-1, i64::from)),
+                '@' => return Ok(()),
+                ' ' => {}
+                c => return Err(format!("Unknown instruction '{}' at ({}, {})", c, x, y)),
+            }
+        }
+
+        for _ in 0..if skip { 2 } else { 1 } {
+            x = (x as isize + dx).rem_euclid(WIDTH as isize) as usize;
+            y = (y as isize + dy).rem_euclid(HEIGHT as isize) as usize;
+        }
+    }
+}
+
+/// Playfield coordinates for `g`/`p`, or `None` when they are off the playfield
+fn cell_at(x: i64, y: i64) -> Option<(usize, usize)> {
+    let x = usize::try_from(x).ok().filter(|&x| x < WIDTH)?;
+    let y = usize::try_from(y).ok().filter(|&y| y < HEIGHT)?;
+    Some((x, y))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hello_world() {
+        let code = "\"!dlroW ,olleH\">:#,_@";
+        assert_eq!(
+            Befunge.run(code, vec![], Budget::default()),
+            Ok(b"Hello, World!".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_arithmetic_and_numbers() {
+        assert_eq!(
+            Befunge.run("93*4-.52%.@", vec![], Budget::default()),
+            Ok(b"23 1 ".to_vec())
+        );
+        assert_eq!(
+            Befunge.run("10/.@", vec![], Budget::default()),
+            Ok(b"0 ".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_directions_wrap() {
+        // Runs off the left edge and wraps to the right end of the line
+        let code = "<@,+55.1";
+        assert_eq!(
+            Befunge.run(code, vec![], Budget::default()),
+            Ok(b"1 \n".to_vec())
+        );
+
+        let code = "v\n2\n.\n@";
+        assert_eq!(
+            Befunge.run(code, vec![], Budget::default()),
+            Ok(b"2 ".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_input() {
+        let inputs: Vec<u32> = "12 30".chars().map(u32::from).collect();
+        assert_eq!(
+            Befunge.run("&&+.@", inputs, Budget::default()),
+            Ok(b"42 ".to_vec())
+        );
+        assert_eq!(
+            Befunge.run("~,~.@", vec![65], Budget::default()),
+            Ok(b"A-1 ".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_get_and_put() {
+        // Writes '@' over the 'X' at (6, 0) and then runs into it
+        let code = "88*60pX";
+        assert_eq!(Befunge.run(code, vec![], Budget::default()), Ok(Vec::new()));
+        assert_eq!(
+            Befunge.run("10g,@", vec![], Budget::default()),
+            Ok(b"0".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            Befunge.run("1.x", vec![], Budget::default()),
+            Err((
+                "Unknown instruction 'x' at (2, 0)".to_string(),
+                b"1 ".to_vec()
+            ))
+        );
+        assert_eq!(
+            Befunge.run("1.€", vec![], Budget::default()),
+            Err((
+                "Unknown instruction '€' at (2, 0)".to_string(),
+                b"1 ".to_vec()
+            ))
+        );
+        assert_eq!(
+            Befunge.run("01-00p", vec![], Budget::default()),
+            Err(("Unknown instruction -1 at (0, 0)".to_string(), Vec::new()))
+        );
+        assert_eq!(
+            Befunge.run(">", vec![], Budget::default()),
+            Err(("Instruction limit exceeded".to_string(), Vec::new()))
+        );
+    }
+}
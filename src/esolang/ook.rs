@@ -0,0 +1,103 @@
+use super::{Budget, EsolangInterpreter};
+use crate::brainfuck::{interpret_brainfuck, Dialect};
+
+/// Ook!, a Brainfuck with every instruction spelled as a pair of Ook words
+pub struct Ook;
+
+impl EsolangInterpreter for Ook {
+    fn name(&self) -> &'static str {
+        "Ook!"
+    }
+
+    fn byte_input(&self) -> bool {
+        true
+    }
+
+    fn max_input(&self) -> u32 {
+        0xFF
+    }
+
+    fn run(
+        &self,
+        code: &str,
+        inputs: Vec<u32>,
+        mut budget: Budget,
+    ) -> Result<Vec<u8>, (String, Vec<u8>)> {
+        let source = to_brainfuck(code).map_err(|e| (e, Vec::new()))?;
+        interpret_brainfuck(source, inputs, &Dialect::default(), &mut budget)
+    }
+}
+
+/// Translate Ook! into Brainfuck, one character per pair of words, so
+/// positions in Brainfuck errors are the index of the offending pair
+fn to_brainfuck(code: &str) -> Result<String, String> {
+    let words: Vec<&str> = code
+        .split_whitespace()
+        .filter(|word| matches!(*word, "Ook." | "Ook?" | "Ook!"))
+        .collect();
+    if !words.len().is_multiple_of(2) {
+        return Err("Odd number of Ook words".to_string());
+    }
+
+    words
+        .chunks(2)
+        .enumerate()
+        .map(|(i, pair)| match (pair[0], pair[1]) {
+            ("Ook.", "Ook?") => Ok('>'),
+            ("Ook?", "Ook.") => Ok('<'),
+            ("Ook.", "Ook.") => Ok('+'),
+            ("Ook!", "Ook!") => Ok('-'),
+            ("Ook!", "Ook.") => Ok('.'),
+            ("Ook.", "Ook!") => Ok(','),
+            ("Ook!", "Ook?") => Ok('['),
+            ("Ook?", "Ook!") => Ok(']'),
+            (a, b) => Err(format!(
+                "Unknown instruction '{} {}' at position {}",
+                a, b, i
+            )),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_brainfuck() {
+        let code =
+            "Ook. Ook? Ook? Ook. Ook. Ook. Ook! Ook! Ook! Ook. Ook. Ook! Ook! Ook? Ook? Ook!";
+        assert_eq!(to_brainfuck(code), Ok("><+-.,[]".to_string()));
+    }
+
+    #[test]
+    fn test_echo() {
+        let code = "Ook. Ook! Ook! Ook. Ook. Ook. Ook! Ook.";
+        assert_eq!(
+            Ook.run(code, vec![64], Budget::default()),
+            Ok(b"@A".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_bad_code() {
+        assert_eq!(
+            Ook.run("Ook. Ook. Ook.", vec![], Budget::default()),
+            Err(("Odd number of Ook words".to_string(), Vec::new()))
+        );
+        assert_eq!(
+            Ook.run("Ook? Ook?", vec![], Budget::default()),
+            Err((
+                "Unknown instruction 'Ook? Ook?' at position 0".to_string(),
+                Vec::new()
+            ))
+        );
+        assert_eq!(
+            Ook.run("Ook! Ook?", vec![], Budget::default()),
+            Err((
+                "Unmatched opening bracket at position 0".to_string(),
+                Vec::new()
+            ))
+        );
+    }
+}
@@ -0,0 +1,226 @@
+use super::{write_char, Budget, EsolangInterpreter};
+use std::collections::VecDeque;
+
+/// A tiny stack language of whitespace separated words:
+///
+/// - integers push themselves
+/// - `+ - * / %` and the comparisons `= < >` pop two values and push the result
+/// - `dup drop swap over` shuffle the stack
+/// - `.` prints the top as a number, `emit` as a character
+/// - `key` pushes the next input value, or -1 at the end of the input
+/// - `[ ... ]` loops while the top of the stack is non-zero, without popping it
+pub struct Stack;
+
+#[derive(Debug)]
+enum Word {
+    Push(i64),
+    Binary(fn(i64, i64) -> Option<i64>),
+    Dup,
+    Drop,
+    Swap,
+    Over,
+    Print,
+    Emit,
+    Key,
+    /// Holding the index of the matching `]`
+    LoopStart(usize),
+    /// Holding the index of the matching `[`
+    LoopEnd(usize),
+}
+
+impl EsolangInterpreter for Stack {
+    fn name(&self) -> &'static str {
+        "Stack"
+    }
+
+    fn run(
+        &self,
+        code: &str,
+        inputs: Vec<u32>,
+        budget: Budget,
+    ) -> Result<Vec<u8>, (String, Vec<u8>)> {
+        let program = parse(code).map_err(|e| (e, Vec::new()))?;
+        let mut output = Vec::new();
+        match execute(&program, inputs.into_iter().collect(), &mut output, budget) {
+            Ok(()) => Ok(output),
+            Err(e) => Err((e, output)),
+        }
+    }
+}
+
+fn parse(code: &str) -> Result<Vec<Word>, String> {
+    let mut program = Vec::new();
+    let mut loop_stack = Vec::new();
+    for (i, token) in code.split_whitespace().enumerate() {
+        let word = match token {
+            "+" => Word::Binary(|a, b| Some(a.wrapping_add(b))),
+            "-" => Word::Binary(|a, b| Some(a.wrapping_sub(b))),
+            "*" => Word::Binary(|a, b| Some(a.wrapping_mul(b))),
+            "/" => Word::Binary(|a, b| a.checked_div(b)),
+            // The remainder of `i64::MIN / -1` is 0 even though the quotient overflows
+            "%" => Word::Binary(|a, b| (b != 0).then(|| a.wrapping_rem(b))),
+            "=" => Word::Binary(|a, b| Some((a == b) as i64)),
+            "<" => Word::Binary(|a, b| Some((a < b) as i64)),
+            ">" => Word::Binary(|a, b| Some((a > b) as i64)),
+            "dup" => Word::Dup,
+            "drop" => Word::Drop,
+            "swap" => Word::Swap,
+            "over" => Word::Over,
+            "." => Word::Print,
+            "emit" => Word::Emit,
+            "key" => Word::Key,
+            "[" => {
+                loop_stack.push((i, program.len()));
+                Word::LoopStart(0)
+            }
+            "]" => {
+                let (_, open) = loop_stack
+                    .pop()
+                    .ok_or_else(|| format!("Unmatched ']' at word {}", i))?;
+                program[open] = Word::LoopStart(program.len());
+                Word::LoopEnd(open)
+            }
+            _ => Word::Push(
+                token
+                    .parse()
+                    .map_err(|_| format!("Unknown word '{}' at word {}", token, i))?,
+            ),
+        };
+        program.push(word);
+    }
+    if let Some((i, _)) = loop_stack.pop() {
+        return Err(format!("Unmatched '[' at word {}", i));
+    }
+    Ok(program)
+}
+
+fn execute(
+    program: &[Word],
+    mut input: VecDeque<u32>,
+    output: &mut Vec<u8>,
+    mut budget: Budget,
+) -> Result<(), String> {
+    let mut stack: Vec<i64> = Vec::new();
+    let mut pc = 0;
+    let underflow = || "Stack underflow".to_string();
+
+    while pc < program.len() {
+        budget.charge(1)?;
+        match &program[pc] {
+            Word::Push(value) => stack.push(*value),
+            Word::Binary(f) => {
+                let b = stack.pop().ok_or_else(underflow)?;
+                let a = stack.pop().ok_or_else(underflow)?;
+                let error = if b == 0 {
+                    "Division by zero"
+                } else {
+                    "Division overflow"
+                };
+                stack.push(f(a, b).ok_or(error)?);
+            }
+            Word::Dup => stack.push(*stack.last().ok_or_else(underflow)?),
+            Word::Drop => {
+                stack.pop().ok_or_else(underflow)?;
+            }
+            Word::Swap => {
+                let len = stack.len();
+                if len < 2 {
+                    return Err(underflow());
+                }
+                stack.swap(len - 1, len - 2);
+            }
+            Word::Over => {
+                let len = stack.len();
+                if len < 2 {
+                    return Err(underflow());
+                }
+                stack.push(stack[len - 2]);
+            }
+            Word::Print => {
+                let value = stack.pop().ok_or_else(underflow)?;
+                output.extend_from_slice(value.to_string().as_bytes());
+            }
+            Word::Emit => write_char(output, stack.pop().ok_or_else(underflow)?),
+            Word::Key => stack.push(input.pop_front().map_or(-1, i64::from)),
+            Word::LoopStart(end) => {
+                if *stack.last().ok_or_else(underflow)? == 0 {
+                    pc = *end;
+                }
+            }
+            Word::LoopEnd(start) => {
+                if *stack.last().ok_or_else(underflow)? != 0 {
+                    pc = *start;
+                }
+            }
+        }
+        pc += 1;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arithmetic() {
+        assert_eq!(
+            Stack.run("2 3 + 4 * .", vec![], Budget::default()),
+            Ok(b"20".to_vec())
+        );
+        assert_eq!(
+            Stack.run("7 2 % 7 2 / - .", vec![], Budget::default()),
+            Ok(b"-2".to_vec())
+        );
+        assert_eq!(
+            Stack.run("1 2 < 2 1 < 3 3 = + + .", vec![], Budget::default()),
+            Ok(b"2".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_countdown() {
+        assert_eq!(
+            Stack.run("3 [ dup . 32 emit 1 - ] drop", vec![], Budget::default()),
+            Ok(b"3 2 1 ".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_echo_input() {
+        // Print every input character until `key` returns -1
+        let code = "key 1 + [ 1 - emit key 1 + ]";
+        assert_eq!(
+            Stack.run(code, vec![72, 105], Budget::default()),
+            Ok(b"Hi".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            Stack.run("1 . +", vec![], Budget::default()),
+            Err(("Stack underflow".to_string(), b"1".to_vec()))
+        );
+        assert_eq!(
+            Stack.run("1 0 /", vec![], Budget::default()),
+            Err(("Division by zero".to_string(), Vec::new()))
+        );
+        assert_eq!(
+            Stack.run(&format!("{} -1 /", i64::MIN), vec![], Budget::default()),
+            Err(("Division overflow".to_string(), Vec::new()))
+        );
+        assert_eq!(
+            Stack.run("1 foo", vec![], Budget::default()),
+            Err(("Unknown word 'foo' at word 1".to_string(), Vec::new()))
+        );
+        assert_eq!(
+            Stack.run("1 [", vec![], Budget::default()),
+            Err(("Unmatched '[' at word 1".to_string(), Vec::new()))
+        );
+        assert_eq!(
+            Stack.run("1 [ ]", vec![], Budget::default()),
+            Err(("Instruction limit exceeded".to_string(), Vec::new()))
+        );
+    }
+}
@@ -0,0 +1,451 @@
+use super::{read_number, write_char, Budget, EsolangInterpreter};
+use std::collections::{HashMap, VecDeque};
+
+/// Whitespace, where only spaces, tabs and line feeds are code
+pub struct Whitespace;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Instr {
+    Push(i64),
+    Dup,
+    Copy(i64),
+    Swap,
+    Discard,
+    Slide(i64),
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Store,
+    Retrieve,
+    Mark,
+    /// Flow control holds the index of the target label's `Mark`
+    Call(usize),
+    Jump(usize),
+    JumpIfZero(usize),
+    JumpIfNegative(usize),
+    Return,
+    End,
+    OutputChar,
+    OutputNumber,
+    ReadChar,
+    ReadNumber,
+}
+
+impl EsolangInterpreter for Whitespace {
+    fn name(&self) -> &'static str {
+        "Whitespace"
+    }
+
+    fn run(
+        &self,
+        code: &str,
+        inputs: Vec<u32>,
+        budget: Budget,
+    ) -> Result<Vec<u8>, (String, Vec<u8>)> {
+        let program = parse(code).map_err(|e| (e, Vec::new()))?;
+        let mut output = Vec::new();
+        match execute(&program, inputs.into_iter().collect(), &mut output, budget) {
+            Ok(()) => Ok(output),
+            Err(e) => Err((e, output)),
+        }
+    }
+}
+
+/// Reads the significant characters of a program as 'S', 'T' and 'L'
+struct Tokens {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Tokens {
+    fn next(&mut self) -> Result<char, String> {
+        let c = self
+            .chars
+            .get(self.pos)
+            .copied()
+            .ok_or("Unexpected end of program")?;
+        self.pos += 1;
+        Ok(c)
+    }
+
+    /// A sign followed by binary digits, terminated by a line feed
+    fn number(&mut self) -> Result<i64, String> {
+        let negative = match self.next()? {
+            'S' => false,
+            'T' => true,
+            _ => return Err(format!("Number without a sign at {}", self.pos - 1)),
+        };
+        let mut value: i64 = 0;
+        loop {
+            match self.next()? {
+                'S' => value = value.wrapping_mul(2),
+                'T' => value = value.wrapping_mul(2).wrapping_add(1),
+                _ => break,
+            }
+        }
+        Ok(if negative {
+            value.wrapping_neg()
+        } else {
+            value
+        })
+    }
+
+    /// Any spaces and tabs, terminated by a line feed
+    fn label(&mut self) -> Result<String, String> {
+        let mut label = String::new();
+        loop {
+            match self.next()? {
+                'L' => return Ok(label),
+                c => label.push(c),
+            }
+        }
+    }
+}
+
+fn parse(code: &str) -> Result<Vec<Instr>, String> {
+    let chars = code
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('S'),
+            '\t' => Some('T'),
+            '\n' => Some('L'),
+            _ => None,
+        })
+        .collect();
+    let mut tokens = Tokens { chars, pos: 0 };
+    let mut program = Vec::new();
+    let mut labels: HashMap<String, usize> = HashMap::new();
+    // (instruction index, label) of flow control waiting for its target
+    let mut jumps: Vec<(usize, String)> = Vec::new();
+
+    while tokens.pos < tokens.chars.len() {
+        let start = tokens.pos;
+        let t = &mut tokens;
+        let instr = match (t.next()?, t.next()?) {
+            ('S', 'S') => Instr::Push(t.number()?),
+            ('S', 'L') => match t.next()? {
+                'S' => Instr::Dup,
+                'T' => Instr::Swap,
+                _ => Instr::Discard,
+            },
+            ('S', 'T') => match (t.next()?, t.next()?) {
+                ('S', _) => {
+                    t.pos -= 1;
+                    Instr::Copy(t.number()?)
+                }
+                ('L', _) => {
+                    t.pos -= 1;
+                    Instr::Slide(t.number()?)
+                }
+                _ => return Err(format!("Unknown stack instruction at {}", start)),
+            },
+            ('T', 'S') => match (t.next()?, t.next()?) {
+                ('S', 'S') => Instr::Add,
+                ('S', 'T') => Instr::Sub,
+                ('S', 'L') => Instr::Mul,
+                ('T', 'S') => Instr::Div,
+                ('T', 'T') => Instr::Mod,
+                _ => return Err(format!("Unknown arithmetic instruction at {}", start)),
+            },
+            ('T', 'T') => match t.next()? {
+                'S' => Instr::Store,
+                'T' => Instr::Retrieve,
+                _ => return Err(format!("Unknown heap instruction at {}", start)),
+            },
+            ('T', 'L') => match (t.next()?, t.next()?) {
+                ('S', 'S') => Instr::OutputChar,
+                ('S', 'T') => Instr::OutputNumber,
+                ('T', 'S') => Instr::ReadChar,
+                ('T', 'T') => Instr::ReadNumber,
+                _ => return Err(format!("Unknown I/O instruction at {}", start)),
+            },
+            ('L', 'L') if t.next()? == 'L' => Instr::End,
+            ('L', 'T') if t.chars.get(t.pos) == Some(&'L') => {
+                t.pos += 1;
+                Instr::Return
+            }
+            ('L', a) => {
+                let b = t.next()?;
+                let label = t.label()?;
+                if (a, b) == ('S', 'S') {
+                    if labels.insert(label, program.len()).is_some() {
+                        return Err(format!("Label defined twice at {}", start));
+                    }
+                    Instr::Mark
+                } else {
+                    jumps.push((program.len(), label));
+                    match (a, b) {
+                        ('S', 'T') => Instr::Call(0),
+                        ('S', 'L') => Instr::Jump(0),
+                        ('T', 'S') => Instr::JumpIfZero(0),
+                        ('T', 'T') => Instr::JumpIfNegative(0),
+                        _ => return Err(format!("Unknown flow instruction at {}", start)),
+                    }
+                }
+            }
+            _ => return Err(format!("Unknown instruction at {}", start)),
+        };
+        program.push(instr);
+    }
+
+    for (index, label) in jumps {
+        let target = *labels.get(&label).ok_or("Jump to an undefined label")?;
+        program[index] = match program[index] {
+            Instr::Call(_) => Instr::Call(target),
+            Instr::Jump(_) => Instr::Jump(target),
+            Instr::JumpIfZero(_) => Instr::JumpIfZero(target),
+            _ => Instr::JumpIfNegative(target),
+        };
+    }
+    Ok(program)
+}
+
+fn execute(
+    program: &[Instr],
+    mut input: VecDeque<u32>,
+    output: &mut Vec<u8>,
+    mut budget: Budget,
+) -> Result<(), String> {
+    let mut stack: Vec<i64> = Vec::new();
+    let mut heap: HashMap<i64, i64> = HashMap::new();
+    let mut calls: Vec<usize> = Vec::new();
+    let mut pc = 0;
+    let underflow = || "Stack underflow".to_string();
+
+    while pc < program.len() {
+        budget.charge(1)?;
+        let mut next = pc + 1;
+        match program[pc] {
+            Instr::Push(value) => stack.push(value),
+            Instr::Dup => stack.push(*stack.last().ok_or_else(underflow)?),
+            Instr::Copy(n) => {
+                let index = usize::try_from(n)
+                    .ok()
+                    .and_then(|n| stack.len().checked_sub(n + 1))
+                    .ok_or_else(underflow)?;
+                stack.push(stack[index]);
+            }
+            Instr::Swap => {
+                let len = stack.len();
+                if len < 2 {
+                    return Err(underflow());
+                }
+                stack.swap(len - 1, len - 2);
+            }
+            Instr::Discard => {
+                stack.pop().ok_or_else(underflow)?;
+            }
+            Instr::Slide(n) => {
+                let top = stack.pop().ok_or_else(underflow)?;
+                let keep = stack.len().saturating_sub(n.max(0) as usize);
+                stack.truncate(keep);
+                stack.push(top);
+            }
+            Instr::Add | Instr::Sub | Instr::Mul | Instr::Div | Instr::Mod => {
+                let b = stack.pop().ok_or_else(underflow)?;
+                let a = stack.pop().ok_or_else(underflow)?;
+                let result = match program[pc] {
+                    Instr::Add => a.wrapping_add(b),
+                    Instr::Sub => a.wrapping_sub(b),
+                    Instr::Mul => a.wrapping_mul(b),
+                    Instr::Div => floor_div(a, b)?,
+                    _ => floor_mod(a, b)?,
+                };
+                stack.push(result);
+            }
+            Instr::Store => {
+                let value = stack.pop().ok_or_else(underflow)?;
+                let address = stack.pop().ok_or_else(underflow)?;
+                heap.insert(address, value);
+            }
+            Instr::Retrieve => {
+                let address = stack.pop().ok_or_else(underflow)?;
+                stack.push(heap.get(&address).copied().unwrap_or(0));
+            }
+            Instr::Mark => {}
+            Instr::Call(target) => {
+                calls.push(next);
+                next = target;
+            }
+            Instr::Jump(target) => next = target,
+            Instr::JumpIfZero(target) => {
+                if stack.pop().ok_or_else(underflow)? == 0 {
+                    next = target;
+                }
+            }
+            Instr::JumpIfNegative(target) => {
+                if stack.pop().ok_or_else(underflow)? < 0 {
+                    next = target;
+                }
+            }
+            Instr::Return => next = calls.pop().ok_or("Return outside of a subroutine")?,
+            Instr::End => return Ok(()),
+            Instr::OutputChar => write_char(output, stack.pop().ok_or_else(underflow)?),
+            Instr::OutputNumber => {
+                let value = stack.pop().ok_or_else(underflow)?;
+                output.extend_from_slice(value.to_string().as_bytes());
+            }
+            Instr::ReadChar | Instr::ReadNumber => {
+                let address = stack.pop().ok_or_else(underflow)?;
+                let value = if program[pc] == Instr::ReadChar {
+                    input.pop_front().map(i64::from)
+                } else {
+                    read_number(&mut input)
+                };
+                heap.insert(address, value.ok_or("Not enough input values provided")?);
+            }
+        }
+        pc = next;
+    }
+    Ok(())
+}
+
+/// Quotient rounded towards negative infinity, like Haskell's `div` in the
+/// reference interpreter
+fn floor_div(a: i64, b: i64) -> Result<i64, String> {
+    if b == 0 {
+        return Err("Division by zero".to_string());
+    }
+    let quotient = a.checked_div(b).ok_or("Division overflow")?;
+    Ok(if a % b != 0 && (a < 0) != (b < 0) {
+        quotient - 1
+    } else {
+        quotient
+    })
+}
+
+/// Remainder with the sign of the divisor, like Haskell's `mod`
+fn floor_mod(a: i64, b: i64) -> Result<i64, String> {
+    if b == 0 {
+        return Err("Division by zero".to_string());
+    }
+    let remainder = a.wrapping_rem(b);
+    Ok(if remainder != 0 && (remainder < 0) != (b < 0) {
+        remainder + b
+    } else {
+        remainder
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Spell a program with S, T and L for space, tab and line feed
+    fn ws(code: &str) -> String {
+        code.chars()
+            .filter_map(|c| match c {
+                'S' => Some(' '),
+                'T' => Some('\t'),
+                'L' => Some('\n'),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_output_chars() {
+        // push 72, print char, push 105, print char, end
+        let code = ws("SS STSSTSSSL TLSS SS STTSTSSTL TLSS LLL");
+        assert_eq!(
+            Whitespace.run(&code, vec![], Budget::default()),
+            Ok(b"Hi".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_loop_with_labels() {
+        // 1; A: dup, print, +1, dup, -4, jz B, jump A; B: end
+        let code = ws("SS STL  LSS SL  SLS TLST  SS STL TSSS  SLS SS STSSL TSST \
+             LTS TL  LSL SL  LSS TL  LLL");
+        assert_eq!(
+            Whitespace.run(&code, vec![], Budget::default()),
+            Ok(b"123".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_subroutine_and_heap() {
+        // store 7 at 0, call S, end; S: retrieve 0, print number, return
+        let code = ws("SS SL SS STTTL TTS  LST SL  LLL  LSS SL  SS SL TTT TLST  LTL");
+        assert_eq!(
+            Whitespace.run(&code, vec![], Budget::default()),
+            Ok(b"7".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_read_input() {
+        // read a number into 0 and a char into 1, print both
+        let code = ws("SS SL TLTT  SS STL TLTS  SS SL TTT TLST  SS STL TTT TLSS");
+        let inputs: Vec<u32> = "-12x".chars().map(u32::from).collect();
+        assert_eq!(
+            Whitespace.run(&code, inputs, Budget::default()),
+            Ok(b"-12x".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_number_wraps() {
+        // Negative 2^63 wraps around to i64::MIN rather than overflowing
+        let code = ws(&format!("SS TT{}L TLST LLL", "S".repeat(63)));
+        assert_eq!(
+            Whitespace.run(&code, vec![], Budget::default()),
+            Ok(i64::MIN.to_string().into_bytes())
+        );
+    }
+
+    #[test]
+    fn test_division_floors() {
+        // 7 and -2, divided or reduced, then printed
+        let run = |op: &str| {
+            let code = ws(&format!("SS STTTL SS TTSL {} TLST LLL", op));
+            Whitespace.run(&code, vec![], Budget::default())
+        };
+        assert_eq!(run("TSTS"), Ok(b"-4".to_vec()));
+        assert_eq!(run("TSTT"), Ok(b"-1".to_vec()));
+        assert_eq!(floor_div(-7, 2), Ok(-4));
+        assert_eq!(floor_mod(-7, 2), Ok(1));
+        assert_eq!(floor_div(6, -2), Ok(-3));
+        assert_eq!(floor_mod(i64::MIN, -1), Ok(0));
+        assert_eq!(
+            floor_div(i64::MIN, -1),
+            Err("Division overflow".to_string())
+        );
+        assert_eq!(floor_div(1, 0), Err("Division by zero".to_string()));
+    }
+
+    #[test]
+    fn test_comments_are_ignored() {
+        let code: String = ws("SS STSSSSSTL TLSS")
+            .chars()
+            .flat_map(|c| ['#', c])
+            .collect();
+        assert_eq!(
+            Whitespace.run(&code, vec![], Budget::default()),
+            Ok(b"A".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            Whitespace.run(&ws("TSSS"), vec![], Budget::default()),
+            Err(("Stack underflow".to_string(), Vec::new()))
+        );
+        assert_eq!(
+            Whitespace.run(&ws("LSL SL"), vec![], Budget::default()),
+            Err(("Jump to an undefined label".to_string(), Vec::new()))
+        );
+        assert_eq!(
+            Whitespace.run(&ws("SS ST"), vec![], Budget::default()),
+            Err(("Unexpected end of program".to_string(), Vec::new()))
+        );
+        assert_eq!(
+            Whitespace.run(&ws("LSS SL LSL SL"), vec![], Budget::default()),
+            Err(("Instruction limit exceeded".to_string(), Vec::new()))
+        );
+    }
+}
@@ -1,65 +1,368 @@
+use crate::context::{Context, Error};
 use num_complex::Complex;
+use poise::serenity_prelude as serenity;
+use poise::ChoiceParameter;
+use spectrum::{Spectrum, Window};
 
-/// Calculate the Fast Fourier Transform of a list of real numbers
+mod audio;
+mod convolution;
+mod spectrum;
+mod transform;
+mod wav;
+
+const MESSAGE_LIMIT: usize = 2000;
+// Characters shown on each side of a number that could not be read
+const ERROR_CONTEXT: usize = 20;
+
+/// Fourier transforms and the convolutions computed with them
+#[poise::command(
+    slash_command,
+    subcommands(
+        "forward",
+        "inverse",
+        "convolve",
+        "correlate",
+        "multiply",
+        "audio::audio"
+    ),
+    subcommand_required
+)]
+pub async fn fft(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Calculate the Fast Fourier Transform of a list of numbers and chart its
+/// spectrum
 #[poise::command(slash_command)]
-pub async fn fft(
-    ctx: crate::context::Context<'_>,
-    #[description = "Real numbers (integers or floats)"] numbers: String,
-) -> Result<(), crate::context::Error> {
-    let numbers = parse_real_numbers(&numbers);
-    match numbers {
-        Ok(valid_numbers) => {
-            let output = fft_calculator(valid_numbers);
-            let output = output
-                .iter()
-                .map(|c| format!("{:.2}", c))
-                .collect::<Vec<_>>()
-                .join("\n");
-            ctx.say(output).await?;
+pub async fn forward(
+    ctx: Context<'_>,
+    #[description = "Numbers like 2, -1.5 or 3+4i, separated by commas"] numbers: String,
+    #[description = "Pad with zeros to the next power of two"] pad: Option<bool>,
+    #[description = "Taper the samples to reduce leakage between bins"] window: Option<Window>,
+    #[description = "Samples per second, to show frequencies in Hz"] sample_rate: Option<f64>,
+) -> Result<(), Error> {
+    let reply = parse_complex_numbers(&numbers).and_then(|mut numbers| {
+        if sample_rate.is_some_and(|rate| !(rate.is_finite() && rate > 0.0)) {
+            return Err("The sample rate must be a positive number".to_string());
         }
-        Err(_) => {
-            ctx.say("meh").await?;
+        let real = numbers.iter().all(|x| x.im == 0.0);
+        let window = window.unwrap_or_default();
+        window.apply(&mut numbers);
+        if pad.unwrap_or(false) {
+            numbers = transform::zero_pad(numbers);
         }
-    }
+        let transform = fft_calculator(numbers);
+        Ok(spectrum_reply(&transform, real, window, sample_rate))
+    });
+    send(ctx, reply).await
+}
+
+/// Calculate the inverse Fast Fourier Transform of a list of numbers
+#[poise::command(slash_command)]
+pub async fn inverse(
+    ctx: Context<'_>,
+    #[description = "Numbers like 2, -1.5 or 3+4i, separated by commas"] numbers: String,
+) -> Result<(), Error> {
+    let reply = parse_complex_numbers(&numbers)
+        .map(|numbers| values_reply("Inverse transform", &ifft_calculator(numbers)));
+    send(ctx, reply).await
+}
+
+/// Convolve two lists of numbers
+#[poise::command(slash_command)]
+pub async fn convolve(
+    ctx: Context<'_>,
+    #[description = "Numbers like 2, -1.5 or 3+4i, separated by commas"] a: String,
+    #[description = "Numbers like 2, -1.5 or 3+4i, separated by commas"] b: String,
+    #[description = "Wrap around, padding the shorter list to the longer one"] circular: Option<
+        bool,
+    >,
+) -> Result<(), Error> {
+    let reply = parse_pair(&a, &b).map(|(a, b)| {
+        if circular.unwrap_or(false) {
+            values_reply("Circular convolution", &convolution::circular(&a, &b))
+        } else {
+            values_reply("Convolution", &convolution::linear(&a, &b))
+        }
+    });
+    send(ctx, reply).await
+}
+
+/// Cross-correlate two lists of numbers at every lag where they overlap
+#[poise::command(slash_command)]
+pub async fn correlate(
+    ctx: Context<'_>,
+    #[description = "Numbers like 2, -1.5 or 3+4i, separated by commas"] a: String,
+    #[description = "Numbers like 2, -1.5 or 3+4i, separated by commas"] b: String,
+) -> Result<(), Error> {
+    let reply = parse_pair(&a, &b).map(|(a, b)| correlation_reply(&a, &b));
+    send(ctx, reply).await
+}
+
+/// Multiply two polynomials
+#[poise::command(slash_command)]
+pub async fn multiply(
+    ctx: Context<'_>,
+    #[description = "Coefficients, highest degree first, like 1, -2, 3+4i"] a: String,
+    #[description = "Coefficients, highest degree first, like 1, -2, 3+4i"] b: String,
+) -> Result<(), Error> {
+    let reply = parse_pair(&a, &b).map(|(a, b)| {
+        let product = convolution::linear(&a, &b);
+        let polynomial = format_polynomial(&product);
+        // A long product is only shown through its coefficients
+        let header = if polynomial.chars().count() <= MESSAGE_LIMIT / 2 {
+            format!("Product: {}\nCoefficients", polynomial)
+        } else {
+            "Coefficients of the product".to_string()
+        };
+        values_reply(&header, &product)
+    });
+    send(ctx, reply).await
+}
+
+async fn send(ctx: Context<'_>, reply: Result<poise::CreateReply, String>) -> Result<(), Error> {
+    let reply = reply.unwrap_or_else(|e| poise::CreateReply::default().content(e));
+    ctx.send(reply).await?;
     Ok(())
 }
 
-fn parse_real_numbers(input: &str) -> Result<Vec<Complex<f64>>, String> {
+type Numbers = Vec<Complex<f64>>;
+
+fn parse_pair(a: &str, b: &str) -> Result<(Numbers, Numbers), String> {
+    let a = parse_complex_numbers(a).map_err(|e| format!("In a: {}", e))?;
+    let b = parse_complex_numbers(b).map_err(|e| format!("In b: {}", e))?;
+    Ok((a, b))
+}
+
+/// Parse a number like `2`, `-1.5e3`, `4i`, `-i` or `3+4i`
+fn parse_complex(input: &str) -> Option<Complex<f64>> {
+    let text: String = input.chars().filter(|c| !c.is_whitespace()).collect();
+    let number = match text.strip_suffix('i') {
+        Some(body) => {
+            // The imaginary part starts at the last sign that is not an exponent's
+            let split = body
+                .char_indices()
+                .skip(1)
+                .filter(|&(k, c)| (c == '+' || c == '-') && !body[..k].ends_with(['e', 'E']))
+                .last()
+                .map_or(0, |(k, _)| k);
+            let (re, im) = body.split_at(split);
+            let re = if re.is_empty() { 0.0 } else { re.parse().ok()? };
+            let im = match im {
+                "" | "+" => 1.0,
+                "-" => -1.0,
+                _ => im.parse().ok()?,
+            };
+            Complex::new(re, im)
+        }
+        None => Complex::new(text.parse().ok()?, 0.0),
+    };
+    number.is_finite().then_some(number)
+}
+
+fn parse_complex_numbers(input: &str) -> Result<Vec<Complex<f64>>, String> {
+    let mut offset = 0;
     input
         .split(',')
-        .map(|s| {
-            s.trim()
-                .parse::<f64>()
-                .map(|re| Complex::new(re, 0.0))
-                .map_err(|_| "Failed to parse number".to_string())
+        .enumerate()
+        .map(|(k, token)| {
+            let start = offset;
+            offset += token.len() + 1;
+            parse_complex(token).ok_or_else(|| parse_error(input, start, token, k + 1))
         })
         .collect()
 }
 
-pub fn fft_calculator(input: Vec<Complex<f64>>) -> Vec<Complex<f64>> {
-    let n = input.len();
-    if n == 1 {
-        return input;
-    }
-    let mut even = Vec::new();
-    let mut odd = Vec::new();
-    for i in 0..n {
-        if i % 2 == 0 {
-            even.push(input[i]);
+/// Error for the `position`th number, `token`, which starts at byte `start`
+/// of `input`, shown in its context with carets under it
+fn parse_error(input: &str, start: usize, token: &str, position: usize) -> String {
+    let trimmed = token.trim();
+    let begin = start + token.len() - token.trim_start().len();
+    let end = begin + trimmed.len();
+    let message = if trimmed.is_empty() {
+        format!("Number {} is missing", position)
+    } else {
+        format!("Could not read number {}, \"{}\"", position, trimmed)
+    };
+
+    let before: Vec<char> = input[..begin].chars().collect();
+    let before: String = if before.len() > ERROR_CONTEXT {
+        let kept: String = before[before.len() - ERROR_CONTEXT..].iter().collect();
+        format!("…{}", kept)
+    } else {
+        before.into_iter().collect()
+    };
+    let shorten = |text: &str| {
+        let kept: String = text.chars().take(ERROR_CONTEXT).collect();
+        if kept.len() < text.len() {
+            format!("{}…", kept)
         } else {
-            odd.push(input[i]);
+            kept
         }
+    };
+    let shown = shorten(trimmed);
+    // Anything that would break the line or the code block is blanked out
+    let line = format!("{}{}{}", before, shown, shorten(&input[end..]))
+        .replace(['\n', '\r', '\t'], " ")
+        .replace('`', "'");
+    let carets = format!(
+        "{}{}",
+        " ".repeat(before.chars().count()),
+        "^".repeat(shown.chars().count().max(1))
+    );
+    format!("{}:\n```\n{}\n{}\n```", message, line, carets)
+}
+
+/// Up to four decimals, without trailing zeros or a negative zero
+fn format_real(x: f64) -> String {
+    let rounded = format!("{:.4}", x);
+    let trimmed = rounded.trim_end_matches('0').trim_end_matches('.');
+    match trimmed {
+        "-0" => "0".to_string(),
+        _ => trimmed.to_string(),
     }
-    let even = fft_calculator(even);
-    let odd = fft_calculator(odd);
-    let mut output = vec![Complex::new(0.0, 0.0); n];
-    for k in 0..n / 2 {
-        let t = even[k];
-        let u = odd[k] * Complex::new(0.0, -2.0 * std::f64::consts::PI * k as f64 / n as f64).exp();
-        output[k] = t + u;
-        output[k + n / 2] = t - u;
+}
+
+/// A number in the syntax the commands read, like `3`, `-i` or `3+4i`
+fn format_complex(c: &Complex<f64>) -> String {
+    let (re, im) = (format_real(c.re), format_real(c.im));
+    let im = match im.as_str() {
+        "0" => return re,
+        "1" => String::new(),
+        "-1" => "-".to_string(),
+        _ => im,
+    };
+    match (re.as_str(), im.starts_with('-')) {
+        ("0", _) => format!("{}i", im),
+        (_, true) => format!("{}{}i", re, im),
+        (_, false) => format!("{}+{}i", re, im),
     }
-    output
+}
+
+/// A polynomial from its coefficients, highest degree first, like `x^2 - 3x + 2`
+fn format_polynomial(coefficients: &[Complex<f64>]) -> String {
+    let terms: Vec<String> = coefficients
+        .iter()
+        .rev()
+        .enumerate()
+        .rev()
+        .filter_map(|(degree, c)| {
+            let c = format_complex(c);
+            let power = match degree {
+                0 => return (c != "0").then_some(c),
+                1 => "x".to_string(),
+                _ => format!("x^{}", degree),
+            };
+            match c.as_str() {
+                "0" => None,
+                "1" => Some(power),
+                "-1" => Some(format!("-{}", power)),
+                _ if c[1..].contains(['+', '-']) => Some(format!("({}){}", c, power)),
+                _ => Some(format!("{}{}", c, power)),
+            }
+        })
+        .collect();
+    let Some((first, rest)) = terms.split_first() else {
+        return "0".to_string();
+    };
+    rest.iter().fold(first.clone(), |polynomial, term| {
+        match term.strip_prefix('-') {
+            Some(term) => format!("{} - {}", polynomial, term),
+            None => format!("{} + {}", polynomial, term),
+        }
+    })
+}
+
+/// `values` under `header`, separated by commas so they can be passed on to
+/// another command. Values that do not fit in a message are attached.
+fn values_reply(header: &str, values: &[Complex<f64>]) -> poise::CreateReply {
+    let values = values
+        .iter()
+        .map(format_complex)
+        .collect::<Vec<_>>()
+        .join(", ");
+    let message = format!("{}:\n{}", header, values);
+    if message.chars().count() <= MESSAGE_LIMIT {
+        return poise::CreateReply::default().content(message);
+    }
+    poise::CreateReply::default()
+        .content(format!("{} is attached.", header))
+        .attachment(serenity::CreateAttachment::bytes(values, "fft.txt"))
+}
+
+/// The values of `transform` with a chart of its spectrum
+fn spectrum_reply(
+    transform: &[Complex<f64>],
+    real: bool,
+    window: Window,
+    sample_rate: Option<f64>,
+) -> poise::CreateReply {
+    let spectrum = Spectrum::new(transform, real, sample_rate);
+    let mut embed = serenity::CreateEmbed::new()
+        .title("Spectrum")
+        .description("Magnitude above, phase in radians below")
+        .field("Window", window.name(), true);
+    if let Some(peak) = spectrum.peak() {
+        embed = embed.field(
+            "Peak",
+            format!(
+                "{}, magnitude {}",
+                spectrum.position(peak),
+                format_real(spectrum.magnitudes[peak])
+            ),
+            true,
+        );
+    }
+    if let Some(resolution) = spectrum.resolution {
+        embed = embed.field(
+            "Resolution",
+            format!("{} Hz", format_real(resolution)),
+            true,
+        );
+    }
+    let reply = values_reply("Transform", transform);
+    match spectrum.chart() {
+        Ok(png) => reply
+            .attachment(serenity::CreateAttachment::bytes(png, "spectrum.png"))
+            .embed(embed.attachment("spectrum.png")),
+        Err(e) => reply.embed(embed.field("Chart", e, false)),
+    }
+}
+
+/// Correlation of `a` and `b`, with the lag at which they match best
+fn correlation_reply(a: &[Complex<f64>], b: &[Complex<f64>]) -> poise::CreateReply {
+    let correlation = convolution::correlate(a, b);
+    let first_lag = 1 - b.len() as i64;
+    let best = correlation
+        .iter()
+        .enumerate()
+        .max_by(|(_, x), (_, y)| x.norm().total_cmp(&y.norm()))
+        .map_or(0, |(i, _)| i as i64 + first_lag);
+    values_reply(
+        &format!(
+            "Cross-correlation at lags {} to {}, strongest at lag {}",
+            first_lag,
+            a.len() - 1,
+            best
+        ),
+        &correlation,
+    )
+}
+
+/// Transform of `input`, which may have any length
+pub fn fft_calculator(mut input: Vec<Complex<f64>>) -> Vec<Complex<f64>> {
+    transform::fft(&mut input);
+    input
+}
+
+/// Inverse transform of `input`, as the conjugate of the transform of its
+/// conjugate
+pub fn ifft_calculator(input: Vec<Complex<f64>>) -> Vec<Complex<f64>> {
+    let scale = 1.0 / input.len() as f64;
+    fft_calculator(input.into_iter().map(|x| x.conj()).collect())
+        .into_iter()
+        .map(|x| x.conj() * scale)
+        .collect()
 }
 
 // tests
@@ -76,7 +379,7 @@ mod tests {
             Complex::new(9.0, 0.0),
         ];
         let output = fft_calculator(input);
-        let expected = vec![
+        let expected = [
             Complex::new(26.0, 0.0),
             Complex::new(-13.0, 7.0),
             Complex::new(4.0, 0.0),
@@ -85,12 +388,29 @@ mod tests {
         for (a, b) in output.iter().zip(expected.iter()) {
             assert!((a - b).norm() < 1e-6);
         }
+
+        assert_eq!(fft_calculator(vec![]), vec![]);
+        let output = fft_calculator(vec![Complex::new(1.0, 0.0); 3]);
+        assert!((output[0] - Complex::new(3.0, 0.0)).norm() < 1e-9);
+        assert!(output[1].norm() < 1e-9 && output[2].norm() < 1e-9);
+    }
+
+    #[test]
+    fn test_inverse() {
+        let input: Vec<Complex<f64>> = (0..7)
+            .map(|t| Complex::new(t as f64 * 1.5 - 2.0, (t * t) as f64))
+            .collect();
+        let output = ifft_calculator(fft_calculator(input.clone()));
+        for (a, b) in output.iter().zip(&input) {
+            assert!((a - b).norm() < 1e-9);
+        }
+        assert!(ifft_calculator(vec![]).is_empty());
     }
 
     #[test]
     fn test_parser() {
         let input = "1, -2, 3.2, 4";
-        let output = parse_real_numbers(input);
+        let output = parse_complex_numbers(input);
         let expected = vec![
             Complex::new(1.0, 0.0),
             Complex::new(-2.0, 0.0),
@@ -99,4 +419,101 @@ mod tests {
         ];
         assert_eq!(output, Ok(expected));
     }
+
+    #[test]
+    fn test_parse_complex() {
+        let parsed = |s| parse_complex(s).map(|c| (c.re, c.im));
+        assert_eq!(parsed("3+4i"), Some((3.0, 4.0)));
+        assert_eq!(parsed(" 3 - 4i "), Some((3.0, -4.0)));
+        assert_eq!(parsed("-2.5i"), Some((0.0, -2.5)));
+        assert_eq!(parsed("i"), Some((0.0, 1.0)));
+        assert_eq!(parsed("-i"), Some((0.0, -1.0)));
+        assert_eq!(parsed("1-i"), Some((1.0, -1.0)));
+        assert_eq!(parsed("1e3-2e-3i"), Some((1000.0, -0.002)));
+        assert_eq!(parsed("-1E+2"), Some((-100.0, 0.0)));
+        assert_eq!(parsed("4i+3"), None);
+        assert_eq!(parsed("3+4j"), None);
+        assert_eq!(parsed("inf"), None);
+        assert_eq!(parsed("NaN+i"), None);
+        assert_eq!(parsed(""), None);
+        assert_eq!(
+            parse_complex_numbers("1, 2x, 3"),
+            Err("Could not read number 2, \"2x\":\n```\n1, 2x, 3\n   ^^\n```".to_string())
+        );
+        assert_eq!(
+            parse_complex_numbers("1,,3"),
+            Err("Number 2 is missing:\n```\n1,,3\n  ^\n```".to_string())
+        );
+        let long = format!("{}, oops{}", "1, ".repeat(20) + "1", ", 2".repeat(20));
+        let error = parse_complex_numbers(&long).unwrap_err();
+        assert!(error.starts_with("Could not read number 22, \"oops\""));
+        assert!(error.contains("\n…, 1, 1, 1, 1, 1, 1, oops, 2, 2, 2, 2, 2, 2, …\n"));
+        assert!(error.ends_with("\n                     ^^^^\n```"));
+        assert!(parse_pair("1", "1, `")
+            .unwrap_err()
+            .starts_with("In b: Could not read number 2, \"`\""));
+    }
+
+    #[test]
+    fn test_format() {
+        let formatted = |re, im| format_complex(&Complex::new(re, im));
+        assert_eq!(formatted(26.0, 0.0), "26");
+        assert_eq!(formatted(-13.000000000000002, 6.999999999999998), "-13+7i");
+        assert_eq!(formatted(0.5, -1.0), "0.5-i");
+        assert_eq!(formatted(-1e-12, 2.25), "2.25i");
+        assert_eq!(formatted(0.0, -0.00001), "0");
+        // Output can be read back
+        for (re, im) in [(1.25, -3.0), (0.0, 1.0), (-7.0, 0.0), (2.0, 0.5)] {
+            assert_eq!(
+                parse_complex(&formatted(re, im)),
+                Some(Complex::new(re, im))
+            );
+        }
+
+        let polynomial = |c: &[(f64, f64)]| {
+            let c: Vec<Complex<f64>> = c.iter().map(|&(re, im)| Complex::new(re, im)).collect();
+            format_polynomial(&c)
+        };
+        assert_eq!(
+            polynomial(&[(1.0, 0.0), (-3.0, 0.0), (2.0, 0.0)]),
+            "x^2 - 3x + 2"
+        );
+        assert_eq!(
+            polynomial(&[(-1.0, 0.0), (0.0, 0.0), (1.0, 2.0), (0.0, -1.0)]),
+            "-x^3 + (1+2i)x - i"
+        );
+        assert_eq!(polynomial(&[(0.0, 0.0), (0.0, 0.0)]), "0");
+    }
+
+    #[test]
+    fn test_replies() {
+        let reals = |values: &[f64]| -> Vec<Complex<f64>> {
+            values.iter().map(|&re| Complex::new(re, 0.0)).collect()
+        };
+        let reply = values_reply("Transform", &fft_calculator(reals(&[1.0, 2.0, 14.0, 9.0])));
+        assert_eq!(
+            reply.content.as_deref(),
+            Some("Transform:\n26, -13+7i, 4, -13-7i")
+        );
+
+        let reply = correlation_reply(&reals(&[1.0, 2.0, 3.0]), &reals(&[0.0, 1.0]));
+        assert_eq!(
+            reply.content.as_deref(),
+            Some("Cross-correlation at lags -1 to 2, strongest at lag 1:\n1, 2, 3, 0")
+        );
+
+        let reply = spectrum_reply(
+            &fft_calculator(reals(&[0.0, 1.0, 0.0, -1.0])),
+            true,
+            Window::Rectangular,
+            Some(8.0),
+        );
+        assert_eq!(reply.content.as_deref(), Some("Transform:\n0, -2i, 0, 2i"));
+        assert_eq!(reply.attachments[0].filename, "spectrum.png");
+        assert_eq!(reply.embeds.len(), 1);
+
+        let reply = values_reply("Transform", &reals(&[1234.5; 500]));
+        assert_eq!(reply.content.as_deref(), Some("Transform is attached."));
+        assert_eq!(reply.attachments[0].filename, "fft.txt");
+    }
 }
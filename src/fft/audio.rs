@@ -0,0 +1,377 @@
+use super::spectrum::Window;
+use super::{fft_calculator, format_real, wav};
+use crate::context::{Context, Error};
+use crate::render;
+use crate::sandbox::CancelToken;
+use num_complex::Complex;
+use poise::serenity_prelude as serenity;
+
+// Larger attachments are refused before downloading them
+const MAX_FILE_SIZE: u32 = 25 * 1024 * 1024;
+// Longer clips are cut short, so the analysis ends well within the sandbox timeout
+const MAX_SECONDS: f64 = 60.0;
+// Samples per transform, and how far apart transforms start
+const FRAME_SIZE: usize = 4096;
+const HOP_SIZE: usize = FRAME_SIZE / 4;
+// Frequencies below this are rumble rather than notes
+const MIN_FREQUENCY: f64 = 20.0;
+const MAX_PEAKS: usize = 5;
+// Peaks this much quieter than the loudest one are left out
+const PEAK_RANGE_DB: f64 = 40.0;
+// The spectrogram shows frequencies up to this, or half the sample rate
+const MAX_SPECTROGRAM_FREQUENCY: f64 = 8000.0;
+const SPECTROGRAM_COLUMNS: usize = 400;
+// Loudness range of the spectrogram, quieter cells are black
+const SPECTROGRAM_RANGE_DB: f64 = 80.0;
+
+const NOTE_NAMES: [&str; 12] = [
+    "C", "C♯", "D", "D♯", "E", "F", "F♯", "G", "G♯", "A", "A♯", "B",
+];
+
+/// A frequency standing out of the average spectrum
+#[derive(Debug, Clone, PartialEq)]
+struct Peak {
+    frequency: f64,
+    /// Relative to the loudest peak
+    decibels: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Analysis {
+    /// Loudest first
+    peaks: Vec<Peak>,
+    /// Seconds analyzed
+    duration: f64,
+    truncated: bool,
+    /// Highest frequency of the spectrogram
+    top_frequency: f64,
+    /// Loudness of time slices from the lowest frequency up, between 0 and 1
+    spectrogram: Vec<Vec<f64>>,
+}
+
+/// Nearest note of the equal temperament tuned to A4 = 440 Hz, and how many
+/// cents `frequency` is above it
+fn nearest_note(frequency: f64) -> (String, f64) {
+    let semitones = 69.0 + 12.0 * (frequency / 440.0).log2();
+    let nearest = semitones.round();
+    let midi = nearest as i64;
+    let name = NOTE_NAMES[midi.rem_euclid(12) as usize];
+    let octave = midi.div_euclid(12) - 1;
+    (format!("{}{}", name, octave), (semitones - nearest) * 100.0)
+}
+
+/// Start of every frame of a clip of `length` samples; a clip shorter than a
+/// frame is one padded frame
+fn frame_starts(length: usize) -> impl Iterator<Item = usize> {
+    (0..=length.saturating_sub(FRAME_SIZE)).step_by(HOP_SIZE)
+}
+
+/// Power of the bins up to half the sample rate of every windowed frame,
+/// averaged over the clip and pooled into the loudest of each spectrogram
+/// column
+fn power_spectra(
+    samples: &[f64],
+    columns: usize,
+    cancel: &CancelToken,
+) -> Result<(Vec<f64>, Vec<Vec<f64>>), String> {
+    let bins = FRAME_SIZE / 2 + 1;
+    let frames = frame_starts(samples.len()).count();
+    let mut average = vec![0.0; bins];
+    let mut pooled = vec![vec![0.0; bins]; columns.min(frames)];
+    let pooled_columns = pooled.len();
+    for (i, start) in frame_starts(samples.len()).enumerate() {
+        if cancel.is_cancelled() {
+            return Err("Ran out of time, try a shorter clip".to_string());
+        }
+        let mut frame = vec![Complex::new(0.0, 0.0); FRAME_SIZE];
+        for (x, &s) in frame.iter_mut().zip(&samples[start..]) {
+            *x = Complex::new(s, 0.0);
+        }
+        Window::Hann.apply(&mut frame);
+        let transform = fft_calculator(frame);
+        let column = &mut pooled[i * pooled_columns / frames];
+        for (bin, x) in transform[..bins].iter().enumerate() {
+            let power = x.norm_sqr();
+            average[bin] += power / frames as f64;
+            column[bin] = f64::max(column[bin], power);
+        }
+    }
+    Ok((average, pooled))
+}
+
+/// Loudest local maxima of `power`, with their frequencies refined between
+/// bins by fitting a parabola to the log power around them
+fn find_peaks(power: &[f64], sample_rate: f64) -> Vec<Peak> {
+    let resolution = sample_rate / FRAME_SIZE as f64;
+    let mut peaks: Vec<(f64, f64)> = (1..power.len().saturating_sub(1))
+        .filter(|&k| power[k] > power[k - 1] && power[k] >= power[k + 1])
+        .filter(|&k| k as f64 * resolution >= MIN_FREQUENCY)
+        .map(|k| {
+            let (a, b, c) = (power[k - 1].ln(), power[k].ln(), power[k + 1].ln());
+            let curvature = a - 2.0 * b + c;
+            // A silent neighbour has no logarithm to fit, so stay on the bin
+            let fits = [a, c].iter().all(|x| x.is_finite());
+            let offset = if fits && curvature < 0.0 {
+                0.5 * (a - c) / curvature
+            } else {
+                0.0
+            };
+            ((k as f64 + offset) * resolution, power[k])
+        })
+        .collect();
+    peaks.sort_by(|a, b| b.1.total_cmp(&a.1));
+    let Some(&(_, loudest)) = peaks.first() else {
+        return Vec::new();
+    };
+    peaks
+        .into_iter()
+        .take(MAX_PEAKS)
+        .map(|(frequency, power)| Peak {
+            frequency,
+            decibels: 10.0 * (power / loudest).log10(),
+        })
+        .filter(|peak| peak.decibels >= -PEAK_RANGE_DB)
+        .collect()
+}
+
+fn analyze(bytes: &[u8], cancel: &CancelToken) -> Result<Analysis, String> {
+    let audio = wav::decode(bytes, MAX_SECONDS)?;
+    let sample_rate = f64::from(audio.sample_rate);
+    if audio.samples.iter().all(|&s| s == 0.0) {
+        return Err("The clip is silent".to_string());
+    }
+    let (average, pooled) = power_spectra(&audio.samples, SPECTROGRAM_COLUMNS, cancel)?;
+    let peaks = find_peaks(&average, sample_rate);
+    if peaks.is_empty() {
+        return Err("No frequency stands out of the clip".to_string());
+    }
+
+    let top_frequency = MAX_SPECTROGRAM_FREQUENCY.min(sample_rate / 2.0);
+    let rows = (top_frequency / sample_rate * FRAME_SIZE as f64) as usize + 1;
+    let loudest = pooled.iter().flatten().copied().fold(0.0, f64::max);
+    let spectrogram = pooled
+        .iter()
+        .map(|column| {
+            column[..rows]
+                .iter()
+                .map(|&power| {
+                    let decibels = 10.0 * (power / loudest).log10();
+                    1.0 + decibels.max(-SPECTROGRAM_RANGE_DB) / SPECTROGRAM_RANGE_DB
+                })
+                .collect()
+        })
+        .collect();
+    Ok(Analysis {
+        peaks,
+        duration: audio.samples.len() as f64 / sample_rate,
+        truncated: audio.truncated,
+        top_frequency,
+        spectrogram,
+    })
+}
+
+fn analysis_reply(analysis: &Analysis, filename: &str) -> poise::CreateReply {
+    let describe = |peak: &Peak| {
+        let (note, cents) = nearest_note(peak.frequency);
+        format!(
+            "{} Hz, {} {:+.0} cents",
+            format_real((peak.frequency * 10.0).round() / 10.0),
+            note,
+            cents
+        )
+    };
+    let (note, _) = nearest_note(analysis.peaks[0].frequency);
+    let frequencies = analysis
+        .peaks
+        .iter()
+        .map(|peak| format!("{} ({:.0} dB)", describe(peak), peak.decibels))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let mut duration = format!(
+        "{} s",
+        format_real((analysis.duration * 100.0).round() / 100.0)
+    );
+    if analysis.truncated {
+        duration.push_str(", the rest was left out");
+    }
+    let mut embed = serenity::CreateEmbed::new()
+        .title(format!("{} sounds like {}", filename, note))
+        .field("Dominant frequencies", frequencies, false)
+        .field("Analyzed", duration, true);
+    let image = "spectrogram.png";
+    let chart = render::heat_map(
+        &analysis.spectrogram,
+        &format!("{} Hz", format_real(analysis.top_frequency.round())),
+        &format!(
+            "{} s",
+            format_real((analysis.duration * 10.0).round() / 10.0)
+        ),
+    );
+    let mut reply = poise::CreateReply::default();
+    match chart {
+        Ok(png) => {
+            reply = reply.attachment(serenity::CreateAttachment::bytes(png, image));
+            embed = embed.attachment(image);
+        }
+        Err(e) => embed = embed.field("Spectrogram", e, false),
+    }
+    reply.embed(embed)
+}
+
+/// Find the dominant frequencies and notes of a WAV file
+#[poise::command(slash_command)]
+pub async fn audio(
+    ctx: Context<'_>,
+    #[description = "WAV file to analyze"] file: serenity::Attachment,
+) -> Result<(), Error> {
+    if file.size > MAX_FILE_SIZE {
+        ctx.say(format!(
+            "Files can be at most {} MB",
+            MAX_FILE_SIZE / 1024 / 1024
+        ))
+        .await?;
+        return Ok(());
+    }
+    ctx.defer().await?;
+    let bytes = file.download().await?;
+    let job = move |cancel: CancelToken| analyze(&bytes, &cancel);
+    let reply = match ctx.data().sandbox.run(ctx.author().id, job).await {
+        Ok(Ok(analysis)) => analysis_reply(&analysis, &file.filename),
+        Ok(Err(e)) => poise::CreateReply::default().content(e),
+        Err(e) => poise::CreateReply::default().content(e.to_string()),
+    };
+    ctx.send(reply).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    fn tones(sample_rate: u32, seconds: f64, tones: &[(f64, f64)]) -> Vec<f64> {
+        let count = (seconds * f64::from(sample_rate)) as usize;
+        (0..count)
+            .map(|t| {
+                let time = t as f64 / f64::from(sample_rate);
+                tones
+                    .iter()
+                    .map(|&(frequency, amplitude)| amplitude * (2.0 * PI * frequency * time).sin())
+                    .sum()
+            })
+            .collect()
+    }
+
+    fn analyzed(sample_rate: u32, samples: Vec<f64>) -> Result<Analysis, String> {
+        analyze(
+            &wav::encode(sample_rate, &[samples]),
+            &CancelToken::default(),
+        )
+    }
+
+    #[test]
+    fn test_nearest_note() {
+        let (note, cents) = nearest_note(440.0);
+        assert_eq!(note, "A4");
+        assert!(cents.abs() < 1e-9);
+        let (note, cents) = nearest_note(261.63);
+        assert_eq!(note, "C4");
+        assert!(cents.abs() < 1.0);
+        // A quarter tone above A4 rounds up to A♯4, fifty cents flat
+        let (note, cents) = nearest_note(440.0 * 2f64.powf(0.51 / 12.0));
+        assert_eq!(note, "A♯4");
+        assert!((cents + 49.0).abs() < 1e-6);
+        assert_eq!(nearest_note(27.5).0, "A0");
+        assert_eq!(nearest_note(16.35).0, "C0");
+        assert_eq!(nearest_note(8.0).0, "C-1");
+        assert_eq!(nearest_note(7.72).0, "B-2");
+    }
+
+    #[test]
+    fn test_single_tone() {
+        for (sample_rate, frequency) in [(44100, 440.0), (8000, 196.0), (48000, 1318.51)] {
+            let analysis =
+                analyzed(sample_rate, tones(sample_rate, 1.0, &[(frequency, 0.8)])).unwrap();
+            let peak = &analysis.peaks[0];
+            assert!(
+                (peak.frequency - frequency).abs() < 1.0,
+                "{} Hz found as {} Hz",
+                frequency,
+                peak.frequency
+            );
+            assert_eq!(peak.decibels, 0.0);
+            assert!((analysis.duration - 1.0).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_peak_next_to_silence() {
+        let mut power = vec![0.0; 64];
+        power[20] = 1.0;
+        power[30] = 2.0;
+        power[31] = 1.0;
+        let peaks = find_peaks(&power, 8000.0);
+        let resolution = 8000.0 / FRAME_SIZE as f64;
+        assert_eq!(peaks[0].frequency, 30.0 * resolution);
+        assert_eq!(peaks[1].frequency, 20.0 * resolution);
+    }
+
+    #[test]
+    fn test_chord() {
+        // A major: A4, C♯5 and E5, the A loudest
+        let samples = tones(22050, 2.0, &[(440.0, 0.5), (554.37, 0.3), (659.26, 0.2)]);
+        let analysis = analyzed(22050, samples).unwrap();
+        let notes: Vec<String> = analysis
+            .peaks
+            .iter()
+            .take(3)
+            .map(|peak| nearest_note(peak.frequency).0)
+            .collect();
+        assert_eq!(notes, ["A4", "C♯5", "E5"]);
+        assert!(analysis.peaks[1].decibels < 0.0);
+
+        let reply = analysis_reply(&analysis, "chord.wav");
+        assert_eq!(reply.attachments.len(), 1);
+        assert_eq!(reply.attachments[0].filename, "spectrogram.png");
+        assert_eq!(reply.embeds.len(), 1);
+    }
+
+    #[test]
+    fn test_spectrogram() {
+        // A short clip is one padded frame
+        let analysis = analyzed(8000, tones(8000, 0.1, &[(1000.0, 0.5)])).unwrap();
+        assert_eq!(analysis.spectrogram.len(), 1);
+        assert_eq!(analysis.top_frequency, 4000.0);
+        assert_eq!(analysis.spectrogram[0].len(), FRAME_SIZE / 2 + 1);
+
+        let analysis = analyzed(44100, tones(44100, 3.0, &[(440.0, 0.5)])).unwrap();
+        assert_eq!(analysis.top_frequency, MAX_SPECTROGRAM_FREQUENCY);
+        assert_eq!(analysis.spectrogram.len(), frame_starts(3 * 44100).count());
+        let column = &analysis.spectrogram[10];
+        let loudest = (0..column.len())
+            .max_by(|&a, &b| column[a].total_cmp(&column[b]))
+            .unwrap();
+        assert_eq!(
+            loudest,
+            (440.0 / 44100.0 * FRAME_SIZE as f64).round() as usize
+        );
+        assert!(column.iter().all(|&v| (0.0..=1.0).contains(&v)));
+    }
+
+    #[test]
+    fn test_unusable_clips() {
+        assert_eq!(
+            analyzed(8000, vec![0.0; 9000]),
+            Err("The clip is silent".to_string())
+        );
+        assert!(analyze(b"not a wav file", &CancelToken::default()).is_err());
+
+        let cancel = CancelToken::default();
+        cancel.cancel();
+        let bytes = wav::encode(8000, &[tones(8000, 1.0, &[(440.0, 0.5)])]);
+        assert_eq!(
+            analyze(&bytes, &cancel),
+            Err("Ran out of time, try a shorter clip".to_string())
+        );
+    }
+}
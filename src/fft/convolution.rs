@@ -0,0 +1,123 @@
+use super::{fft_calculator, ifft_calculator};
+use num_complex::Complex;
+
+/// Elementwise product of the transforms of `a` and `b`, each padded with
+/// zeros to `size`, transformed back
+fn via_transforms(a: &[Complex<f64>], b: &[Complex<f64>], size: usize) -> Vec<Complex<f64>> {
+    let padded = |x: &[Complex<f64>]| {
+        let mut x = x.to_vec();
+        x.resize(size, Complex::new(0.0, 0.0));
+        fft_calculator(x)
+    };
+    let product = padded(a)
+        .into_iter()
+        .zip(padded(b))
+        .map(|(x, y)| x * y)
+        .collect();
+    ifft_calculator(product)
+}
+
+/// Linear convolution of `a` and `b`, which has `a.len() + b.len() - 1`
+/// values. Polynomial multiplication, with coefficients in either order.
+pub fn linear(a: &[Complex<f64>], b: &[Complex<f64>]) -> Vec<Complex<f64>> {
+    if a.is_empty() || b.is_empty() {
+        return Vec::new();
+    }
+    let length = a.len() + b.len() - 1;
+    // Power-of-two transforms are the fastest, the extra values are zero
+    let mut output = via_transforms(a, b, length.next_power_of_two());
+    output.truncate(length);
+    output
+}
+
+/// Circular convolution of `a` and `b`, the shorter padded with zeros to the
+/// length of the longer
+pub fn circular(a: &[Complex<f64>], b: &[Complex<f64>]) -> Vec<Complex<f64>> {
+    if a.is_empty() || b.is_empty() {
+        return Vec::new();
+    }
+    via_transforms(a, b, a.len().max(b.len()))
+}
+
+/// Cross-correlation `r[k] = Σ a[n + k] · conj(b[n])` for every lag `k` where
+/// the two overlap, from `1 - b.len()` up to `a.len() - 1`
+pub fn correlate(a: &[Complex<f64>], b: &[Complex<f64>]) -> Vec<Complex<f64>> {
+    let reversed: Vec<Complex<f64>> = b.iter().rev().map(|x| x.conj()).collect();
+    linear(a, &reversed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn complexes(values: &[(f64, f64)]) -> Vec<Complex<f64>> {
+        values
+            .iter()
+            .map(|&(re, im)| Complex::new(re, im))
+            .collect()
+    }
+
+    fn reals(values: &[f64]) -> Vec<Complex<f64>> {
+        values.iter().map(|&re| Complex::new(re, 0.0)).collect()
+    }
+
+    fn assert_close(actual: &[Complex<f64>], expected: &[Complex<f64>]) {
+        assert_eq!(actual.len(), expected.len());
+        for (a, b) in actual.iter().zip(expected) {
+            assert!((a - b).norm() < 1e-9, "{} != {}", a, b);
+        }
+    }
+
+    /// Convolution straight from the definition
+    fn naive_linear(a: &[Complex<f64>], b: &[Complex<f64>]) -> Vec<Complex<f64>> {
+        let mut output = vec![Complex::new(0.0, 0.0); a.len() + b.len() - 1];
+        for (i, x) in a.iter().enumerate() {
+            for (j, y) in b.iter().enumerate() {
+                output[i + j] += x * y;
+            }
+        }
+        output
+    }
+
+    #[test]
+    fn test_linear() {
+        // (x + 2)(x + 3) = x² + 5x + 6
+        assert_close(
+            &linear(&reals(&[1.0, 2.0]), &reals(&[1.0, 3.0])),
+            &reals(&[1.0, 5.0, 6.0]),
+        );
+        let a = complexes(&[(1.0, 2.0), (-3.0, 0.5), (0.0, -1.0), (4.0, 4.0), (2.0, 0.0)]);
+        let b = complexes(&[(0.5, -1.0), (2.0, 3.0), (-1.0, 0.0)]);
+        assert_close(&linear(&a, &b), &naive_linear(&a, &b));
+        assert_close(&linear(&b, &a), &naive_linear(&a, &b));
+        assert!(linear(&a, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_circular() {
+        assert_close(
+            &circular(&reals(&[1.0, 2.0, 3.0]), &reals(&[4.0, 5.0, 6.0])),
+            &reals(&[31.0, 31.0, 28.0]),
+        );
+        // The shorter input is padded: [1, 1, 0, 0] ⊛ [1, 2, 3, 4]
+        assert_close(
+            &circular(&reals(&[1.0, 1.0]), &reals(&[1.0, 2.0, 3.0, 4.0])),
+            &reals(&[5.0, 3.0, 5.0, 7.0]),
+        );
+    }
+
+    #[test]
+    fn test_correlate() {
+        // Lags -1, 0 and 1
+        assert_close(
+            &correlate(&reals(&[1.0, 2.0]), &reals(&[3.0, 4.0])),
+            &reals(&[4.0, 11.0, 6.0]),
+        );
+        // A signal matches itself best at lag 0, conjugating the second input
+        let a = complexes(&[(1.0, 1.0), (0.0, 2.0), (-1.0, 0.0)]);
+        let r = correlate(&a, &a);
+        assert_eq!(r.len(), 5);
+        assert!((r[2] - Complex::new(7.0, 0.0)).norm() < 1e-9);
+        assert!(r.iter().all(|x| x.norm() <= r[2].norm() + 1e-9));
+    }
+}
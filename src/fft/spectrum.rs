@@ -0,0 +1,188 @@
+use super::format_real;
+use crate::render;
+use num_complex::Complex;
+use std::f64::consts::PI;
+
+// Bins weaker than this fraction of the strongest have their phase shown as 0,
+// as it is only rounding noise
+const PHASE_THRESHOLD: f64 = 1e-9;
+
+/// Taper applied to the samples before the transform, to reduce the leakage
+/// of a frequency into its neighbouring bins
+#[derive(Debug, Clone, Copy, PartialEq, Default, poise::ChoiceParameter)]
+pub enum Window {
+    #[default]
+    #[name = "none"]
+    Rectangular,
+    Hann,
+    Hamming,
+    Blackman,
+}
+
+impl Window {
+    /// Weight of sample `t` of `n`
+    fn weight(self, t: usize, n: usize) -> f64 {
+        if n <= 1 {
+            return 1.0;
+        }
+        let x = 2.0 * PI * t as f64 / (n - 1) as f64;
+        match self {
+            Window::Rectangular => 1.0,
+            Window::Hann => 0.5 - 0.5 * x.cos(),
+            Window::Hamming => 0.54 - 0.46 * x.cos(),
+            Window::Blackman => 0.42 - 0.5 * x.cos() + 0.08 * (2.0 * x).cos(),
+        }
+    }
+
+    pub fn apply(self, samples: &mut [Complex<f64>]) {
+        let n = samples.len();
+        for (t, sample) in samples.iter_mut().enumerate() {
+            *sample *= self.weight(t, n);
+        }
+    }
+}
+
+/// Magnitude and phase of the bins of a transform
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spectrum {
+    pub magnitudes: Vec<f64>,
+    /// In radians, between -π and π
+    pub phases: Vec<f64>,
+    /// Frequency step between bins in Hz, if the sample rate is known
+    pub resolution: Option<f64>,
+}
+
+impl Spectrum {
+    /// Spectrum of `transform`. The upper half of the transform of `real`
+    /// samples mirrors the lower half, so only bins up to half the sample
+    /// rate are kept.
+    pub fn new(transform: &[Complex<f64>], real: bool, sample_rate: Option<f64>) -> Spectrum {
+        let shown = if real {
+            transform.len() / 2 + 1
+        } else {
+            transform.len()
+        }
+        .min(transform.len());
+        let bins = &transform[..shown];
+        let magnitudes: Vec<f64> = bins.iter().map(|x| x.norm()).collect();
+        let strongest = magnitudes.iter().copied().fold(0.0, f64::max);
+        let phases = bins
+            .iter()
+            .zip(&magnitudes)
+            .map(|(x, &magnitude)| {
+                if magnitude > strongest * PHASE_THRESHOLD {
+                    x.arg()
+                } else {
+                    0.0
+                }
+            })
+            .collect();
+        Spectrum {
+            magnitudes,
+            phases,
+            resolution: sample_rate.map(|rate| rate / transform.len() as f64),
+        }
+    }
+
+    /// Strongest bin, leaving out the constant one unless it is alone
+    pub fn peak(&self) -> Option<usize> {
+        let skip = usize::from(self.magnitudes.len() > 1);
+        self.magnitudes
+            .iter()
+            .enumerate()
+            .skip(skip)
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(bin, _)| bin)
+    }
+
+    /// Frequency of `bin` in Hz, or its index without a sample rate
+    pub fn position(&self, bin: usize) -> String {
+        match self.resolution {
+            Some(resolution) => format!("{} Hz", format_real(bin as f64 * resolution)),
+            None => bin.to_string(),
+        }
+    }
+
+    /// PNG of the magnitudes above the phases
+    pub fn chart(&self) -> Result<Vec<u8>, String> {
+        let last = self.magnitudes.len().saturating_sub(1);
+        render::stacked_chart(
+            &[&self.magnitudes, &self.phases],
+            self.peak(),
+            &self.position(last),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fft::fft_calculator;
+
+    fn tone(n: usize, cycles: f64) -> Vec<Complex<f64>> {
+        (0..n)
+            .map(|t| Complex::new((2.0 * PI * cycles * t as f64 / n as f64).cos(), 0.0))
+            .collect()
+    }
+
+    #[test]
+    fn test_windows() {
+        for window in [Window::Hann, Window::Hamming, Window::Blackman] {
+            let mut samples = vec![Complex::new(1.0, 0.0); 9];
+            window.apply(&mut samples);
+            // Symmetric, peaking at 1 in the middle
+            assert!((samples[4].re - 1.0).abs() < 1e-12);
+            for t in 0..9 {
+                assert!((samples[t] - samples[8 - t]).norm() < 1e-12);
+                assert!(samples[t].re <= 1.0 + 1e-12);
+            }
+        }
+        let mut samples = vec![Complex::new(2.0, 0.0); 5];
+        Window::Hann.apply(&mut samples);
+        assert_eq!(samples[0], Complex::new(0.0, 0.0));
+        Window::Rectangular.apply(&mut samples);
+        assert_eq!(samples[2], Complex::new(2.0, 0.0));
+
+        let mut single = vec![Complex::new(3.0, 0.0)];
+        Window::Blackman.apply(&mut single);
+        assert_eq!(single, vec![Complex::new(3.0, 0.0)]);
+    }
+
+    #[test]
+    fn test_leakage() {
+        // Between two bins, a window keeps far bins much quieter
+        let far = |window: Window| {
+            let mut samples = tone(64, 10.5);
+            window.apply(&mut samples);
+            Spectrum::new(&fft_calculator(samples), true, None).magnitudes[30]
+        };
+        assert!(far(Window::Hann) < far(Window::Rectangular) / 10.0);
+        assert!(far(Window::Blackman) < far(Window::Rectangular) / 10.0);
+    }
+
+    #[test]
+    fn test_spectrum() {
+        let spectrum = Spectrum::new(&fft_calculator(tone(16, 3.0)), true, Some(800.0));
+        assert_eq!(spectrum.magnitudes.len(), 9);
+        assert_eq!(spectrum.peak(), Some(3));
+        assert_eq!(spectrum.position(3), "150 Hz");
+        assert_eq!(spectrum.position(8), "400 Hz");
+        assert!((spectrum.magnitudes[3] - 8.0).abs() < 1e-9);
+        assert!(spectrum.phases[3].abs() < 1e-9);
+        // Silent bins have no phase
+        assert_eq!(spectrum.phases[5], 0.0);
+
+        let complex: Vec<Complex<f64>> = (0..8)
+            .map(|t| Complex::from_polar(1.0, 2.0 * PI * 6.0 * t as f64 / 8.0))
+            .collect();
+        let spectrum = Spectrum::new(&fft_calculator(complex), false, None);
+        assert_eq!(spectrum.magnitudes.len(), 8);
+        assert_eq!(spectrum.peak(), Some(6));
+        assert_eq!(spectrum.position(6), "6");
+        assert!(spectrum.chart().is_ok());
+
+        let spectrum = Spectrum::new(&[Complex::new(-2.0, 0.0)], true, None);
+        assert_eq!(spectrum.peak(), Some(0));
+        assert!((spectrum.phases[0] - PI).abs() < 1e-12);
+    }
+}
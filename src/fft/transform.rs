@@ -0,0 +1,251 @@
+use num_complex::Complex;
+use std::f64::consts::PI;
+
+// Lengths with a prime factor above this go through Bluestein's algorithm
+const MAX_RADIX: usize = 31;
+
+/// Factors of `n` for the mixed-radix transform, fours first, or `None` if
+/// it has a prime factor larger than `MAX_RADIX`
+fn radices(mut n: usize) -> Option<Vec<usize>> {
+    let mut factors = Vec::new();
+    while n.is_multiple_of(4) {
+        factors.push(4);
+        n /= 4;
+    }
+    let mut p = 2;
+    while n > 1 {
+        if p > MAX_RADIX {
+            return None;
+        }
+        while n.is_multiple_of(p) {
+            factors.push(p);
+            n /= p;
+        }
+        p += 1;
+    }
+    Some(factors)
+}
+
+/// `exp(-2πi t / n)` for every `t` below `n`
+fn roots(n: usize) -> Vec<Complex<f64>> {
+    (0..n)
+        .map(|t| Complex::from_polar(1.0, -2.0 * PI * t as f64 / n as f64))
+        .collect()
+}
+
+/// Decimation-in-time transform over `radices`, whose product is the length
+/// of `data`.
+///
+/// The input is put in mixed-radix digit-reversed order once, after which
+/// every stage combines `r` neighbouring transforms of length `span` into
+/// one of length `r * span`, in place.
+fn mixed_radix(data: &mut [Complex<f64>], radices: &[usize]) {
+    let n = data.len();
+    // Element i goes where its digits, least significant first in the
+    // order of `radices`, are read most significant first
+    let reordered: Vec<Complex<f64>> = (0..n)
+        .map(|position| {
+            let (mut rest, mut size, mut index, mut weight) = (position, n, 0, 1);
+            for &r in radices {
+                size /= r;
+                index += rest / size * weight;
+                rest %= size;
+                weight *= r;
+            }
+            data[index]
+        })
+        .collect();
+    data.copy_from_slice(&reordered);
+
+    let roots = roots(n);
+    let mut scratch = [Complex::new(0.0, 0.0); MAX_RADIX];
+    let mut span = 1;
+    for &r in radices.iter().rev() {
+        let length = span * r;
+        let stride = n / length;
+        for block in (0..n).step_by(length) {
+            for k in 0..span {
+                let inputs = &mut scratch[..r];
+                for (j, input) in inputs.iter_mut().enumerate() {
+                    *input = data[block + k + j * span] * roots[j * k * stride];
+                }
+                for q in 0..r {
+                    data[block + k + q * span] = match (r, q) {
+                        (2, 0) => inputs[0] + inputs[1],
+                        (2, _) => inputs[0] - inputs[1],
+                        // Multiplying by -i and i exactly keeps small results clean
+                        (4, _) => {
+                            let (even, odd) = (inputs[0] + inputs[2], inputs[0] - inputs[2]);
+                            let (sum, difference) = (inputs[1] + inputs[3], inputs[1] - inputs[3]);
+                            let rotated = Complex::new(difference.im, -difference.re);
+                            [even + sum, odd + rotated, even - sum, odd - rotated][q]
+                        }
+                        _ => inputs
+                            .iter()
+                            .enumerate()
+                            .map(|(j, &x)| x * roots[(j * q % r) * (n / r)])
+                            .sum(),
+                    };
+                }
+            }
+        }
+        span = length;
+    }
+}
+
+/// Transform of any length as a convolution with a chirp, which is done
+/// with power-of-two transforms
+fn bluestein(data: &mut [Complex<f64>]) {
+    let n = data.len();
+    let size = (2 * n - 1).next_power_of_two();
+    // exp(-πi k² / n), with k² reduced mod 2n to keep the angle small
+    let chirp: Vec<Complex<f64>> = (0..n)
+        .map(|k| {
+            let square = (k as u128 * k as u128 % (2 * n as u128)) as f64;
+            Complex::from_polar(1.0, -PI * square / n as f64)
+        })
+        .collect();
+    let mut a = vec![Complex::new(0.0, 0.0); size];
+    for (k, (&x, &w)) in data.iter().zip(&chirp).enumerate() {
+        a[k] = x * w;
+    }
+    let mut b = vec![Complex::new(0.0, 0.0); size];
+    b[0] = chirp[0].conj();
+    for k in 1..n {
+        b[k] = chirp[k].conj();
+        b[size - k] = chirp[k].conj();
+    }
+    fft(&mut a);
+    fft(&mut b);
+    for (x, y) in a.iter_mut().zip(&b) {
+        *x = (*x * y).conj();
+    }
+    // The inverse transform, as the conjugate of the forward one
+    fft(&mut a);
+    let scale = 1.0 / size as f64;
+    for (k, x) in data.iter_mut().enumerate() {
+        *x = a[k].conj() * scale * chirp[k];
+    }
+}
+
+/// Discrete Fourier transform of `data` in place, for any length
+pub fn fft(data: &mut [Complex<f64>]) {
+    if data.len() <= 1 {
+        return;
+    }
+    match radices(data.len()) {
+        Some(radices) => mixed_radix(data, &radices),
+        None => bluestein(data),
+    }
+}
+
+/// `input` followed by zeros up to the next power of two
+pub fn zero_pad(mut input: Vec<Complex<f64>>) -> Vec<Complex<f64>> {
+    input.resize(input.len().next_power_of_two(), Complex::new(0.0, 0.0));
+    input
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn naive_dft(input: &[Complex<f64>]) -> Vec<Complex<f64>> {
+        let n = input.len();
+        (0..n)
+            .map(|k| {
+                input
+                    .iter()
+                    .enumerate()
+                    .map(|(t, &x)| {
+                        let angle = -2.0 * PI * (t * k % n) as f64 / n as f64;
+                        x * Complex::from_polar(1.0, angle)
+                    })
+                    .sum()
+            })
+            .collect()
+    }
+
+    fn assert_close(actual: &[Complex<f64>], expected: &[Complex<f64>]) {
+        assert_eq!(actual.len(), expected.len());
+        let scale: f64 = expected.iter().map(|x| x.norm()).fold(1.0, f64::max);
+        for (k, (a, b)) in actual.iter().zip(expected).enumerate() {
+            assert!(
+                (a - b).norm() <= 1e-9 * scale,
+                "bin {} of {}: {} != {}",
+                k,
+                actual.len(),
+                a,
+                b
+            );
+        }
+    }
+
+    fn transformed(input: &[Complex<f64>]) -> Vec<Complex<f64>> {
+        let mut data = input.to_vec();
+        fft(&mut data);
+        data
+    }
+
+    fn signal(n: usize) -> Vec<Complex<f64>> {
+        (0..n)
+            .map(|t| Complex::new((t as f64 * 0.7).sin() * 3.0 + t as f64, (t % 5) as f64))
+            .collect()
+    }
+
+    #[test]
+    fn test_radices() {
+        assert_eq!(radices(1), Some(vec![]));
+        assert_eq!(radices(64), Some(vec![4, 4, 4]));
+        assert_eq!(radices(360), Some(vec![4, 2, 3, 3, 5]));
+        assert_eq!(radices(31 * 29), Some(vec![29, 31]));
+        assert_eq!(radices(37), None);
+        assert_eq!(radices(2 * 1009), None);
+    }
+
+    #[test]
+    fn test_lengths() {
+        // Powers of two, mixed radices, every radix, and large primes
+        for n in [
+            2, 3, 4, 6, 8, 12, 15, 16, 30, 49, 60, 64, 97, 210, 360, 961, 1009, 1024,
+        ] {
+            let input = signal(n);
+            assert_close(&transformed(&input), &naive_dft(&input));
+        }
+    }
+
+    #[test]
+    fn test_edge_cases() {
+        let mut empty: Vec<Complex<f64>> = vec![];
+        fft(&mut empty);
+        assert!(empty.is_empty());
+        assert_eq!(
+            transformed(&[Complex::new(2.0, 1.0)]),
+            vec![Complex::new(2.0, 1.0)]
+        );
+        // An impulse has a flat spectrum
+        let mut impulse = vec![Complex::new(0.0, 0.0); 37];
+        impulse[0] = Complex::new(1.0, 0.0);
+        assert_close(&transformed(&impulse), &[Complex::new(1.0, 0.0); 37]);
+    }
+
+    #[test]
+    fn test_zero_pad() {
+        let padded = zero_pad(signal(5));
+        assert_eq!(padded.len(), 8);
+        assert_eq!(padded[..5], signal(5)[..]);
+        assert_eq!(padded[5..], [Complex::new(0.0, 0.0); 3]);
+        assert_eq!(zero_pad(signal(8)).len(), 8);
+    }
+
+    proptest! {
+        #[test]
+        fn matches_naive_dft(
+            input in prop::collection::vec((-100.0..100.0f64, -100.0..100.0f64), 0..300)
+        ) {
+            let input: Vec<Complex<f64>> =
+                input.into_iter().map(|(re, im)| Complex::new(re, im)).collect();
+            assert_close(&transformed(&input), &naive_dft(&input));
+        }
+    }
+}
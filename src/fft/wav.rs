@@ -0,0 +1,243 @@
+// Format codes of the fmt chunk
+const FORMAT_PCM: u16 = 1;
+const FORMAT_FLOAT: u16 = 3;
+const FORMAT_EXTENSIBLE: u16 = 0xfffe;
+
+/// Samples of a WAV file, its channels mixed down to one
+#[derive(Debug, Clone, PartialEq)]
+pub struct Audio {
+    pub sample_rate: u32,
+    /// Between -1 and 1
+    pub samples: Vec<f64>,
+    /// Whether the file was longer than the samples kept
+    pub truncated: bool,
+}
+
+/// How the samples of a file are stored
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Format {
+    float: bool,
+    channels: usize,
+    sample_rate: u32,
+    bits: u16,
+}
+
+fn u16_at(bytes: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([bytes[at], bytes[at + 1]])
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+}
+
+fn parse_format(chunk: &[u8]) -> Result<Format, String> {
+    if chunk.len() < 16 {
+        return Err("The WAV format chunk is too short".to_string());
+    }
+    let mut code = u16_at(chunk, 0);
+    if code == FORMAT_EXTENSIBLE {
+        // The real format is the start of the sub-format GUID
+        if chunk.len() < 26 {
+            return Err("The WAV format chunk is too short".to_string());
+        }
+        code = u16_at(chunk, 24);
+    }
+    let format = Format {
+        float: code == FORMAT_FLOAT,
+        channels: usize::from(u16_at(chunk, 2)),
+        sample_rate: u32_at(chunk, 4),
+        bits: u16_at(chunk, 14),
+    };
+    let supported = match code {
+        FORMAT_PCM => matches!(format.bits, 8 | 16 | 24 | 32),
+        FORMAT_FLOAT => matches!(format.bits, 32 | 64),
+        _ => false,
+    };
+    if !supported {
+        return Err(format!(
+            "Only uncompressed WAV files can be read, this one has format {} with {} bits",
+            code, format.bits
+        ));
+    }
+    if format.channels == 0 || format.sample_rate == 0 {
+        return Err("The WAV file has no channels or no sample rate".to_string());
+    }
+    Ok(format)
+}
+
+/// One sample stored in `bytes`, scaled to between -1 and 1
+fn sample(bytes: &[u8], format: Format) -> f64 {
+    match (format.float, format.bits) {
+        (true, 32) => f64::from(f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])),
+        (true, _) => f64::from_le_bytes(bytes[..8].try_into().unwrap_or_default()),
+        // Eight bit samples are the only unsigned ones
+        (false, 8) => (f64::from(bytes[0]) - 128.0) / 128.0,
+        (false, 16) => f64::from(i16::from_le_bytes([bytes[0], bytes[1]])) / 32768.0,
+        (false, 24) => {
+            f64::from(i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) >> 8) / 8388608.0
+        }
+        (false, _) => {
+            f64::from(i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])) / 2147483648.0
+        }
+    }
+}
+
+/// Decode a WAV file of integer or floating point samples, keeping at most
+/// `max_seconds` of it
+pub fn decode(bytes: &[u8], max_seconds: f64) -> Result<Audio, String> {
+    if bytes.len() < 12 || &bytes[..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return Err("Only WAV files can be read".to_string());
+    }
+    let mut format = None;
+    let mut at = 12;
+    while at + 8 <= bytes.len() {
+        let id = &bytes[at..at + 4];
+        // Files being streamed may give a size past the end
+        let size = (u32_at(bytes, at + 4) as usize).min(bytes.len() - at - 8);
+        let chunk = &bytes[at + 8..at + 8 + size];
+        match id {
+            b"fmt " => format = Some(parse_format(chunk)?),
+            b"data" => {
+                let format = format.ok_or("The WAV file has its samples before their format")?;
+                return Ok(mix_down(chunk, format, max_seconds));
+            }
+            _ => {}
+        }
+        // Chunks are padded to an even size
+        at += 8 + size + size % 2;
+    }
+    Err("The WAV file has no samples".to_string())
+}
+
+/// Average the channels of every frame of `data`, the samples of a file
+fn mix_down(data: &[u8], format: Format, max_seconds: f64) -> Audio {
+    let width = usize::from(format.bits / 8);
+    let frame_size = width * format.channels;
+    let max_frames = (max_seconds * f64::from(format.sample_rate)) as usize;
+    let frames = data.chunks_exact(frame_size);
+    let truncated = frames.len() > max_frames;
+    let samples = frames
+        .take(max_frames)
+        .map(|frame| {
+            let total: f64 = frame
+                .chunks_exact(width)
+                .map(|bytes| sample(bytes, format))
+                .sum();
+            total / format.channels as f64
+        })
+        .collect();
+    Audio {
+        sample_rate: format.sample_rate,
+        samples,
+        truncated,
+    }
+}
+
+/// A WAV file of 16 bit samples, for tests
+#[cfg(test)]
+pub fn encode(sample_rate: u32, channels: &[Vec<f64>]) -> Vec<u8> {
+    let count = channels.first().map_or(0, |c| c.len());
+    let data_size = (count * channels.len() * 2) as u32;
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(36 + data_size).to_le_bytes());
+    bytes.extend_from_slice(b"WAVEfmt ");
+    bytes.extend_from_slice(&16u32.to_le_bytes());
+    bytes.extend_from_slice(&FORMAT_PCM.to_le_bytes());
+    bytes.extend_from_slice(&(channels.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(&sample_rate.to_le_bytes());
+    bytes.extend_from_slice(&(sample_rate * channels.len() as u32 * 2).to_le_bytes());
+    bytes.extend_from_slice(&(channels.len() as u16 * 2).to_le_bytes());
+    bytes.extend_from_slice(&16u16.to_le_bytes());
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_size.to_le_bytes());
+    for i in 0..count {
+        for channel in channels {
+            let value = (channel[i] * 32767.0).round() as i16;
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        let left = vec![0.5, -0.5, 1.0, 0.0];
+        let right = vec![0.5, 0.5, -1.0, 0.25];
+        let audio = decode(&encode(8000, &[left, right]), 60.0).unwrap();
+        assert_eq!(audio.sample_rate, 8000);
+        assert!(!audio.truncated);
+        let expected = [0.5, 0.0, 0.0, 0.125];
+        assert_eq!(audio.samples.len(), 4);
+        for (a, b) in audio.samples.iter().zip(expected) {
+            assert!((a - b).abs() < 1e-4, "{} != {}", a, b);
+        }
+
+        let audio = decode(&encode(4, &[vec![0.1; 20]]), 2.0).unwrap();
+        assert_eq!(audio.samples.len(), 8);
+        assert!(audio.truncated);
+    }
+
+    #[test]
+    fn test_formats() {
+        let format = |float, bits| Format {
+            float,
+            channels: 1,
+            sample_rate: 8000,
+            bits,
+        };
+        assert_eq!(sample(&[0, 0x80], format(false, 16)), -1.0);
+        assert_eq!(
+            sample(&[0xff, 0xff, 0x7f], format(false, 24)),
+            8388607.0 / 8388608.0
+        );
+        assert_eq!(sample(&[0x00, 0x00, 0x80], format(false, 24)), -1.0);
+        assert_eq!(sample(&[0x00, 0x00, 0xc0], format(false, 24)), -0.5);
+        assert_eq!(sample(&[0, 0, 0, 0x40], format(false, 32)), 0.5);
+        assert_eq!(sample(&[128], format(false, 8)), 0.0);
+        assert_eq!(sample(&0.25f32.to_le_bytes(), format(true, 32)), 0.25);
+        assert_eq!(sample(&(-0.75f64).to_le_bytes(), format(true, 64)), -0.75);
+
+        // An extensible header with a float sub-format
+        let mut chunk = vec![0u8; 40];
+        chunk[..2].copy_from_slice(&FORMAT_EXTENSIBLE.to_le_bytes());
+        chunk[2..4].copy_from_slice(&2u16.to_le_bytes());
+        chunk[4..8].copy_from_slice(&48000u32.to_le_bytes());
+        chunk[14..16].copy_from_slice(&32u16.to_le_bytes());
+        chunk[24..26].copy_from_slice(&FORMAT_FLOAT.to_le_bytes());
+        assert_eq!(
+            parse_format(&chunk),
+            Ok(Format {
+                float: true,
+                channels: 2,
+                sample_rate: 48000,
+                bits: 32,
+            })
+        );
+        // Compressed formats such as A-law are refused
+        chunk[24..26].copy_from_slice(&6u16.to_le_bytes());
+        assert!(parse_format(&chunk).is_err());
+    }
+
+    #[test]
+    fn test_invalid_files() {
+        assert!(decode(b"OggS\0\0\0\0\0\0\0\0", 60.0).is_err());
+        assert!(decode(b"", 60.0).is_err());
+        let bytes = encode(8000, &[vec![0.0; 4]]);
+        // Without its data chunk
+        assert_eq!(
+            decode(&bytes[..36], 60.0),
+            Err("The WAV file has no samples".to_string())
+        );
+        // A data chunk claiming more than there is is cut short
+        let audio = decode(&bytes[..bytes.len() - 3], 60.0).unwrap();
+        assert_eq!(audio.samples.len(), 2);
+        let mut no_format = bytes.clone();
+        no_format[12..16].copy_from_slice(b"junk");
+        assert!(decode(&no_format, 60.0).is_err());
+    }
+}
@@ -20,7 +20,7 @@ async fn fetch_image_url(prompt: &str) -> Result<String, Error> {
     let url = output["images"][0]["url"]
         .as_str()
         .ok_or_else(|| "Failed to get image URL".to_string())?;
-    return Ok(url.to_string());
+    Ok(url.to_string())
 }
 
 #[poise::command(slash_command)]
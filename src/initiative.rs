@@ -0,0 +1,476 @@
+use crate::context::{Context, Error};
+use crate::dice::notation;
+use poise::serenity_prelude as serenity;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+// How often a waiting tracker checks whether its fight has ended
+const POLL_INTERVAL: Duration = Duration::from_secs(300);
+const MAX_COMBATANTS: usize = 40;
+const MAX_NAME_LENGTH: usize = 32;
+
+#[derive(Debug, Clone, PartialEq)]
+struct Combatant {
+    name: String,
+    modifier: i64,
+    initiative: i64,
+}
+
+/// Turn order of the fight in one channel
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tracker {
+    /// Invocation that started the fight, whose id prefixes its buttons
+    session: u64,
+    message: Option<serenity::MessageId>,
+    /// Highest initiative first
+    combatants: Vec<Combatant>,
+    turn: usize,
+    round: u32,
+}
+
+impl Tracker {
+    fn new(session: u64) -> Self {
+        Tracker {
+            session,
+            message: None,
+            combatants: Vec::new(),
+            turn: 0,
+            round: 1,
+        }
+    }
+
+    fn find(&self, name: &str) -> Option<usize> {
+        self.combatants
+            .iter()
+            .position(|c| c.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Put `combatant` in order, leaving the turn with whoever has it once
+    /// the fight is under way
+    fn add(&mut self, combatant: Combatant) -> Result<(), String> {
+        if self.find(&combatant.name).is_some() {
+            return Err(format!("{} is already in the fight", combatant.name));
+        }
+        if self.combatants.len() >= MAX_COMBATANTS {
+            return Err(format!(
+                "A fight can have at most {} combatants",
+                MAX_COMBATANTS
+            ));
+        }
+        // Until the first turn has passed, the fight opens with whoever is
+        // highest in the order
+        let started = self.round > 1 || self.turn > 0;
+        let current = started
+            .then(|| self.combatants.get(self.turn).map(|c| c.name.clone()))
+            .flatten();
+        self.combatants.push(combatant);
+        // Ties go to the higher modifier, then to whoever joined first
+        self.combatants
+            .sort_by_key(|c| std::cmp::Reverse((c.initiative, c.modifier)));
+        self.turn = current.and_then(|name| self.find(&name)).unwrap_or(0);
+        Ok(())
+    }
+
+    /// Take `name` out of the fight; if it was their turn, it passes on
+    fn remove(&mut self, name: &str) -> Result<Combatant, String> {
+        let index = self
+            .find(name)
+            .ok_or_else(|| format!("{} is not in the fight", name))?;
+        let combatant = self.combatants.remove(index);
+        if index < self.turn {
+            self.turn -= 1;
+        } else if self.turn == self.combatants.len() && self.turn > 0 {
+            self.turn = 0;
+            self.round += 1;
+        }
+        Ok(combatant)
+    }
+
+    fn next(&mut self) {
+        if self.combatants.is_empty() {
+            return;
+        }
+        self.turn += 1;
+        if self.turn == self.combatants.len() {
+            self.turn = 0;
+            self.round += 1;
+        }
+    }
+
+    fn previous(&mut self) {
+        if self.turn > 0 {
+            self.turn -= 1;
+        } else if self.round > 1 && !self.combatants.is_empty() {
+            self.round -= 1;
+            self.turn = self.combatants.len() - 1;
+        }
+    }
+
+    /// One line per combatant, marking whose turn it is
+    fn order(&self) -> String {
+        if self.combatants.is_empty() {
+            return "No combatants yet, add them with `/initiative add`".to_string();
+        }
+        self.combatants
+            .iter()
+            .enumerate()
+            .map(|(i, c)| {
+                let line = format!("`{:>3}` {} ({:+})", c.initiative, c.name, c.modifier);
+                if i == self.turn {
+                    format!("▶️ **{}**", line)
+                } else {
+                    format!("◻️ {}", line)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn embed(&self) -> serenity::CreateEmbed {
+        serenity::CreateEmbed::new()
+            .title(format!("⚔️ Initiative · round {}", self.round))
+            .description(self.order())
+    }
+}
+
+/// Fights in progress, one per channel
+#[derive(Default)]
+pub struct Trackers {
+    channels: Mutex<HashMap<serenity::ChannelId, Tracker>>,
+}
+
+impl Trackers {
+    fn start(&self, channel: serenity::ChannelId, session: u64) -> Result<(), String> {
+        let mut channels = self.channels.lock().unwrap();
+        if channels.contains_key(&channel) {
+            return Err(
+                "A fight is already going on here, end it with `/initiative end` first".to_string(),
+            );
+        }
+        channels.insert(channel, Tracker::new(session));
+        Ok(())
+    }
+
+    fn end(&self, channel: serenity::ChannelId) -> Option<Tracker> {
+        self.channels.lock().unwrap().remove(&channel)
+    }
+
+    /// Change the fight in `channel`, if there is one
+    fn update<T>(
+        &self,
+        channel: serenity::ChannelId,
+        f: impl FnOnce(&mut Tracker) -> Result<T, String>,
+    ) -> Result<T, String> {
+        let mut channels = self.channels.lock().unwrap();
+        let tracker = channels
+            .get_mut(&channel)
+            .ok_or("There is no fight in this channel, start one with `/initiative start`")?;
+        f(tracker)
+    }
+}
+
+/// Track turn order in a fight
+#[poise::command(
+    slash_command,
+    subcommands("start", "add", "remove", "end"),
+    subcommand_required
+)]
+pub async fn initiative(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Start a fight in this channel, with a pinned turn order
+#[poise::command(slash_command)]
+pub async fn start(ctx: Context<'_>) -> Result<(), Error> {
+    let trackers = &ctx.data().initiative;
+    let channel = ctx.channel_id();
+    let ctx_id = ctx.id();
+    if let Err(e) = trackers.start(channel, ctx_id) {
+        ctx.say(e).await?;
+        return Ok(());
+    }
+
+    let previous_id = format!("{}previous", ctx_id);
+    let next_id = format!("{}next", ctx_id);
+    let buttons = vec![serenity::CreateActionRow::Buttons(vec![
+        serenity::CreateButton::new(&previous_id)
+            .label("Previous")
+            .style(serenity::ButtonStyle::Secondary),
+        serenity::CreateButton::new(&next_id).label("Next"),
+    ])];
+    let embed = Tracker::new(ctx_id).embed();
+    let handle = ctx
+        .send(
+            poise::CreateReply::default()
+                .embed(embed)
+                .components(buttons),
+        )
+        .await?;
+    let message = handle.message().await?;
+    // Pinning needs the Manage Messages permission, the tracker works without
+    let _ = message.pin(ctx).await;
+    let _ = trackers.update(channel, |tracker| {
+        tracker.message = Some(message.id);
+        Ok(())
+    });
+
+    // Match whole ids, another invocation's id can start with this one's
+    let ids = [previous_id.clone(), next_id.clone()];
+    loop {
+        let ids = ids.clone();
+        let press = serenity::ComponentInteractionCollector::new(ctx)
+            .filter(move |press| ids.contains(&press.data.custom_id))
+            .timeout(POLL_INTERVAL)
+            .await;
+        let updated = trackers.update(channel, |tracker| {
+            if tracker.session != ctx_id {
+                return Err("This fight is over".to_string());
+            }
+            match &press {
+                Some(press) if press.data.custom_id == previous_id => tracker.previous(),
+                Some(press) if press.data.custom_id == next_id => tracker.next(),
+                _ => {}
+            }
+            Ok(tracker.embed())
+        });
+        match (press, updated) {
+            (Some(press), Ok(embed)) => {
+                let message = serenity::CreateInteractionResponseMessage::new().embed(embed);
+                press
+                    .create_response(
+                        ctx.serenity_context(),
+                        serenity::CreateInteractionResponse::UpdateMessage(message),
+                    )
+                    .await?;
+            }
+            (None, Ok(_)) => {}
+            // `/initiative end` already took the buttons away
+            (_, Err(_)) => return Ok(()),
+        }
+    }
+}
+
+/// Roll initiative for a combatant and put them in the turn order
+#[poise::command(slash_command)]
+pub async fn add(
+    ctx: Context<'_>,
+    #[description = "Name of the combatant"] name: String,
+    #[description = "Added to their d20 roll"]
+    #[min = -100]
+    #[max = 100]
+    modifier: Option<i64>,
+) -> Result<(), Error> {
+    let name = name.trim().to_string();
+    let modifier = modifier.unwrap_or(0);
+    if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+        ctx.say(format!(
+            "Names must have between 1 and {} characters",
+            MAX_NAME_LENGTH
+        ))
+        .await?;
+        return Ok(());
+    }
+
+    let expression = notation::parse(&format!("1d20{:+}", modifier))?;
+    let roll = ctx.data().dice_rng.with(|rng| expression.roll(rng));
+    let combatant = Combatant {
+        name: name.clone(),
+        modifier,
+        initiative: roll.total,
+    };
+    let channel = ctx.channel_id();
+    let added = ctx.data().initiative.update(channel, |tracker| {
+        tracker.add(combatant)?;
+        Ok((tracker.message, tracker.embed()))
+    });
+    match added {
+        Ok((message, embed)) => {
+            let rolled = format!(
+                "**{}** rolls **{}** for initiative ({} on the d20, {:+})",
+                name, roll.total, roll.terms[0].value, modifier
+            );
+            reply_and_refresh(ctx, rolled, message, embed).await
+        }
+        Err(e) => {
+            ctx.say(e).await?;
+            Ok(())
+        }
+    }
+}
+
+/// Take a combatant out of the turn order
+#[poise::command(slash_command)]
+pub async fn remove(
+    ctx: Context<'_>,
+    #[description = "Name of the combatant"] name: String,
+) -> Result<(), Error> {
+    let removed = ctx.data().initiative.update(ctx.channel_id(), |tracker| {
+        let combatant = tracker.remove(name.trim())?;
+        Ok((combatant, tracker.message, tracker.embed()))
+    });
+    match removed {
+        Ok((combatant, message, embed)) => {
+            let text = format!("**{}** left the fight", combatant.name);
+            reply_and_refresh(ctx, text, message, embed).await
+        }
+        Err(e) => {
+            ctx.say(e).await?;
+            Ok(())
+        }
+    }
+}
+
+/// End the fight in this channel
+#[poise::command(slash_command)]
+pub async fn end(ctx: Context<'_>) -> Result<(), Error> {
+    let channel = ctx.channel_id();
+    let Some(tracker) = ctx.data().initiative.end(channel) else {
+        ctx.say("There is no fight in this channel").await?;
+        return Ok(());
+    };
+    if let Some(message) = tracker.message {
+        let embed = tracker
+            .embed()
+            .footer(serenity::CreateEmbedFooter::new("The fight is over"));
+        let edit = serenity::EditMessage::new()
+            .embed(embed)
+            .components(Vec::new());
+        // The tracker may have been deleted or unpinned by hand
+        let _ = channel.edit_message(ctx, message, edit).await;
+        let _ = channel.unpin(ctx, message).await;
+    }
+    let rounds = tracker.round;
+    ctx.say(format!(
+        "The fight is over after {} round{}",
+        rounds,
+        if rounds == 1 { "" } else { "s" }
+    ))
+    .await?;
+    Ok(())
+}
+
+/// Say `text` and update the tracker message in place, or show the turn
+/// order in the reply if that message is gone
+async fn reply_and_refresh(
+    ctx: Context<'_>,
+    text: String,
+    message: Option<serenity::MessageId>,
+    embed: serenity::CreateEmbed,
+) -> Result<(), Error> {
+    let edit = serenity::EditMessage::new().embed(embed.clone());
+    let refreshed = match message {
+        Some(message) => ctx
+            .channel_id()
+            .edit_message(ctx, message, edit)
+            .await
+            .is_ok(),
+        None => false,
+    };
+    let reply = poise::CreateReply::default().content(text);
+    ctx.send(if refreshed { reply } else { reply.embed(embed) })
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn combatant(name: &str, initiative: i64, modifier: i64) -> Combatant {
+        Combatant {
+            name: name.to_string(),
+            modifier,
+            initiative,
+        }
+    }
+
+    fn names(tracker: &Tracker) -> Vec<&str> {
+        tracker.combatants.iter().map(|c| c.name.as_str()).collect()
+    }
+
+    fn fight() -> Tracker {
+        let mut tracker = Tracker::new(1);
+        tracker.add(combatant("Orc", 12, 0)).unwrap();
+        tracker.add(combatant("Elf", 18, 3)).unwrap();
+        tracker.add(combatant("Dwarf", 12, 1)).unwrap();
+        tracker
+    }
+
+    #[test]
+    fn test_order() {
+        let tracker = fight();
+        assert_eq!(names(&tracker), vec!["Elf", "Dwarf", "Orc"]);
+        assert_eq!(
+            tracker.order(),
+            "▶️ **` 18` Elf (+3)**\n◻️ ` 12` Dwarf (+1)\n◻️ ` 12` Orc (+0)"
+        );
+        let mut tracker = tracker;
+        assert!(tracker.add(combatant("elf", 1, 0)).is_err());
+        assert_eq!(
+            Tracker::new(1).order(),
+            "No combatants yet, add them with `/initiative add`"
+        );
+    }
+
+    #[test]
+    fn test_turns_and_rounds() {
+        let mut tracker = fight();
+        tracker.previous();
+        assert_eq!((tracker.turn, tracker.round), (0, 1));
+        tracker.next();
+        tracker.next();
+        assert_eq!((tracker.turn, tracker.round), (2, 1));
+        tracker.next();
+        assert_eq!((tracker.turn, tracker.round), (0, 2));
+        tracker.previous();
+        assert_eq!((tracker.turn, tracker.round), (2, 1));
+
+        let mut empty = Tracker::new(1);
+        empty.next();
+        empty.previous();
+        assert_eq!((empty.turn, empty.round), (0, 1));
+    }
+
+    #[test]
+    fn test_joining_keeps_the_turn() {
+        let mut tracker = fight();
+        tracker.next();
+        tracker.add(combatant("Goblin", 20, 2)).unwrap();
+        assert_eq!(tracker.combatants[tracker.turn].name, "Dwarf");
+        assert_eq!(names(&tracker), vec!["Goblin", "Elf", "Dwarf", "Orc"]);
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut tracker = fight();
+        tracker.next();
+        assert_eq!(tracker.remove("elf").unwrap().name, "Elf");
+        assert_eq!(tracker.combatants[tracker.turn].name, "Dwarf");
+        assert!(tracker.remove("Elf").is_err());
+
+        // The last one in the round leaving starts the next round
+        tracker.next();
+        tracker.remove("Orc").unwrap();
+        assert_eq!((tracker.turn, tracker.round), (0, 2));
+        tracker.remove("Dwarf").unwrap();
+        assert_eq!(tracker.turn, 0);
+        assert!(tracker.combatants.is_empty());
+    }
+
+    #[test]
+    fn test_trackers_are_per_channel() {
+        let trackers = Trackers::default();
+        let (a, b) = (serenity::ChannelId::new(1), serenity::ChannelId::new(2));
+        assert!(trackers.update(a, |_| Ok(())).is_err());
+        trackers.start(a, 10).unwrap();
+        assert!(trackers.start(a, 11).is_err());
+        trackers.start(b, 12).unwrap();
+        trackers
+            .update(a, |t| t.add(combatant("Orc", 5, 0)))
+            .unwrap();
+        assert_eq!(trackers.update(b, |t| Ok(t.combatants.len())), Ok(0));
+        assert_eq!(trackers.end(a).map(|t| t.session), Some(10));
+        assert!(trackers.end(a).is_none());
+    }
+}
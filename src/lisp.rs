@@ -1,27 +1,325 @@
 use crate::context::{Context, Error};
-use std::io::prelude::*;
-use tulisp::TulispContext;
+use crate::esolang::Budget;
+use std::cell::RefCell;
+use std::rc::Rc;
+use tulisp::{ErrorKind, TulispContext, TulispObject};
 
+pub mod macros;
+mod report;
+pub mod session;
+mod stdlib;
+
+// Sessions run on threads with stacks of this size, so deeply nested code
+// has room to be evaluated
+const STACK_SIZE: usize = 64 * 1024 * 1024;
+// Stack that must be left when entering a function or loop iteration
+const STACK_RED_ZONE: usize = 1024 * 1024;
+// Printed output kept per evaluation, far more than a reply can show
+const MAX_CAPTURED_OUTPUT: usize = 64 * 1024;
+
+/// Evaluate Lisp in a session that remembers your definitions
+#[poise::command(
+    slash_command,
+    subcommands("eval", "env", "save", "reset"),
+    subcommand_required
+)]
+pub async fn lisp(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Evaluate Lisp code in your session
 #[poise::command(slash_command)]
-pub async fn lisp(
+pub async fn eval(
     ctx: Context<'_>,
     #[description = "Lisp code"] code: String,
 ) -> Result<(), Error> {
-    let temp_file_name = "temp.lisp";
-    let mut file = std::fs::File::create(temp_file_name)?;
-    file.write_all(code.as_bytes())?;
-
-    let result = tokio::task::spawn_blocking(move || {
-        let mut lisp_ctx = TulispContext::new();
-        match lisp_ctx.eval_file(&temp_file_name) {
-            Ok(value) => value.to_string(),
-            Err(_) => "LispError!".to_string(),
+    let sessions = ctx.data().lisp_sessions.clone();
+    let user = ctx.author().id;
+    let result = ctx
+        .data()
+        .sandbox
+        .run(user, move |cancel| {
+            let token = cancel.clone();
+            sessions.with_session(user, &cancel, move |session| {
+                report::render(&code, &session.eval(&code, token))
+            })
+        })
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|result| result);
+
+    ctx.say(result.unwrap_or_else(|e| e)).await?;
+    Ok(())
+}
+
+/// List the functions, macros and variables defined in your session
+#[poise::command(slash_command)]
+pub async fn env(ctx: Context<'_>) -> Result<(), Error> {
+    let sessions = ctx.data().lisp_sessions.clone();
+    let user = ctx.author().id;
+    let result = ctx
+        .data()
+        .sandbox
+        .run(user, move |cancel| {
+            sessions.with_session(user, &cancel, |session| {
+                session
+                    .definitions()
+                    .iter()
+                    .map(|d| format!("{} `{}`", d.kind, d.name))
+                    .collect::<Vec<_>>()
+            })
+        })
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|result| result);
+
+    let message = match result {
+        Ok(lines) if lines.is_empty() => "Nothing is defined in your session".to_string(),
+        Ok(lines) => lines.join("\n"),
+        Err(e) => e,
+    };
+    ctx.say(message).await?;
+    Ok(())
+}
+
+/// Save your session's definitions so they survive bot restarts
+#[poise::command(slash_command)]
+pub async fn save(ctx: Context<'_>) -> Result<(), Error> {
+    let sessions = ctx.data().lisp_sessions.clone();
+    let user = ctx.author().id;
+    let result = ctx
+        .data()
+        .sandbox
+        .run(user, move |cancel| sessions.save(user, &cancel))
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|result| result);
+
+    let message = match result {
+        Ok(count) => format!("Saved {} definitions", count),
+        Err(e) => e,
+    };
+    ctx.say(message).await?;
+    Ok(())
+}
+
+/// Forget your session and its saved definitions
+#[poise::command(slash_command)]
+pub async fn reset(ctx: Context<'_>) -> Result<(), Error> {
+    let message = match ctx.data().lisp_sessions.reset(ctx.author().id) {
+        Ok(()) => "Your Lisp session was reset".to_string(),
+        Err(e) => e,
+    };
+    ctx.say(message).await?;
+    Ok(())
+}
+
+/// Charge every loop iteration and function call to `budget`, and refuse to
+/// recurse once the stack runs low.
+///
+/// `while` and `dotimes` are replaced, and `defun`, `defmacro` and `lambda`
+/// are wrapped to start every body with a call to the tick function. The
+/// tick is spliced in as a function object rather than a symbol, so
+/// programs cannot rebind it.
+fn limit(ctx: &mut TulispContext, budget: Rc<RefCell<Budget>>) -> Result<(), tulisp::Error> {
+    let tick = Rc::new(move || {
+        if stacker::remaining_stack().is_some_and(|left| left < STACK_RED_ZONE) {
+            return Err(limit_error("Recursion too deep".to_string()));
+        }
+        budget.borrow_mut().charge(1).map_err(limit_error)
+    });
+
+    let charge = tick.clone();
+    ctx.add_special_form("bytie--tick", move |_, _| {
+        charge()?;
+        Ok(TulispObject::nil())
+    });
+    let tick_fn = ctx.intern("bytie--tick").get()?;
+
+    for (name, leading) in [("defun", 2), ("defmacro", 2), ("lambda", 1)] {
+        let original = ctx.intern(name).get()?;
+        let tick_fn = tick_fn.clone();
+        ctx.add_special_form(name, move |ctx, args| {
+            // The name and parameter list come before the body
+            let mut head = Vec::new();
+            let mut body = args.clone();
+            for _ in 0..leading {
+                head.push(body.car()?);
+                body = body.cdr()?;
+            }
+            if body.car()?.as_string().is_ok() {
+                head.push(body.car()?);
+                body = body.cdr()?;
+            }
+            // A form headed by the tick cannot be the tail of a `defun`
+            if body.consp() {
+                let call = TulispObject::cons(tick_fn.clone(), TulispObject::nil());
+                body = TulispObject::cons(call, body);
+            }
+            let form = head
+                .into_iter()
+                .rev()
+                .fold(body, |rest, item| TulispObject::cons(item, rest));
+            ctx.eval(&TulispObject::cons(original.clone(), form))
+        });
+    }
+
+    let charge = tick.clone();
+    ctx.add_special_form("while", move |ctx, args| {
+        let condition = args.car()?;
+        let body = args.cdr()?;
+        let mut result = TulispObject::nil();
+        loop {
+            charge()?;
+            if ctx.eval(&condition)?.null() {
+                return Ok(result);
+            }
+            result = ctx.eval_progn(&body)?;
         }
-    })
-    .await?;
+    });
+
+    let charge = tick;
+    ctx.add_special_form("dotimes", move |ctx, args| {
+        let spec = args.car()?;
+        let body = args.cdr()?;
+        let var = spec.car()?;
+        let count = ctx.eval(&spec.cadr()?)?.as_int()?;
+        var.set_scope(0.into())?;
+        for i in 0..count {
+            charge()?;
+            var.set(i.into())?;
+            ctx.eval_progn(&body)?;
+        }
+        var.unset()?;
+        ctx.eval(&spec.caddr()?)
+    });
+    Ok(())
+}
+
+/// Send `print`, `princ` and `message` to `output` instead of stdout
+fn capture_output(
+    ctx: &mut TulispContext,
+    output: Rc<RefCell<String>>,
+) -> Result<(), tulisp::Error> {
+    let write = move |text: &str| {
+        let mut output = output.borrow_mut();
+        let room = MAX_CAPTURED_OUTPUT.saturating_sub(output.len());
+        output.extend(text.chars().take(room));
+    };
+    let write = Rc::new(write);
+
+    let print = write.clone();
+    ctx.add_special_form("print", move |ctx, args| {
+        let value = ctx.eval(&args.car()?)?;
+        print(&format!("{}\n", value));
+        Ok(value)
+    });
 
-    std::fs::remove_file(temp_file_name)?;
+    let princ = write.clone();
+    ctx.add_special_form("princ", move |ctx, args| {
+        let value = ctx.eval(&args.car()?)?;
+        princ(&value.fmt_string());
+        Ok(value)
+    });
 
-    ctx.say(result).await?;
+    // `message` formats its arguments like `format` and prints a line
+    let format = ctx.intern("format").get()?;
+    ctx.add_special_form("message", move |ctx, args| {
+        let text = ctx.eval(&TulispObject::cons(format.clone(), args.clone()))?;
+        write(&format!("{}\n", text.as_string()?));
+        Ok(text)
+    });
     Ok(())
 }
+
+fn limit_error(message: String) -> tulisp::Error {
+    tulisp::Error::new(ErrorKind::OutOfRange, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::session::Session;
+    use super::*;
+    use crate::sandbox::CancelToken;
+
+    fn evaluate(code: &str, cancel: CancelToken) -> report::Evaluation {
+        stacker::grow(STACK_SIZE, || Session::new().unwrap().eval(code, cancel))
+    }
+
+    fn run(code: &str) -> String {
+        let evaluation = evaluate(code, CancelToken::default());
+        evaluation.value.unwrap_or_else(|e| e.message)
+    }
+
+    fn failure(code: &str, cancel: CancelToken) -> String {
+        evaluate(code, cancel).value.unwrap_err().message
+    }
+
+    fn cancelled() -> CancelToken {
+        let cancel = CancelToken::default();
+        cancel.cancel();
+        cancel
+    }
+
+    #[test]
+    fn test_eval() {
+        assert_eq!(run("(+ 1 2)"), "3");
+        assert_eq!(
+            run("(let ((x 0)) (dotimes (i 5) (setq x (+ x i))) x)"),
+            "10"
+        );
+        assert_eq!(
+            run("(defun fact (n) \"Factorial\" (if (< n 2) 1 (* n (fact (- n 1))))) (fact 10)"),
+            "3628800"
+        );
+        assert_eq!(run("(funcall (lambda (x) (* x x)) 7)"), "49");
+    }
+
+    #[test]
+    fn test_tail_calls_still_work() {
+        let code =
+            "(defun count (n acc) (if (equal n 0) acc (count (- n 1) (+ acc 1)))) (count 10000 0)";
+        assert_eq!(run(code), "10000");
+    }
+
+    #[test]
+    fn test_cancelled_loop_stops() {
+        assert_eq!(
+            failure("(while t)", cancelled()),
+            "Execution time limit exceeded"
+        );
+    }
+
+    #[test]
+    fn test_deep_recursion_stops() {
+        assert_eq!(
+            failure("(defun f (x) (+ 1 (f x))) (f 0)", CancelToken::default()),
+            "Recursion too deep"
+        );
+    }
+
+    #[test]
+    fn test_tick_cannot_be_rebound() {
+        let code = "(defun bytie--tick () nil) (defun loop () (loop)) (loop)";
+        assert_eq!(failure(code, cancelled()), "Execution time limit exceeded");
+    }
+
+    #[test]
+    fn test_output_is_captured() {
+        let evaluation = evaluate(
+            "(print 1) (princ \"a\") (princ \"b\") (message \"%d items\" 3)",
+            CancelToken::default(),
+        );
+        assert_eq!(evaluation.output, "1\nab3 items\n");
+        assert_eq!(evaluation.value, Ok("\"3 items\"".to_string()));
+    }
+
+    #[test]
+    fn test_error_points_at_form() {
+        let code = "(setq x 1)\n(+ x (car x))";
+        let evaluation = evaluate(code, CancelToken::default());
+        let reply = report::render(code, &evaluation);
+        assert!(reply.starts_with("Error: TypeMismatch at line 2, column 6:"));
+        assert!(reply.ends_with("2 | (+ x (car x))\n  |      ^^^^^^^\n```"));
+    }
+}
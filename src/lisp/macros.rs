@@ -0,0 +1,365 @@
+use super::report;
+use super::session::Session;
+use super::STACK_SIZE;
+use crate::context::{Context, Error};
+use crate::store::JsonStore;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tulisp::TulispObject;
+
+pub const MACROS_FILE: &str = "macros.json";
+const MAX_NAME_LENGTH: usize = 32;
+const MAX_PARAMS: usize = 8;
+// Binding these would not fail, but would change every `t` and `nil` in the body
+const RESERVED_NAMES: [&str; 2] = ["nil", "t"];
+
+/// A guild command written in Lisp
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Macro {
+    pub author: u64,
+    pub author_name: String,
+    pub params: Vec<String>,
+    pub body: String,
+}
+
+impl Macro {
+    /// Bind the whitespace-separated words of `args` to the parameters, the
+    /// last parameter taking whatever is left
+    fn bind(&self, args: &str) -> Result<Vec<(String, String)>, String> {
+        let mut rest = args.trim();
+        let mut bound = Vec::new();
+        for (i, param) in self.params.iter().enumerate() {
+            let value = if i + 1 == self.params.len() {
+                std::mem::take(&mut rest)
+            } else {
+                let (word, tail) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                rest = tail.trim_start();
+                word
+            };
+            if value.is_empty() {
+                return Err(format!(
+                    "This macro takes {} arguments: {}",
+                    self.params.len(),
+                    self.params.join(" ")
+                ));
+            }
+            bound.push((param.clone(), value.to_string()));
+        }
+        if !rest.is_empty() {
+            return Err("This macro takes no arguments".to_string());
+        }
+        Ok(bound)
+    }
+}
+
+type GuildMacros = BTreeMap<String, Macro>;
+
+/// Macros of every guild
+pub type Macros = JsonStore<GuildMacros>;
+
+fn not_found(name: &str) -> String {
+    format!("There is no macro named '{}'", name)
+}
+
+fn is_identifier(name: &str) -> bool {
+    !name.is_empty()
+        && name.chars().count() <= MAX_NAME_LENGTH
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Parse space-separated parameter names
+fn parse_params(params: &str) -> Result<Vec<String>, String> {
+    let params: Vec<String> = params.split_whitespace().map(str::to_string).collect();
+    if params.len() > MAX_PARAMS {
+        return Err(format!("Macros can have at most {} parameters", MAX_PARAMS));
+    }
+    if let Some(bad) = params
+        .iter()
+        .find(|p| !is_identifier(p) || p.parse::<f64>().is_ok())
+    {
+        return Err(format!("'{}' is not a valid parameter name", bad));
+    }
+    if let Some(reserved) = params.iter().find(|p| RESERVED_NAMES.contains(&p.as_str())) {
+        return Err(format!(
+            "'{}' is reserved and cannot be a parameter",
+            reserved
+        ));
+    }
+    if let Some((i, _)) = params
+        .iter()
+        .enumerate()
+        .find(|(i, p)| params[..*i].contains(p))
+    {
+        return Err(format!("Parameter '{}' is repeated", params[i]));
+    }
+    Ok(params)
+}
+
+/// Numbers are passed as numbers, anything else as a string
+fn argument_value(text: &str) -> TulispObject {
+    if let Ok(n) = text.parse::<i64>() {
+        n.into()
+    } else if let Ok(x) = text.parse::<f64>() {
+        x.into()
+    } else {
+        text.into()
+    }
+}
+
+/// Evaluate `body` in a fresh session with `bindings` set, replying as
+/// `/lisp eval` would
+fn evaluate(
+    body: &str,
+    bindings: Vec<(String, String)>,
+    cancel: crate::sandbox::CancelToken,
+) -> String {
+    stacker::grow(STACK_SIZE, || {
+        let mut session = match Session::new() {
+            Ok(session) => session,
+            Err(e) => return e,
+        };
+        for (name, value) in bindings {
+            if let Err(e) = session.bind(&name, argument_value(&value)) {
+                return format!("Could not bind '{}': {}", name, e.desc());
+            }
+        }
+        report::render(body, &session.eval(body, cancel))
+    })
+}
+
+/// Whether the invoking member may manage this guild
+async fn is_admin(ctx: Context<'_>) -> bool {
+    ctx.author_member()
+        .await
+        .and_then(|member| member.permissions)
+        .is_some_and(|permissions| permissions.manage_guild())
+}
+
+/// Commands of this server written in Lisp
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "macro",
+    subcommands("define", "run", "list", "delete"),
+    subcommand_required
+)]
+pub async fn macros(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Define a macro; its parameters are bound as variables when it runs
+#[poise::command(slash_command, guild_only)]
+pub async fn define(
+    ctx: Context<'_>,
+    #[description = "Macro name"] name: String,
+    #[description = "Lisp code"] body: String,
+    #[description = "Parameter names, separated by spaces"] params: Option<String>,
+) -> Result<(), Error> {
+    let Some(guild) = ctx.guild_id() else {
+        return Ok(());
+    };
+    let name = name.trim().to_string();
+    let result = if !is_identifier(&name) {
+        Err(format!(
+            "Macro names must be 1 to {} letters, digits, - or _",
+            MAX_NAME_LENGTH
+        ))
+    } else {
+        parse_params(&params.unwrap_or_default()).and_then(|params| {
+            let definition = Macro {
+                author: ctx.author().id.get(),
+                author_name: ctx.author().name.clone(),
+                params,
+                body,
+            };
+            ctx.data().macros.update(guild, |m| {
+                if m.contains_key(&name) {
+                    return Err(format!("A macro named '{}' already exists", name));
+                }
+                m.insert(name.clone(), definition);
+                Ok(format!("Defined '{}', try it with `/macro run`", name))
+            })
+        })
+    };
+    ctx.say(result.unwrap_or_else(|e| e)).await?;
+    Ok(())
+}
+
+/// Run a macro of this server
+#[poise::command(slash_command, guild_only)]
+pub async fn run(
+    ctx: Context<'_>,
+    #[description = "Macro name"] name: String,
+    #[description = "Arguments, separated by spaces"] args: Option<String>,
+) -> Result<(), Error> {
+    let Some(guild) = ctx.guild_id() else {
+        return Ok(());
+    };
+    let definition = ctx.data().macros.view(guild, |m| m.get(&name).cloned());
+    let Some(definition) = definition else {
+        ctx.say(not_found(&name)).await?;
+        return Ok(());
+    };
+    let bindings = match definition.bind(&args.unwrap_or_default()) {
+        Ok(bindings) => bindings,
+        Err(e) => {
+            ctx.say(e).await?;
+            return Ok(());
+        }
+    };
+
+    let job = move |cancel| evaluate(&definition.body, bindings, cancel);
+    let message = match ctx.data().sandbox.run(ctx.author().id, job).await {
+        Ok(reply) => reply,
+        Err(e) => format!("Error running macro: {}", e),
+    };
+    ctx.say(message).await?;
+    Ok(())
+}
+
+/// List the macros of this server
+#[poise::command(slash_command, guild_only)]
+pub async fn list(ctx: Context<'_>) -> Result<(), Error> {
+    let Some(guild) = ctx.guild_id() else {
+        return Ok(());
+    };
+    let lines: Vec<String> = ctx.data().macros.view(guild, |m| {
+        m.iter()
+            .map(|(name, m)| {
+                let usage = std::iter::once(name.as_str())
+                    .chain(m.params.iter().map(String::as_str))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                format!("**{}** by {}", usage, m.author_name)
+            })
+            .collect()
+    });
+    if lines.is_empty() {
+        ctx.say("There are no macros yet").await?;
+    } else {
+        ctx.say(lines.join("\n")).await?;
+    }
+    Ok(())
+}
+
+/// Delete a macro; only its author or server managers can
+#[poise::command(slash_command, guild_only)]
+pub async fn delete(
+    ctx: Context<'_>,
+    #[description = "Macro name"] name: String,
+) -> Result<(), Error> {
+    let Some(guild) = ctx.guild_id() else {
+        return Ok(());
+    };
+    let user = ctx.author().id.get();
+    let admin = is_admin(ctx).await;
+    let result = ctx.data().macros.update(guild, |m| {
+        let definition = m.get(&name).ok_or_else(|| not_found(&name))?;
+        if definition.author != user && !admin {
+            return Err(format!(
+                "Only {} or a server manager can delete '{}'",
+                definition.author_name, name
+            ));
+        }
+        m.remove(&name);
+        Ok(format!("Deleted '{}'", name))
+    });
+    ctx.say(result.unwrap_or_else(|e| e)).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sandbox::CancelToken;
+    use poise::serenity_prelude as serenity;
+
+    fn greeting(params: &[&str], body: &str) -> Macro {
+        Macro {
+            author: 1,
+            author_name: "ada".to_string(),
+            params: params.iter().map(|p| p.to_string()).collect(),
+            body: body.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse_params() {
+        assert_eq!(
+            parse_params(" who  times "),
+            Ok(vec!["who".to_string(), "times".to_string()])
+        );
+        assert_eq!(parse_params(""), Ok(vec![]));
+        assert!(parse_params("a (b)").is_err());
+        assert!(parse_params("12").is_err());
+        assert!(parse_params("a b a").unwrap_err().contains("repeated"));
+        assert!(parse_params("x nil").unwrap_err().contains("reserved"));
+        assert!(parse_params("t").unwrap_err().contains("reserved"));
+    }
+
+    #[test]
+    fn test_bind() {
+        let m = greeting(&["times", "who"], "");
+        assert_eq!(
+            m.bind("3  Ada  Lovelace "),
+            Ok(vec![
+                ("times".to_string(), "3".to_string()),
+                ("who".to_string(), "Ada  Lovelace".to_string())
+            ])
+        );
+        assert!(m.bind("3").is_err());
+        assert!(greeting(&[], "").bind("extra").is_err());
+        assert_eq!(greeting(&[], "").bind(" "), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_evaluate_binds_arguments() {
+        let m = greeting(
+            &["n", "who"],
+            "(concat \"Hi \" who \" \" (prin1-to-string (* n 2)))",
+        );
+        let bindings = m.bind("21 Ada").unwrap();
+        assert_eq!(
+            evaluate(&m.body, bindings, CancelToken::default()),
+            "\"Hi Ada 42\""
+        );
+    }
+
+    #[test]
+    fn test_evaluate_reports_bind_failures() {
+        // Keywords are constants, parameter names cannot be one
+        let bindings = vec![(":x".to_string(), "1".to_string())];
+        let reply = evaluate(":x", bindings, CancelToken::default());
+        assert!(reply.starts_with("Could not bind ':x'"), "{}", reply);
+    }
+
+    #[test]
+    fn test_evaluate_is_limited() {
+        let cancel = CancelToken::default();
+        cancel.cancel();
+        let reply = evaluate("(while t)", vec![], cancel);
+        assert!(reply.contains("Execution time limit exceeded"));
+    }
+
+    #[test]
+    fn test_macros_persist() {
+        let path = std::env::temp_dir().join(format!("bytie-macros-{}.json", std::process::id()));
+        let guild = serenity::GuildId::new(1);
+        let macros = Macros::load(&path).unwrap();
+        macros
+            .update(guild, |m| {
+                m.insert("hi".to_string(), greeting(&["who"], "who"));
+                Ok(())
+            })
+            .unwrap();
+        let loaded = Macros::load(&path).unwrap();
+        assert_eq!(
+            loaded.view(guild, |m| m.get("hi").cloned()),
+            Some(greeting(&["who"], "who"))
+        );
+        assert!(loaded.view(serenity::GuildId::new(2), |m| m.is_empty()));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
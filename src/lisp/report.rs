@@ -0,0 +1,235 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+use tulisp::TulispContext;
+
+const MESSAGE_LIMIT: usize = 2000;
+
+// A backtrace line of `tulisp::Error::format` for a list form of code run
+// with `eval_string`. Atoms are skipped, their spans can belong to wherever
+// the value was first read.
+static TRACE_SPAN: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?m)^<eval_string>:(\d+)\.(\d+)-(\d+)\.(\d+):\s+at \(").unwrap());
+
+/// Source range of a form, as 1-based line and column pairs with an
+/// exclusive end
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Span {
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Failure {
+    pub kind: String,
+    pub message: String,
+    /// The innermost form the error passed through
+    pub span: Option<Span>,
+}
+
+impl Failure {
+    pub fn new(error: &tulisp::Error, ctx: &TulispContext) -> Self {
+        // The backtrace is private, so read the spans back out of its rendering
+        let span = TRACE_SPAN.captures(&error.format(ctx)).map(|c| {
+            let n = |i: usize| c[i].parse().unwrap_or(0);
+            Span {
+                start: (n(1), n(2)),
+                end: (n(3), n(4)),
+            }
+        });
+        Failure {
+            kind: error.kind().to_string(),
+            message: error.desc(),
+            span,
+        }
+    }
+}
+
+/// What one evaluation printed and returned
+#[derive(Debug, Clone, PartialEq)]
+pub struct Evaluation {
+    pub output: String,
+    pub value: Result<String, Failure>,
+}
+
+/// Reply for `evaluation` of `code`: printed output first, then the value,
+/// or the error with the failing form marked. The value or error is kept
+/// whole when it fits, the output gets whatever room is left.
+pub fn render(code: &str, evaluation: &Evaluation) -> String {
+    let frame = "Output:\n```\n\n```\n".chars().count();
+    // Leave the output room for at least its "…" line
+    let limit = if evaluation.output.is_empty() {
+        MESSAGE_LIMIT
+    } else {
+        MESSAGE_LIMIT - frame - 2
+    };
+    let result = match &evaluation.value {
+        Ok(value) => truncate(value, limit),
+        Err(failure) => {
+            let marked = render_failure(code, failure);
+            if marked.chars().count() <= limit {
+                marked
+            } else {
+                // Most likely a very long line, too long to quote
+                let unmarked = Failure {
+                    span: None,
+                    ..failure.clone()
+                };
+                truncate(&render_failure(code, &unmarked), limit)
+            }
+        }
+    };
+    if evaluation.output.is_empty() {
+        return result;
+    }
+
+    let room = MESSAGE_LIMIT - frame - result.chars().count();
+    let output = evaluation.output.trim_end_matches('\n');
+    let output: String = if output.chars().count() > room {
+        let mut shown: String = output.chars().take(room - 2).collect();
+        shown.push_str("\n…");
+        shown
+    } else {
+        output.to_string()
+    };
+    format!("Output:\n```\n{}\n```\n{}", output, result)
+}
+
+/// `text` cut to at most `limit` characters, ending in "…" when it was cut
+fn truncate(text: &str, limit: usize) -> String {
+    if text.chars().count() <= limit {
+        return text.to_string();
+    }
+    let mut shown: String = text.chars().take(limit - 1).collect();
+    shown.push('…');
+    shown
+}
+
+fn render_failure(code: &str, failure: &Failure) -> String {
+    let Some(span) = failure.span else {
+        return format!("Error: {}: {}", failure.kind, failure.message);
+    };
+    let (line, column) = span.start;
+    let mut message = format!(
+        "Error: {} at line {}, column {}: {}",
+        failure.kind, line, column, failure.message
+    );
+    if let Some(text) = code.lines().nth(line.saturating_sub(1)) {
+        let length = text.chars().count();
+        let start = column.saturating_sub(1).min(length);
+        let end = if span.end.0 == line {
+            span.end.1.saturating_sub(1).min(length)
+        } else {
+            length
+        };
+        let gutter = line.to_string();
+        message.push_str(&format!(
+            "\n```\n{} | {}\n{} | {}{}\n```",
+            gutter,
+            text,
+            " ".repeat(gutter.len()),
+            " ".repeat(start),
+            "^".repeat(end.saturating_sub(start).max(1))
+        ));
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failure(span: Option<Span>) -> Failure {
+        Failure {
+            kind: "TypeMismatch".to_string(),
+            message: "Expected number".to_string(),
+            span,
+        }
+    }
+
+    #[test]
+    fn test_failure_span() {
+        let mut ctx = TulispContext::new();
+        let error = ctx.eval_string("(+ 1\n   (car 5))").unwrap_err();
+        let failure = Failure::new(&error, &ctx);
+        assert_eq!(failure.kind, "TypeMismatch");
+        assert_eq!(
+            failure.span,
+            Some(Span {
+                start: (2, 4),
+                end: (2, 11)
+            })
+        );
+    }
+
+    #[test]
+    fn test_render_failure() {
+        let code = "(setq x 1)\n(+ x \"a\")";
+        let span = Span {
+            start: (2, 1),
+            end: (2, 10),
+        };
+        assert_eq!(
+            render_failure(code, &failure(Some(span))),
+            "Error: TypeMismatch at line 2, column 1: Expected number\n```\n\
+             2 | (+ x \"a\")\n  | ^^^^^^^^^\n```"
+        );
+        assert_eq!(
+            render_failure(code, &failure(None)),
+            "Error: TypeMismatch: Expected number"
+        );
+    }
+
+    #[test]
+    fn test_render_output() {
+        let evaluation = Evaluation {
+            output: "hello\n".to_string(),
+            value: Ok("nil".to_string()),
+        };
+        assert_eq!(render("", &evaluation), "Output:\n```\nhello\n```\nnil");
+
+        let evaluation = Evaluation {
+            output: "x".repeat(5000),
+            value: Ok("nil".to_string()),
+        };
+        let reply = render("", &evaluation);
+        assert_eq!(reply.chars().count(), MESSAGE_LIMIT);
+        assert!(reply.ends_with("x\n…\n```\nnil"));
+    }
+
+    #[test]
+    fn test_render_long_value() {
+        let evaluation = Evaluation {
+            output: String::new(),
+            value: Ok("9".repeat(5000)),
+        };
+        let reply = render("", &evaluation);
+        assert_eq!(reply.chars().count(), MESSAGE_LIMIT);
+        assert!(reply.ends_with("99…"));
+
+        // The value takes priority, leaving the output no room but for "…"
+        let evaluation = Evaluation {
+            output: "hello\n".to_string(),
+            value: Ok("9".repeat(5000)),
+        };
+        let reply = render("", &evaluation);
+        assert_eq!(reply.chars().count(), MESSAGE_LIMIT);
+        assert!(reply.starts_with("Output:\n```\n\n…\n```\n99"));
+    }
+
+    #[test]
+    fn test_render_long_failure() {
+        let code = format!("(+ 1 \"{}\")", "a".repeat(3000));
+        let span = Span {
+            start: (1, 1),
+            end: (1, 3010),
+        };
+        let evaluation = Evaluation {
+            output: String::new(),
+            value: Err(failure(Some(span))),
+        };
+        assert_eq!(
+            render(&code, &evaluation),
+            "Error: TypeMismatch: Expected number"
+        );
+    }
+}
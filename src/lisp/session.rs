@@ -0,0 +1,471 @@
+use super::report::{Evaluation, Failure};
+use super::{capture_output, limit, stdlib, STACK_SIZE};
+use crate::esolang::Budget;
+use crate::sandbox::CancelToken;
+use poise::serenity_prelude as serenity;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
+use tulisp::{TulispContext, TulispObject};
+
+pub const SESSIONS_DIR: &str = "lisp_sessions";
+// Sessions nobody has used for this long are dropped
+const IDLE_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+// Each live session holds a thread with a large stack, so past this many the
+// least recently used one is dropped
+const MAX_SESSIONS: usize = 16;
+
+/// A top-level definition made in a session, kept as source so it can be
+/// listed and saved
+#[derive(Debug, Clone, PartialEq)]
+pub struct Definition {
+    pub kind: &'static str,
+    pub name: String,
+    pub source: String,
+}
+
+/// An interpreter that keeps its definitions between evaluations
+pub struct Session {
+    ctx: TulispContext,
+    budget: Rc<RefCell<Budget>>,
+    output: Rc<RefCell<String>>,
+    definitions: Vec<Definition>,
+}
+
+impl Session {
+    pub fn new() -> Result<Self, String> {
+        let mut ctx = TulispContext::new();
+        let budget = Rc::new(RefCell::new(Budget::default()));
+        let output = Rc::new(RefCell::new(String::new()));
+        limit(&mut ctx, budget.clone()).map_err(|e| e.desc())?;
+        capture_output(&mut ctx, output.clone()).map_err(|e| e.desc())?;
+        stdlib::install(&mut ctx);
+        Ok(Session {
+            ctx,
+            budget,
+            output,
+            definitions: Vec::new(),
+        })
+    }
+
+    /// Evaluate `code`, stopping when `cancel` is set or the step budget runs out
+    pub fn eval(&mut self, code: &str, cancel: CancelToken) -> Evaluation {
+        *self.budget.borrow_mut() = Budget::new(cancel);
+        self.output.borrow_mut().clear();
+        let value = match self.ctx.eval_string(code) {
+            Ok(value) => {
+                self.remember(code);
+                Ok(value.to_string())
+            }
+            Err(e) => Err(Failure::new(&e, &self.ctx)),
+        };
+        Evaluation {
+            output: self.output.take(),
+            value,
+        }
+    }
+
+    /// Set the global variable `name` to `value`, which fails for constants
+    /// such as keywords
+    pub fn bind(&mut self, name: &str, value: TulispObject) -> Result<(), tulisp::Error> {
+        self.ctx.intern(name).set(value)
+    }
+
+    pub fn definitions(&self) -> &[Definition] {
+        &self.definitions
+    }
+
+    /// Source of every definition, in the order they were made
+    pub fn saved_source(&self) -> String {
+        self.definitions
+            .iter()
+            .map(|d| d.source.as_str())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Replay saved definitions, skipping any that no longer evaluate.
+    /// Returns false when `cancel` stopped the replay part way.
+    fn restore(&mut self, saved: &str, cancel: &CancelToken) -> bool {
+        for form in top_level_forms(saved) {
+            self.eval(form, cancel.clone());
+        }
+        !cancel.is_cancelled()
+    }
+
+    fn remember(&mut self, code: &str) {
+        for form in top_level_forms(code) {
+            let Some(definition) = definition(form) else {
+                continue;
+            };
+            match self
+                .definitions
+                .iter_mut()
+                .find(|d| d.name == definition.name)
+            {
+                Some(existing) => *existing = definition,
+                None => self.definitions.push(definition),
+            }
+        }
+    }
+}
+
+/// Split source into its top-level forms, skipping comments
+fn top_level_forms(code: &str) -> Vec<&str> {
+    let mut forms = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    let mut in_string = false;
+    let mut in_comment = false;
+    let mut chars = code.char_indices();
+    while let Some((i, c)) = chars.next() {
+        if in_comment {
+            in_comment = c != '\n';
+            continue;
+        }
+        if in_string {
+            match c {
+                '\\' => {
+                    chars.next();
+                }
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            ';' => in_comment = true,
+            '"' => in_string = true,
+            '(' => {
+                if depth == 0 {
+                    start = i;
+                }
+                depth += 1;
+            }
+            ')' if depth > 0 => {
+                depth -= 1;
+                if depth == 0 {
+                    forms.push(&code[start..=i]);
+                }
+            }
+            _ => {}
+        }
+    }
+    forms
+}
+
+/// The definition made by a top-level form, if it is one
+fn definition(form: &str) -> Option<Definition> {
+    let mut words = form[1..].split(|c: char| c.is_whitespace() || c == '(' || c == ')');
+    let kind = match words.next()? {
+        "defun" => "function",
+        "defmacro" => "macro",
+        "setq" => "variable",
+        _ => return None,
+    };
+    let name = words.find(|word| !word.is_empty())?;
+    Some(Definition {
+        kind,
+        name: name.to_string(),
+        source: form.to_string(),
+    })
+}
+
+type Job = Box<dyn FnOnce(&mut Session) + Send>;
+
+struct Handle {
+    id: u64,
+    jobs: mpsc::Sender<Job>,
+    last_used: Instant,
+}
+
+/// Live sessions of every user, each running on its own thread since the
+/// interpreter cannot move between threads
+#[derive(Clone)]
+pub struct Sessions {
+    dir: PathBuf,
+    live: Arc<Mutex<HashMap<serenity::UserId, Handle>>>,
+    next_id: Arc<AtomicU64>,
+    capacity: usize,
+}
+
+impl Sessions {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Sessions {
+            dir: dir.into(),
+            live: Arc::default(),
+            next_id: Arc::default(),
+            capacity: MAX_SESSIONS,
+        }
+    }
+
+    fn saved_path(&self, user: serenity::UserId) -> PathBuf {
+        self.dir.join(format!("{}.lisp", user))
+    }
+
+    /// Run `f` on the session of `user`, starting one if needed, and wait
+    /// for its result. A new session replays the saved definitions until
+    /// `cancel` is set, the token of the sandbox job calling this.
+    pub fn with_session<T, F>(
+        &self,
+        user: serenity::UserId,
+        cancel: &CancelToken,
+        f: F,
+    ) -> Result<T, String>
+    where
+        F: FnOnce(&mut Session) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let (reply, result) = mpsc::channel();
+        let job: Job = Box::new(move |session| {
+            let _ = reply.send(f(session));
+        });
+        {
+            let mut live = self.live.lock().unwrap();
+            let job = match live.get_mut(&user) {
+                Some(handle) => match handle.jobs.send(job) {
+                    Ok(()) => {
+                        handle.last_used = Instant::now();
+                        None
+                    }
+                    // The thread has gone away, start over
+                    Err(mpsc::SendError(job)) => Some(job),
+                },
+                None => Some(job),
+            };
+            if let Some(job) = job {
+                live.remove(&user);
+                // Dropping a handle lets its thread finish what it was sent and stop
+                while live.len() >= self.capacity {
+                    let Some(oldest) = live
+                        .iter()
+                        .min_by_key(|(_, handle)| handle.last_used)
+                        .map(|(&user, _)| user)
+                    else {
+                        break;
+                    };
+                    live.remove(&oldest);
+                }
+                let handle = self.spawn(user, cancel.clone())?;
+                handle
+                    .jobs
+                    .send(job)
+                    .map_err(|_| "The Lisp session failed to start".to_string())?;
+                live.insert(user, handle);
+            }
+        }
+        result.recv().map_err(|_| {
+            if cancel.is_cancelled() {
+                "Your saved definitions took too long to load, use /lisp reset to clear them"
+                    .to_string()
+            } else {
+                "The Lisp session stopped unexpectedly".to_string()
+            }
+        })
+    }
+
+    fn spawn(&self, user: serenity::UserId, cancel: CancelToken) -> Result<Handle, String> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (jobs, queue) = mpsc::channel::<Job>();
+        let live = self.live.clone();
+        let saved = std::fs::read_to_string(self.saved_path(user)).ok();
+        std::thread::Builder::new()
+            .name(format!("lisp-{}", user))
+            .stack_size(STACK_SIZE)
+            .spawn(move || {
+                let unregister = || {
+                    let mut sessions = live.lock().unwrap();
+                    if sessions.get(&user).is_some_and(|handle| handle.id == id) {
+                        sessions.remove(&user);
+                    }
+                };
+                let Ok(mut session) = Session::new() else {
+                    return;
+                };
+                // A half restored session would lose definitions on the next save
+                if saved.is_some_and(|saved| !session.restore(&saved, &cancel)) {
+                    unregister();
+                    return;
+                }
+                loop {
+                    match queue.recv_timeout(IDLE_TIMEOUT) {
+                        Ok(job) => job(&mut session),
+                        Err(mpsc::RecvTimeoutError::Timeout) => break,
+                        Err(mpsc::RecvTimeoutError::Disconnected) => return,
+                    }
+                }
+                // Idle: unregister, then finish anything sent before that
+                unregister();
+                while let Ok(job) = queue.try_recv() {
+                    job(&mut session);
+                }
+            })
+            .map_err(|e| format!("Could not start a Lisp session: {}", e))?;
+        Ok(Handle {
+            id,
+            jobs,
+            last_used: Instant::now(),
+        })
+    }
+
+    /// Write the definitions of `user`'s session to disk, returning how many
+    pub fn save(&self, user: serenity::UserId, cancel: &CancelToken) -> Result<usize, String> {
+        let (count, source) = self.with_session(user, cancel, |session| {
+            (session.definitions().len(), session.saved_source())
+        })?;
+        std::fs::create_dir_all(&self.dir)
+            .and_then(|()| std::fs::write(self.saved_path(user), source))
+            .map_err(|e| format!("Could not save the session: {}", e))?;
+        Ok(count)
+    }
+
+    /// Drop the session of `user` along with its saved definitions
+    pub fn reset(&self, user: serenity::UserId) -> Result<(), String> {
+        self.live.lock().unwrap().remove(&user);
+        match std::fs::remove_file(self.saved_path(user)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(format!("Could not delete the saved session: {}", e))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session() -> Session {
+        Session::new().unwrap()
+    }
+
+    fn eval(session: &mut Session, code: &str) -> String {
+        stacker::grow(STACK_SIZE, || session.eval(code, CancelToken::default()))
+            .value
+            .unwrap_or_else(|e| e.message)
+    }
+
+    #[test]
+    fn test_top_level_forms() {
+        let code = "(defun f () \"(\") ; (ignored\n 42 (setq x '(1 2))";
+        assert_eq!(
+            top_level_forms(code),
+            vec!["(defun f () \"(\")", "(setq x '(1 2))"]
+        );
+    }
+
+    #[test]
+    fn test_definition() {
+        assert_eq!(
+            definition("(defun square (x) (* x x))").map(|d| (d.kind, d.name)),
+            Some(("function", "square".to_string()))
+        );
+        assert_eq!(
+            definition("(setq\n  x 5)").map(|d| (d.kind, d.name)),
+            Some(("variable", "x".to_string()))
+        );
+        assert_eq!(definition("(+ 1 2)"), None);
+    }
+
+    #[test]
+    fn test_definitions_persist() {
+        let mut s = session();
+        assert_eq!(eval(&mut s, "(defun square (x) (* x x))"), "nil");
+        assert_eq!(eval(&mut s, "(setq y 4)"), "4");
+        assert_eq!(eval(&mut s, "(square y)"), "16");
+        // Redefining replaces the earlier definition
+        eval(&mut s, "(setq y 5)");
+        let names: Vec<&str> = s.definitions().iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(names, vec!["square", "y"]);
+        assert_eq!(s.saved_source(), "(defun square (x) (* x x))\n(setq y 5)");
+    }
+
+    #[test]
+    fn test_restore() {
+        let mut s = session();
+        let saved = "(defun twice (x) (* 2 x)) (setq z (undefined-fn)) (setq n 21)";
+        assert!(s.restore(saved, &CancelToken::default()));
+        assert_eq!(eval(&mut s, "(twice n)"), "42");
+        let names: Vec<&str> = s.definitions().iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(names, vec!["twice", "n"]);
+    }
+
+    #[test]
+    fn test_sessions_are_per_user() {
+        let dir = std::env::temp_dir().join(format!("bytie-lisp-{}", std::process::id()));
+        let sessions = Sessions::new(&dir);
+        let alice = serenity::UserId::new(1);
+        let bob = serenity::UserId::new(2);
+        let eval = |user, code: &'static str| {
+            sessions
+                .with_session(user, &CancelToken::default(), move |s| {
+                    s.eval(code, CancelToken::default()).value
+                })
+                .unwrap()
+        };
+        assert_eq!(eval(alice, "(setq x 1)"), Ok("1".to_string()));
+        assert_eq!(eval(alice, "x"), Ok("1".to_string()));
+        assert!(eval(bob, "x").is_err());
+
+        assert_eq!(sessions.save(alice, &CancelToken::default()), Ok(1));
+        sessions.live.lock().unwrap().clear();
+        assert_eq!(eval(alice, "x"), Ok("1".to_string()));
+
+        assert_eq!(sessions.reset(alice), Ok(()));
+        assert!(eval(alice, "x").is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_least_recently_used_session_is_dropped() {
+        let dir = std::env::temp_dir().join(format!("bytie-lisp-lru-{}", std::process::id()));
+        let sessions = Sessions {
+            capacity: 2,
+            ..Sessions::new(&dir)
+        };
+        let (alice, bob, carol) = (
+            serenity::UserId::new(1),
+            serenity::UserId::new(2),
+            serenity::UserId::new(3),
+        );
+        let eval = |user, code: &'static str| {
+            sessions
+                .with_session(user, &CancelToken::default(), move |s| {
+                    s.eval(code, CancelToken::default()).value
+                })
+                .unwrap()
+        };
+        assert_eq!(eval(alice, "(setq x 1)"), Ok("1".to_string()));
+        assert_eq!(eval(bob, "(setq x 2)"), Ok("2".to_string()));
+        assert_eq!(eval(alice, "x"), Ok("1".to_string()));
+        // Carol's session takes the place of Bob's, the least recently used
+        assert_eq!(eval(carol, "(setq x 3)"), Ok("3".to_string()));
+        assert_eq!(sessions.live.lock().unwrap().len(), 2);
+        assert_eq!(eval(alice, "x"), Ok("1".to_string()));
+        assert!(eval(bob, "x").is_err());
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn test_slow_restore_is_abandoned() {
+        let dir = std::env::temp_dir().join(format!("bytie-lisp-slow-{}", std::process::id()));
+        let sessions = Sessions::new(&dir);
+        let user = serenity::UserId::new(1);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(sessions.saved_path(user), "(setq x 1)").unwrap();
+
+        let cancel = CancelToken::default();
+        cancel.cancel();
+        let result = sessions.with_session(user, &cancel, |s| s.definitions().len());
+        assert!(result.unwrap_err().contains("took too long"));
+        // The session is loaded in full next time
+        let result =
+            sessions.with_session(user, &CancelToken::default(), |s| s.definitions().len());
+        assert_eq!(result, Ok(1));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,166 @@
+use num_complex::Complex;
+use tulisp::{ErrorKind, TulispContext, TulispObject};
+
+// Longest input `fft` accepts
+const MAX_FFT_LENGTH: usize = 1 << 16;
+
+/// Make the bot's other commands callable from Lisp:
+///
+/// - `(collatz N)`: the Collatz sequence of `N` as a list
+/// - `(fft LIST)`: transform of a list of numbers or `(RE . IM)` pairs,
+///   returned as `(RE . IM)` pairs
+/// - `(trend LIST)`: `(INTERCEPT SLOPE PREDICTION)` of the linear trend
+/// - `(roll "4d6kh3")`: the total of a dice roll
+///
+/// Lisp rolls use the thread's own generator, not the bot's dice RNG, so
+/// they never advance or reveal the sequence `/dice roll` draws from and
+/// are not recorded in the dice history.
+pub(super) fn install(ctx: &mut TulispContext) {
+    ctx.add_special_form("collatz", |ctx, args| {
+        let n = argument(ctx, args, "collatz")?.as_int()?;
+        let n = u64::try_from(n).map_err(|_| {
+            error(
+                ErrorKind::OutOfRange,
+                "collatz: expected a positive integer",
+            )
+        })?;
+        crate::collatz::collatz_sequence(n)
+            .map_err(|e| error(ErrorKind::OutOfRange, &format!("collatz: {}", e)))?
+            .into_iter()
+            .map(|x| {
+                i64::try_from(x)
+                    .map(TulispObject::from)
+                    .map_err(|_| error(ErrorKind::OutOfRange, "collatz: sequence overflowed"))
+            })
+            .collect()
+    });
+
+    ctx.add_special_form("fft", |ctx, args| {
+        let input = complexes(&argument(ctx, args, "fft")?)?;
+        if input.is_empty() || input.len() > MAX_FFT_LENGTH {
+            return Err(error(
+                ErrorKind::OutOfRange,
+                &format!(
+                    "fft: length must be between 1 and {}, got {}",
+                    MAX_FFT_LENGTH,
+                    input.len()
+                ),
+            ));
+        }
+        Ok(crate::fft::fft_calculator(input)
+            .into_iter()
+            .map(|c| TulispObject::cons(c.re.into(), c.im.into()))
+            .collect())
+    });
+
+    ctx.add_special_form("trend", |ctx, args| {
+        let values = reals(&argument(ctx, args, "trend")?)?;
+        if values.len() < 2 {
+            return Err(error(
+                ErrorKind::OutOfRange,
+                "trend: at least two values are needed",
+            ));
+        }
+        let (a, b) = crate::trend::linear_trend_eq(&values);
+        let next = crate::trend::predict_next(&values);
+        Ok([a, b, next].into_iter().map(TulispObject::from).collect())
+    });
+
+    ctx.add_special_form("roll", |ctx, args| {
+        let notation = argument(ctx, args, "roll")?.as_string()?;
+        let expression = crate::dice::notation::parse(&notation)
+            .map_err(|e| error(ErrorKind::OutOfRange, &format!("roll: {}", e)))?;
+        // Deliberately not `Data.dice_rng`, see above
+        Ok(expression.roll(&mut rand::thread_rng()).total.into())
+    });
+}
+
+/// The value of the only argument of `name`
+fn argument(
+    ctx: &mut TulispContext,
+    args: &TulispObject,
+    name: &str,
+) -> Result<TulispObject, tulisp::Error> {
+    let args = ctx.eval_each(args)?;
+    let mut values = args.base_iter();
+    match (values.next(), values.next()) {
+        (Some(value), None) => Ok(value),
+        _ => Err(error(
+            ErrorKind::MissingArgument,
+            &format!("{}: expected exactly one argument", name),
+        )),
+    }
+}
+
+fn reals(list: &TulispObject) -> Result<Vec<f64>, tulisp::Error> {
+    list.base_iter().map(|x| x.try_float()).collect()
+}
+
+/// Numbers of a list, where complex ones are written `(RE . IM)`
+fn complexes(list: &TulispObject) -> Result<Vec<Complex<f64>>, tulisp::Error> {
+    list.base_iter()
+        .map(|x| {
+            if x.consp() {
+                Ok(Complex::new(x.car()?.try_float()?, x.cdr()?.try_float()?))
+            } else {
+                Ok(Complex::new(x.try_float()?, 0.0))
+            }
+        })
+        .collect()
+}
+
+fn error(kind: ErrorKind, message: &str) -> tulisp::Error {
+    tulisp::Error::new(kind, message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(code: &str) -> Result<String, String> {
+        let mut ctx = TulispContext::new();
+        install(&mut ctx);
+        ctx.eval_string(code)
+            .map(|value| value.to_string())
+            .map_err(|e| e.desc())
+    }
+
+    #[test]
+    fn test_collatz() {
+        assert_eq!(eval("(collatz 3)"), Ok("(3 10 5 16 8 4 2 1)".to_string()));
+        assert_eq!(eval("(length (collatz 27))"), Ok("112".to_string()));
+        assert!(eval("(collatz -1)").is_err());
+        assert!(eval("(collatz 9223372036854775807)").is_err());
+        assert!(eval("(collatz)").is_err());
+    }
+
+    #[test]
+    fn test_fft() {
+        assert_eq!(
+            eval("(fft '(1 2 14 9))"),
+            Ok("((26 . 0) (-13 . 7) (4 . 0) (-13 . -7))".to_string())
+        );
+        assert_eq!(
+            eval("(fft '((0 . 1) (0 . 1)))"),
+            Ok("((0 . 2) (0 . 0))".to_string())
+        );
+        assert_eq!(eval("(car (fft '(1 1 1)))"), Ok("(3 . 0)".to_string()));
+        assert_eq!(eval("(length (fft '(1 2 3 4 5)))"), Ok("5".to_string()));
+        assert!(eval("(fft nil)").unwrap_err().contains("length"));
+        assert!(eval("(fft '(1 \"a\"))").is_err());
+    }
+
+    #[test]
+    fn test_trend() {
+        assert_eq!(eval("(trend '(1 3 5 7))"), Ok("(1 2 9)".to_string()));
+        assert!(eval("(trend '(1))").is_err());
+    }
+
+    #[test]
+    fn test_roll() {
+        assert_eq!(eval("(roll \"2d1+3\")"), Ok("5".to_string()));
+        assert_eq!(eval("(roll \"3d1kh2\")"), Ok("2".to_string()));
+        assert!(eval("(roll \"d0\")").unwrap_err().contains("sides"));
+        assert!(eval("(roll 6)").is_err());
+    }
+}
@@ -6,16 +6,22 @@ mod brainfuck;
 mod bytie;
 mod collatz;
 mod dice;
+mod esolang;
 mod fft;
 mod imagine;
+mod initiative;
 mod latex;
 mod lisp;
 mod pgsays;
 mod ping;
+mod render;
+mod sandbox;
+mod sequence;
 mod stock;
+mod store;
+mod trend;
 mod usdtry;
 mod xkcd;
-mod trend;
 
 #[tokio::main]
 async fn main() {
@@ -30,21 +36,35 @@ async fn main() {
                 stock::stock(),
                 xkcd::xkcd(),
                 lisp::lisp(),
+                lisp::macros::macros(),
                 imagine::imagine(),
                 dice::dice(),
+                initiative::initiative(),
                 collatz::collatz(),
+                sequence::sequence(),
                 latex::latex(),
                 fft::fft(),
                 brainfuck::brainfuck(),
+                esolang::esolang(),
                 pgsays::pgsays(),
-                trend::trend()
+                trend::trend(),
             ], // Add the commands to the framework
             ..Default::default()
         })
         .setup(|ctx, _ready, framework| {
             Box::pin(async move {
                 poise::builtins::register_globally(ctx, &framework.options().commands).await?;
-                Ok(Data {})
+                Ok(Data {
+                    sandbox: sandbox::Sandbox::default(),
+                    challenges: brainfuck::golf::Challenges::load(
+                        brainfuck::golf::CHALLENGES_FILE,
+                    )?,
+                    dice_rng: dice::fair::DiceRng::default(),
+                    dice_history: dice::history::History::load(dice::history::HISTORY_FILE)?,
+                    initiative: initiative::Trackers::default(),
+                    lisp_sessions: lisp::session::Sessions::new(lisp::session::SESSIONS_DIR),
+                    macros: lisp::macros::Macros::load(lisp::macros::MACROS_FILE)?,
+                })
             })
         })
         .build();
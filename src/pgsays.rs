@@ -3,7 +3,6 @@ use once_cell::sync::Lazy;
 use poise::serenity_prelude as serenity;
 use rand::prelude::*;
 use regex::Regex;
-use reqwest;
 use scraper::{Html, Selector};
 use thiserror::Error;
 
@@ -39,7 +38,8 @@ async fn find_random_essay() -> Result<PGEssay, EssayError> {
     let essays_response = reqwest::get(&essays_url).await?.text().await?;
     let html_document = Html::parse_document(&essays_response);
     let selector = Selector::parse("td a").map_err(EssayError::ParseError)?;
-    let essay_links: Vec<_> = html_document.select(&selector)
+    let essay_links: Vec<_> = html_document
+        .select(&selector)
         .filter_map(|element| {
             let link = element.attr("href")?;
             let title = element.text().next()?.trim().to_string();
@@ -47,8 +47,13 @@ async fn find_random_essay() -> Result<PGEssay, EssayError> {
         })
         .collect();
 
-    essay_links.choose(&mut thread_rng())
-        .map(|(link, title)| PGEssay { link: format!("{}/{}", BASE_URL, link.clone()), title: title.clone(), content: None })
+    essay_links
+        .choose(&mut thread_rng())
+        .map(|(link, title)| PGEssay {
+            link: format!("{}/{}", BASE_URL, link.clone()),
+            title: title.clone(),
+            content: None,
+        })
         .ok_or(EssayError::NoEssaysFound)
 }
 
@@ -71,11 +76,13 @@ fn get_random_sentence(essay: &PGEssay) -> Result<String, EssayError> {
         .flat_map(|element| element.text())
         .collect();
 
-    let sentences: Vec<&str> = SENTENCE_REGEX_COMPILED.find_iter(&body_text)
+    let sentences: Vec<&str> = SENTENCE_REGEX_COMPILED
+        .find_iter(&body_text)
         .map(|m| m.as_str())
         .collect();
 
-    sentences.choose(&mut thread_rng())
+    sentences
+        .choose(&mut thread_rng())
         .map(|&sentence| sentence.replace('\n', " "))
         .ok_or(EssayError::NoSentencesFound)
 }
@@ -87,16 +94,21 @@ pub async fn pgsays(
 ) -> Result<(), Error> {
     ctx.defer().await?;
 
-    let random_essay = find_random_essay().await.map_err(|e| Error::from(e.to_string()))?;
-    let random_essay_content = get_essay_content(&random_essay).await.map_err(|e| Error::from(e.to_string()))?;
-    let random_sentence = get_random_sentence(&random_essay_content).map_err(|e| Error::from(e.to_string()))?;
+    let random_essay = find_random_essay()
+        .await
+        .map_err(|e| Error::from(e.to_string()))?;
+    let random_essay_content = get_essay_content(&random_essay)
+        .await
+        .map_err(|e| Error::from(e.to_string()))?;
+    let random_sentence =
+        get_random_sentence(&random_essay_content).map_err(|e| Error::from(e.to_string()))?;
 
     let reply = poise::CreateReply::default().content(String::new()).embed(
         serenity::CreateEmbed::new()
             .title(random_essay.title)
             .url(random_essay.link)
             .description(&random_sentence)
-            .author(serenity::CreateEmbedAuthor::new("Paul Graham").url("https://paulgraham.com"))
+            .author(serenity::CreateEmbedAuthor::new("Paul Graham").url("https://paulgraham.com")),
     );
 
     ctx.send(reply).await?;
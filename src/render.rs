@@ -0,0 +1,385 @@
+use tiny_skia::{Color, ColorU8, FillRule, Paint, PathBuilder, Pixmap, Rect, Stroke, Transform};
+
+// Glyphs are drawn on a grid of this many cells
+pub const GLYPH_WIDTH: u32 = 5;
+pub const GLYPH_HEIGHT: u32 = 7;
+
+const CHART_WIDTH: u32 = 800;
+const CHART_HEIGHT: u32 = 400;
+// Axis labels are drawn with cells of this many pixels
+const LABEL_SCALE: f32 = 2.0;
+const CHART_MARGIN: f32 = 16.0;
+
+/// Rows of a 5×7 glyph, most significant of the low five bits on the left
+fn glyph(c: char) -> Option<[u8; 7]> {
+    let rows = match c {
+        '0' => [0x0e, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0e],
+        '1' => [0x04, 0x0c, 0x04, 0x04, 0x04, 0x04, 0x0e],
+        '2' => [0x0e, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1f],
+        '3' => [0x1f, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0e],
+        '4' => [0x02, 0x06, 0x0a, 0x12, 0x1f, 0x02, 0x02],
+        '5' => [0x1f, 0x10, 0x1e, 0x01, 0x01, 0x11, 0x0e],
+        '6' => [0x06, 0x08, 0x10, 0x1e, 0x11, 0x11, 0x0e],
+        '7' => [0x1f, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0e, 0x11, 0x11, 0x0e, 0x11, 0x11, 0x0e],
+        '9' => [0x0e, 0x11, 0x11, 0x0f, 0x01, 0x02, 0x0c],
+        '-' => [0x00, 0x00, 0x00, 0x1f, 0x00, 0x00, 0x00],
+        '+' => [0x00, 0x04, 0x04, 0x1f, 0x04, 0x04, 0x00],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x0c],
+        'e' => [0x00, 0x00, 0x0e, 0x11, 0x1f, 0x10, 0x0e],
+        'H' => [0x11, 0x11, 0x11, 0x1f, 0x11, 0x11, 0x11],
+        'z' => [0x00, 0x00, 0x1f, 0x02, 0x04, 0x08, 0x1f],
+        's' => [0x00, 0x00, 0x0f, 0x10, 0x0e, 0x01, 0x1e],
+        ' ' => [0; 7],
+        _ => return None,
+    };
+    Some(rows)
+}
+
+pub fn color(r: u8, g: u8, b: u8) -> Color {
+    Color::from_rgba8(r, g, b, 255)
+}
+
+pub fn paint(color: Color) -> Paint<'static> {
+    let mut paint = Paint::default();
+    paint.set_color(color);
+    paint
+}
+
+/// Width of `text` drawn with cells of `scale` pixels, one blank column
+/// between characters
+pub fn text_width(text: &str, scale: f32) -> f32 {
+    let count = text.chars().count() as f32;
+    (count * (GLYPH_WIDTH + 1) as f32 - 1.0).max(0.0) * scale
+}
+
+/// Draw `text` centred on (`x`, `y`); characters without a glyph are drawn
+/// as boxes
+pub fn draw_text(pixmap: &mut Pixmap, text: &str, x: f32, y: f32, scale: f32, color: Color) {
+    let left = x - text_width(text, scale) / 2.0;
+    let top = y - GLYPH_HEIGHT as f32 * scale / 2.0;
+    let mut path = PathBuilder::new();
+    for (i, c) in text.chars().enumerate() {
+        let rows = glyph(c).unwrap_or([0x1f, 0x11, 0x11, 0x11, 0x11, 0x11, 0x1f]);
+        let origin = left + i as f32 * (GLYPH_WIDTH + 1) as f32 * scale;
+        for (row, bits) in rows.iter().enumerate() {
+            for column in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - column)) != 0 {
+                    let cell = Rect::from_xywh(
+                        origin + column as f32 * scale,
+                        top + row as f32 * scale,
+                        scale,
+                        scale,
+                    );
+                    if let Some(cell) = cell {
+                        path.push_rect(cell);
+                    }
+                }
+            }
+        }
+    }
+    if let Some(path) = path.finish() {
+        pixmap.fill_path(
+            &path,
+            &paint(color),
+            FillRule::Winding,
+            Transform::identity(),
+            None,
+        );
+    }
+}
+
+fn axis_label(value: f64) -> String {
+    if value.abs() >= 1e9 {
+        format!("{:.2e}", value)
+    } else if value.fract() == 0.0 {
+        format!("{:.0}", value)
+    } else {
+        format!("{:.2}", value)
+    }
+}
+
+/// PNG line chart of `values` in order, with the first and last index and
+/// the lowest and highest value marked on the axes, and a dot on the point
+/// at `highlight`
+pub fn line_chart(values: &[f64], highlight: Option<usize>) -> Result<Vec<u8>, String> {
+    let last_label = values.len().saturating_sub(1).to_string();
+    stacked_chart(&[values], highlight, &last_label)
+}
+
+/// PNG of line charts stacked on top of each other, all as long as the
+/// first, sharing an x axis that ends at `last_label`. Each chart is drawn
+/// like `line_chart`'s.
+pub fn stacked_chart(
+    panels: &[&[f64]],
+    highlight: Option<usize>,
+    last_label: &str,
+) -> Result<Vec<u8>, String> {
+    let length = panels.first().map_or(0, |values| values.len());
+    if length == 0
+        || panels
+            .iter()
+            .any(|values| values.len() != length || values.iter().any(|v| !v.is_finite()))
+    {
+        return Err("There is nothing to chart".to_string());
+    }
+    let ranges: Vec<(f64, f64)> = panels
+        .iter()
+        .map(|values| {
+            let low = values.iter().copied().fold(f64::INFINITY, f64::min);
+            let high = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+            (low, high)
+        })
+        .collect();
+    let label_height = GLYPH_HEIGHT as f32 * LABEL_SCALE;
+    let widest = ranges
+        .iter()
+        .flat_map(|&(low, high)| [low, high])
+        .map(|v| text_width(&axis_label(v), LABEL_SCALE))
+        .fold(0.0, f32::max);
+
+    let (width, height) = (CHART_WIDTH as f32, CHART_HEIGHT as f32);
+    let left = CHART_MARGIN * 1.5 + widest;
+    let right = width - CHART_MARGIN - text_width(last_label, LABEL_SCALE) / 2.0;
+    let x = |i: usize| left + (right - left) * i as f32 / (length - 1).max(1) as f32;
+
+    let mut pixmap = Pixmap::new(CHART_WIDTH, CHART_HEIGHT * panels.len() as u32)
+        .ok_or("Could not create the image")?;
+    pixmap.fill(color(0xff, 0xff, 0xff));
+    let ink = color(0x42, 0x42, 0x42);
+    let line = color(0x19, 0x76, 0xd2);
+    let stroke = Stroke {
+        width: 2.0,
+        ..Stroke::default()
+    };
+    for (panel, (values, &(low, high))) in panels.iter().zip(&ranges).enumerate() {
+        let offset = height * panel as f32;
+        let top = offset + CHART_MARGIN + label_height / 2.0;
+        let bottom = offset + height - CHART_MARGIN * 1.5 - label_height;
+        let y = |v: f64| {
+            let span = if high > low { high - low } else { 1.0 };
+            bottom - (bottom - top) * ((v - low) / span) as f32
+        };
+
+        let mut axes = PathBuilder::new();
+        axes.move_to(left, top);
+        axes.line_to(left, bottom);
+        axes.line_to(right, bottom);
+        if let Some(axes) = axes.finish() {
+            pixmap.stroke_path(&axes, &paint(ink), &stroke, Transform::identity(), None);
+        }
+        let label_x =
+            |label: &str| left - CHART_MARGIN / 2.0 - text_width(label, LABEL_SCALE) / 2.0;
+        let (low_label, high_label) = (axis_label(low), axis_label(high));
+        draw_text(
+            &mut pixmap,
+            &high_label,
+            label_x(&high_label),
+            y(high),
+            LABEL_SCALE,
+            ink,
+        );
+        if high > low {
+            draw_text(
+                &mut pixmap,
+                &low_label,
+                label_x(&low_label),
+                y(low),
+                LABEL_SCALE,
+                ink,
+            );
+        }
+        let index_y = bottom + CHART_MARGIN / 2.0 + label_height / 2.0;
+        draw_text(&mut pixmap, "0", left, index_y, LABEL_SCALE, ink);
+        if length > 1 {
+            draw_text(&mut pixmap, last_label, right, index_y, LABEL_SCALE, ink);
+        }
+
+        let mut path = PathBuilder::new();
+        path.move_to(x(0), y(values[0]));
+        for (i, &value) in values.iter().enumerate().skip(1) {
+            path.line_to(x(i), y(value));
+        }
+        match path.finish() {
+            Some(path) => {
+                pixmap.stroke_path(&path, &paint(line), &stroke, Transform::identity(), None);
+            }
+            // A single value has no line, so it is drawn as a dot
+            None => {
+                if let Some(dot) = PathBuilder::from_circle(x(0), y(values[0]), 3.0) {
+                    pixmap.fill_path(
+                        &dot,
+                        &paint(line),
+                        FillRule::Winding,
+                        Transform::identity(),
+                        None,
+                    );
+                }
+            }
+        }
+        if let Some(i) = highlight.filter(|&i| i < length) {
+            if let Some(dot) = PathBuilder::from_circle(x(i), y(values[i]), 5.0) {
+                let red = paint(color(0xd3, 0x2f, 0x2f));
+                pixmap.fill_path(&dot, &red, FillRule::Winding, Transform::identity(), None);
+            }
+        }
+    }
+    encode_png(&pixmap)
+}
+
+/// Colour of a heat map cell of `value`, from black through blue, red and
+/// yellow to white as it goes from 0 to 1
+fn heat(value: f64) -> ColorU8 {
+    const STOPS: [(f64, [f64; 3]); 5] = [
+        (0.0, [0.0, 0.0, 0.0]),
+        (0.3, [40.0, 30.0, 160.0]),
+        (0.6, [210.0, 40.0, 60.0]),
+        (0.85, [250.0, 200.0, 40.0]),
+        (1.0, [255.0, 255.0, 255.0]),
+    ];
+    let value = value.clamp(0.0, 1.0);
+    let upper = STOPS
+        .iter()
+        .position(|&(at, _)| at >= value)
+        .unwrap_or(4)
+        .max(1);
+    let ((from, low), (to, high)) = (STOPS[upper - 1], STOPS[upper]);
+    let t = (value - from) / (to - from);
+    let channel = |i: usize| (low[i] + (high[i] - low[i]) * t).round() as u8;
+    ColorU8::from_rgba(channel(0), channel(1), channel(2), 255)
+}
+
+/// PNG heat map of `columns`, each a column of values between 0 and 1 from
+/// the bottom up, with the x axis ending at `last_label` and the y axis at
+/// `top_label`
+pub fn heat_map(
+    columns: &[Vec<f64>],
+    top_label: &str,
+    last_label: &str,
+) -> Result<Vec<u8>, String> {
+    let rows = columns.first().map_or(0, |column| column.len());
+    if rows == 0 || columns.iter().any(|column| column.len() != rows) {
+        return Err("There is nothing to chart".to_string());
+    }
+    let label_height = GLYPH_HEIGHT as f32 * LABEL_SCALE;
+    let (width, height) = (CHART_WIDTH as f32, CHART_HEIGHT as f32);
+    let left = CHART_MARGIN * 1.5 + text_width(top_label, LABEL_SCALE);
+    let right = width - CHART_MARGIN - text_width(last_label, LABEL_SCALE) / 2.0;
+    let top = CHART_MARGIN + label_height / 2.0;
+    let bottom = height - CHART_MARGIN * 1.5 - label_height;
+
+    let mut pixmap = Pixmap::new(CHART_WIDTH, CHART_HEIGHT).ok_or("Could not create the image")?;
+    pixmap.fill(color(0xff, 0xff, 0xff));
+    let (x_range, y_range) = (left as u32..right as u32, top as u32..bottom as u32);
+    let pixels = pixmap.pixels_mut();
+    for py in y_range.clone() {
+        let row = ((bottom - py as f32 - 0.5) / (bottom - top) * rows as f32) as usize;
+        for px in x_range.clone() {
+            let column =
+                ((px as f32 + 0.5 - left) / (right - left) * columns.len() as f32) as usize;
+            let value = columns[column.min(columns.len() - 1)][row.min(rows - 1)];
+            pixels[(py * CHART_WIDTH + px) as usize] = heat(value).premultiply();
+        }
+    }
+
+    let ink = color(0x42, 0x42, 0x42);
+    let mut axes = PathBuilder::new();
+    axes.move_to(left, top);
+    axes.line_to(left, bottom);
+    axes.line_to(right, bottom);
+    if let Some(axes) = axes.finish() {
+        let stroke = Stroke {
+            width: 2.0,
+            ..Stroke::default()
+        };
+        pixmap.stroke_path(&axes, &paint(ink), &stroke, Transform::identity(), None);
+    }
+    let label_x = |label: &str| left - CHART_MARGIN / 2.0 - text_width(label, LABEL_SCALE) / 2.0;
+    draw_text(
+        &mut pixmap,
+        top_label,
+        label_x(top_label),
+        top,
+        LABEL_SCALE,
+        ink,
+    );
+    draw_text(&mut pixmap, "0", label_x("0"), bottom, LABEL_SCALE, ink);
+    let index_y = bottom + CHART_MARGIN / 2.0 + label_height / 2.0;
+    draw_text(&mut pixmap, last_label, right, index_y, LABEL_SCALE, ink);
+    encode_png(&pixmap)
+}
+
+pub fn encode_png(pixmap: &Pixmap) -> Result<Vec<u8>, String> {
+    pixmap
+        .encode_png()
+        .map_err(|e| format!("Could not encode the image: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_width() {
+        assert_eq!(text_width("", 2.0), 0.0);
+        assert_eq!(text_width("1", 2.0), 10.0);
+        assert_eq!(text_width("20", 2.0), 22.0);
+    }
+
+    #[test]
+    fn test_draw_text() {
+        let mut pixmap = Pixmap::new(20, 20).unwrap();
+        draw_text(&mut pixmap, "1", 10.0, 10.0, 2.0, color(0, 0, 0));
+        let opaque = |x: u32, y: u32| pixmap.pixel(x, y).unwrap().alpha() == 255;
+        // The stem of the 1 runs down the middle
+        assert!(opaque(10, 10));
+        assert!(!opaque(1, 10));
+
+        let png = encode_png(&pixmap).unwrap();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    }
+
+    #[test]
+    fn test_line_chart() {
+        for values in [vec![3.0, 10.0, 5.0, 16.0, 8.0], vec![2.5; 4], vec![1.0]] {
+            let png = line_chart(&values, Some(0)).unwrap();
+            let image = Pixmap::decode_png(&png).unwrap();
+            assert_eq!((image.width(), image.height()), (CHART_WIDTH, CHART_HEIGHT));
+        }
+        assert!(line_chart(&[], None).is_err());
+        assert!(line_chart(&[1.0, f64::NAN], None).is_err());
+        assert_eq!(axis_label(16.0), "16");
+        assert_eq!(axis_label(21933016.0), "21933016");
+        assert_eq!(axis_label(1.5e300), "1.50e300");
+        assert_eq!(axis_label(-0.126), "-0.13");
+    }
+
+    #[test]
+    fn test_stacked_chart() {
+        let (magnitudes, phases) = ([4.0, 0.5, 2.0], [0.0, -3.0, 1.5]);
+        let png = stacked_chart(&[&magnitudes, &phases], Some(2), "300 Hz").unwrap();
+        let image = Pixmap::decode_png(&png).unwrap();
+        assert_eq!(
+            (image.width(), image.height()),
+            (CHART_WIDTH, 2 * CHART_HEIGHT)
+        );
+        assert!(stacked_chart(&[&magnitudes, &[1.0]], None, "2").is_err());
+        assert!(stacked_chart(&[], None, "0").is_err());
+    }
+
+    #[test]
+    fn test_heat_map() {
+        let columns = vec![vec![0.0, 0.5, 1.0]; 4];
+        let png = heat_map(&columns, "4000 Hz", "2.5 s").unwrap();
+        let image = Pixmap::decode_png(&png).unwrap();
+        assert_eq!((image.width(), image.height()), (CHART_WIDTH, CHART_HEIGHT));
+        assert!(heat_map(&[], "1", "1").is_err());
+        assert!(heat_map(&[vec![0.0], vec![]], "1", "1").is_err());
+
+        let rgb = |c: ColorU8| (c.red(), c.green(), c.blue());
+        assert_eq!(rgb(heat(0.0)), (0, 0, 0));
+        assert_eq!(rgb(heat(0.3)), (40, 30, 160));
+        assert_eq!(rgb(heat(1.0)), (255, 255, 255));
+        assert_eq!(rgb(heat(7.0)), (255, 255, 255));
+    }
+}
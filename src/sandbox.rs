@@ -0,0 +1,265 @@
+use poise::serenity_prelude as serenity;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::Semaphore;
+
+// Jobs running at the same time, each on its own blocking thread
+const WORKERS: usize = 4;
+// Jobs running or waiting for a worker before new ones are turned away
+const MAX_QUEUE_DEPTH: usize = 16;
+const MAX_JOBS_PER_USER: usize = 2;
+const TIMEOUT: Duration = Duration::from_secs(5);
+// How long a cancelled job gets to notice before it is abandoned
+const CANCEL_GRACE: Duration = Duration::from_secs(1);
+
+#[derive(Error, Debug, PartialEq)]
+pub enum SandboxError {
+    #[error("You already have {0} programs running, please wait for them to finish")]
+    UserBusy(usize),
+    #[error("Too many programs are waiting to run, please try again later")]
+    QueueFull,
+    #[error("Execution time limit exceeded")]
+    Timeout,
+    #[error("The program crashed the interpreter")]
+    Panicked,
+}
+
+/// Set when a job runs out of time; long-running jobs poll it and stop
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    pub workers: usize,
+    pub max_queue_depth: usize,
+    pub max_jobs_per_user: usize,
+    pub timeout: Duration,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            workers: WORKERS,
+            max_queue_depth: MAX_QUEUE_DEPTH,
+            max_jobs_per_user: MAX_JOBS_PER_USER,
+            timeout: TIMEOUT,
+        }
+    }
+}
+
+/// Runs user code off the async runtime, on a bounded set of blocking threads
+pub struct Sandbox {
+    limits: Limits,
+    workers: Arc<Semaphore>,
+    jobs: Arc<Mutex<Jobs>>,
+}
+
+#[derive(Default)]
+struct Jobs {
+    total: usize,
+    per_user: HashMap<serenity::UserId, usize>,
+}
+
+impl Default for Sandbox {
+    fn default() -> Self {
+        Sandbox::new(Limits::default())
+    }
+}
+
+impl Sandbox {
+    pub fn new(limits: Limits) -> Self {
+        Sandbox {
+            limits,
+            workers: Arc::new(Semaphore::new(limits.workers)),
+            jobs: Arc::default(),
+        }
+    }
+
+    /// Reserve a place in the queue for a job of `user`
+    pub fn admit(&self, user: serenity::UserId) -> Result<Ticket, SandboxError> {
+        let mut jobs = self.jobs.lock().unwrap();
+        let running = jobs.per_user.get(&user).copied().unwrap_or(0);
+        if running >= self.limits.max_jobs_per_user {
+            return Err(SandboxError::UserBusy(running));
+        }
+        if jobs.total >= self.limits.max_queue_depth {
+            return Err(SandboxError::QueueFull);
+        }
+        jobs.total += 1;
+        *jobs.per_user.entry(user).or_default() += 1;
+        Ok(Ticket {
+            user,
+            timeout: self.limits.timeout,
+            workers: self.workers.clone(),
+            jobs: self.jobs.clone(),
+        })
+    }
+
+    /// Threads a job may start for itself, so that every worker doing so at
+    /// once still needs no more threads than there are CPUs
+    pub fn threads_per_job(&self) -> usize {
+        let cpus = std::thread::available_parallelism().map_or(1, |n| n.get());
+        (cpus / self.limits.workers).max(1)
+    }
+
+    /// Admit and run `job` in one go
+    pub async fn run<T, F>(&self, user: serenity::UserId, job: F) -> Result<T, SandboxError>
+    where
+        F: FnOnce(CancelToken) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        self.admit(user)?.run(job).await
+    }
+}
+
+/// A job's place in the queue, given back when the job finishes or the ticket is dropped
+pub struct Ticket {
+    user: serenity::UserId,
+    timeout: Duration,
+    workers: Arc<Semaphore>,
+    jobs: Arc<Mutex<Jobs>>,
+}
+
+impl Drop for Ticket {
+    fn drop(&mut self) {
+        let mut jobs = self.jobs.lock().unwrap();
+        jobs.total -= 1;
+        if let Some(count) = jobs.per_user.get_mut(&self.user) {
+            *count -= 1;
+            if *count == 0 {
+                jobs.per_user.remove(&self.user);
+            }
+        }
+    }
+}
+
+impl Ticket {
+    /// Wait for a free worker and run `job` on it.
+    ///
+    /// When the job runs past the timeout its token is cancelled. A job that
+    /// stops within the grace period returns normally, so it can report its
+    /// own partial results; otherwise it is abandoned and keeps its worker
+    /// until it ends.
+    pub async fn run<T, F>(self, job: F) -> Result<T, SandboxError>
+    where
+        F: FnOnce(CancelToken) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let permit = self
+            .workers
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("worker semaphore is never closed");
+        let cancel = CancelToken::default();
+        let token = cancel.clone();
+        let timeout = self.timeout;
+        let mut handle = tokio::task::spawn_blocking(move || {
+            // Both are released only once the job has really stopped
+            let _permit = permit;
+            let _ticket = self;
+            job(token)
+        });
+
+        let result = match tokio::time::timeout(timeout, &mut handle).await {
+            Ok(result) => result,
+            Err(_) => {
+                cancel.cancel();
+                tokio::time::timeout(CANCEL_GRACE, &mut handle)
+                    .await
+                    .map_err(|_| SandboxError::Timeout)?
+            }
+        };
+        result.map_err(|_| SandboxError::Panicked)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits() -> Limits {
+        Limits {
+            workers: 1,
+            max_queue_depth: 2,
+            max_jobs_per_user: 1,
+            timeout: Duration::from_millis(50),
+        }
+    }
+
+    #[test]
+    fn test_threads_per_job() {
+        let cpus = std::thread::available_parallelism().map_or(1, |n| n.get());
+        assert_eq!(Sandbox::new(limits()).threads_per_job(), cpus);
+        let crowded = Limits {
+            workers: 4096,
+            ..limits()
+        };
+        assert_eq!(Sandbox::new(crowded).threads_per_job(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_runs_job() {
+        let sandbox = Sandbox::new(limits());
+        let user = serenity::UserId::new(1);
+        assert_eq!(sandbox.run(user, |_| 6 * 7).await, Ok(42));
+        // The ticket was given back
+        assert!(sandbox.admit(user).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_quotas() {
+        let sandbox = Sandbox::new(limits());
+        let first = sandbox.admit(serenity::UserId::new(1)).unwrap();
+        assert_eq!(
+            sandbox.admit(serenity::UserId::new(1)).err(),
+            Some(SandboxError::UserBusy(1))
+        );
+        let _second = sandbox.admit(serenity::UserId::new(2)).unwrap();
+        assert_eq!(
+            sandbox.admit(serenity::UserId::new(3)).err(),
+            Some(SandboxError::QueueFull)
+        );
+        drop(first);
+        assert!(sandbox.admit(serenity::UserId::new(3)).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_cooperative_cancel() {
+        let sandbox = Sandbox::new(limits());
+        let result = sandbox
+            .run(serenity::UserId::new(1), |cancel| {
+                while !cancel.is_cancelled() {
+                    std::thread::sleep(Duration::from_millis(1));
+                }
+                "stopped"
+            })
+            .await;
+        assert_eq!(result, Ok("stopped"));
+    }
+
+    #[tokio::test]
+    async fn test_abandons_stuck_job() {
+        let sandbox = Sandbox::new(limits());
+        let user = serenity::UserId::new(1);
+        let result = sandbox
+            .run(user, |_| std::thread::sleep(Duration::from_millis(1500)))
+            .await;
+        assert_eq!(result, Err(SandboxError::Timeout));
+        // The stuck job still holds its place
+        assert_eq!(sandbox.admit(user).err(), Some(SandboxError::UserBusy(1)));
+    }
+}
@@ -0,0 +1,473 @@
+use crate::context::{Context, Error};
+use crate::render;
+use generators::{Ending, Run};
+use num_bigint::BigInt;
+use num_traits::ToPrimitive;
+use poise::serenity_prelude as serenity;
+use std::fmt::Display;
+
+mod generators;
+
+const MESSAGE_LIMIT: usize = 2000;
+// Sequences that would make a larger file are not attached
+const MAX_ATTACHMENT_SIZE: usize = 8 * 1024 * 1024;
+// Longer numbers are shortened in titles and fields
+const MAX_SHOWN_DIGITS: usize = 40;
+// Longer cycles are described without listing their terms
+const MAX_SHOWN_CYCLE: usize = 8;
+const MAX_TERMS: u32 = 10_000;
+const MAX_SEED_DIGITS: usize = 100;
+
+/// `n` in full, or its first and last digits if it is long
+pub(crate) fn abbreviate(n: &impl Display) -> String {
+    let digits = n.to_string();
+    if digits.len() <= MAX_SHOWN_DIGITS {
+        return digits;
+    }
+    let half = MAX_SHOWN_DIGITS / 2;
+    format!(
+        "{}…{} ({} digits)",
+        &digits[..half],
+        &digits[digits.len() - half..],
+        digits.len()
+    )
+}
+
+/// Summary of `terms` with `fields` and their peak, a chart of them named
+/// `{stem}.png` and the terms themselves, attached as `{stem}.txt` when they
+/// do not fit in the message
+pub(crate) fn sequence_reply<T: Display + ToPrimitive + Ord>(
+    title: &str,
+    stem: &str,
+    terms: &[T],
+    fields: Vec<(&str, String)>,
+    summary_only: bool,
+) -> poise::CreateReply {
+    let Some((peak_index, peak)) = terms
+        .iter()
+        .enumerate()
+        .max_by(|(i, a), (j, b)| a.cmp(b).then(j.cmp(i)))
+    else {
+        return poise::CreateReply::default().content("The sequence is empty");
+    };
+    let mut embed = serenity::CreateEmbed::new().title(title);
+    for (name, value) in fields {
+        embed = embed.field(name, value, true);
+    }
+    embed = embed.field(
+        "Peak",
+        format!("{} at index {}", abbreviate(peak), peak_index),
+        true,
+    );
+    if summary_only {
+        return poise::CreateReply::default().embed(embed);
+    }
+
+    let mut reply = poise::CreateReply::default();
+    let numbers: Vec<String> = terms.iter().map(|x| x.to_string()).collect();
+    let values: Option<Vec<f64>> = terms
+        .iter()
+        .map(|x| x.to_f64().filter(|v| v.is_finite()))
+        .collect();
+    let chart = match values {
+        Some(values) => render::line_chart(&values, Some(peak_index)),
+        None => {
+            embed = embed.field("Chart", "Number of digits of each term", false);
+            let digits: Vec<f64> = numbers
+                .iter()
+                .map(|x| x.trim_start_matches('-').len() as f64)
+                .collect();
+            render::line_chart(&digits, Some(peak_index))
+        }
+    };
+    match chart {
+        Ok(png) => {
+            let image = format!("{}.png", stem);
+            reply = reply.attachment(serenity::CreateAttachment::bytes(png, image.as_str()));
+            embed = embed.attachment(image);
+        }
+        Err(e) => embed = embed.field("Chart", e, false),
+    }
+    let inline = format!("{}: [{}]", title, numbers.join(", "));
+    if inline.chars().count() <= MESSAGE_LIMIT {
+        return reply.content(inline).embed(embed);
+    }
+    let text: String = numbers.iter().map(|x| format!("{}\n", x)).collect();
+    if text.len() > MAX_ATTACHMENT_SIZE {
+        return reply
+            .content(format!(
+                "The sequence has {} numbers, too many to attach.",
+                terms.len()
+            ))
+            .embed(embed);
+    }
+    reply
+        .content(format!(
+            "The sequence has {} numbers and is attached.",
+            terms.len()
+        ))
+        .attachment(serenity::CreateAttachment::bytes(
+            text.into_bytes(),
+            format!("{}.txt", stem),
+        ))
+        .embed(embed)
+}
+
+/// How `run` ended, listing the terms of a short cycle
+fn describe(run: &Run) -> String {
+    match &run.ending {
+        Ending::Terminated => format!("Ends after {} terms", run.terms.len()),
+        Ending::Cycle { start, length } if *length <= MAX_SHOWN_CYCLE => {
+            let cycle: Vec<String> = run.terms[*start..].iter().map(abbreviate).collect();
+            format!(
+                "Repeats from index {} with period {}: {} → …",
+                start,
+                length,
+                cycle.join(" → ")
+            )
+        }
+        Ending::Cycle { start, length } => {
+            format!("Repeats from index {} with period {}", start, length)
+        }
+        Ending::Limit(reason) => reason.clone(),
+    }
+}
+
+/// Generate a sequence in the sandbox and reply with its summary, chart and
+/// terms
+async fn run_sequence(
+    ctx: Context<'_>,
+    title: String,
+    stem: &str,
+    summary_only: Option<bool>,
+    generate: impl FnOnce(&crate::sandbox::CancelToken) -> Run + Send + 'static,
+    verdict: impl FnOnce(&Run) -> Option<String>,
+) -> Result<(), Error> {
+    let job = move |cancel: crate::sandbox::CancelToken| generate(&cancel);
+    let run = match ctx.data().sandbox.run(ctx.author().id, job).await {
+        Ok(run) => run,
+        Err(e) => {
+            ctx.say(e.to_string()).await?;
+            return Ok(());
+        }
+    };
+    let mut fields = vec![
+        ("Terms", run.terms.len().to_string()),
+        ("Ending", describe(&run)),
+    ];
+    if let Some(verdict) = verdict(&run) {
+        fields.push(("Verdict", verdict));
+    }
+    let reply = sequence_reply(
+        &title,
+        stem,
+        &run.terms,
+        fields,
+        summary_only.unwrap_or(false),
+    );
+    ctx.send(reply).await?;
+    Ok(())
+}
+
+fn max_terms(terms: Option<u32>, default: u32) -> usize {
+    terms.unwrap_or(default).min(MAX_TERMS) as usize
+}
+
+/// Explore integer sequences, with their cycles, peaks and a chart
+#[poise::command(
+    slash_command,
+    subcommands(
+        "collatz",
+        "fibonacci",
+        "lucas",
+        "recaman",
+        "look_and_say",
+        "aliquot",
+        "lychrel",
+        "happy"
+    ),
+    subcommand_required
+)]
+pub async fn sequence(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Collatz with a rule of your own: halve even numbers, take an + b of odd ones
+#[poise::command(slash_command)]
+pub async fn collatz(
+    ctx: Context<'_>,
+    #[description = "Starting number"] n: i64,
+    #[description = "Multiplier for odd numbers, 3 by default"]
+    #[min = 1]
+    #[max = 1000]
+    a: Option<i64>,
+    #[description = "Added to odd numbers after multiplying, 1 by default"]
+    #[min = -1000]
+    #[max = 1000]
+    b: Option<i64>,
+    #[description = "Most terms to compute, 1000 by default"]
+    #[min = 1]
+    #[max = 10000]
+    terms: Option<u32>,
+    #[description = "Only show the summary, without the chart and the sequence"] summary: Option<
+        bool,
+    >,
+) -> Result<(), Error> {
+    let (a, b) = (a.unwrap_or(3), b.unwrap_or(1));
+    let terms = max_terms(terms, 1000);
+    let title = format!("{}n{:+} sequence from {}", a, b, n);
+    let generate = move |cancel: &_| generators::collatz_variant(n, a, b, terms, cancel);
+    run_sequence(ctx, title, "collatz", summary, generate, |_| None).await
+}
+
+/// Fibonacci numbers, or any sequence where each term adds up the two before
+#[poise::command(slash_command)]
+pub async fn fibonacci(
+    ctx: Context<'_>,
+    #[description = "First term, 0 by default"] first: Option<i64>,
+    #[description = "Second term, 1 by default"] second: Option<i64>,
+    #[description = "Take the terms modulo this, to find the period"]
+    #[min = 2]
+    modulus: Option<u64>,
+    #[description = "Most terms to compute, 50 by default"]
+    #[min = 1]
+    #[max = 10000]
+    terms: Option<u32>,
+    #[description = "Only show the summary, without the chart and the sequence"] summary: Option<
+        bool,
+    >,
+) -> Result<(), Error> {
+    let (first, second) = (first.unwrap_or(0), second.unwrap_or(1));
+    let terms = max_terms(terms, 50);
+    let title = match modulus {
+        Some(m) => format!("Fibonacci sequence from {}, {} modulo {}", first, second, m),
+        None => format!("Fibonacci sequence from {}, {}", first, second),
+    };
+    let generate = move |cancel: &_| generators::fibonacci(first, second, modulus, terms, cancel);
+    run_sequence(ctx, title, "fibonacci", summary, generate, |_| None).await
+}
+
+/// Lucas numbers: 2, 1, 3, 4, 7, ...
+#[poise::command(slash_command)]
+pub async fn lucas(
+    ctx: Context<'_>,
+    #[description = "Take the terms modulo this, to find the period"]
+    #[min = 2]
+    modulus: Option<u64>,
+    #[description = "Most terms to compute, 50 by default"]
+    #[min = 1]
+    #[max = 10000]
+    terms: Option<u32>,
+    #[description = "Only show the summary, without the chart and the sequence"] summary: Option<
+        bool,
+    >,
+) -> Result<(), Error> {
+    let terms = max_terms(terms, 50);
+    let title = match modulus {
+        Some(m) => format!("Lucas numbers modulo {}", m),
+        None => "Lucas numbers".to_string(),
+    };
+    let generate = move |cancel: &_| generators::fibonacci(2, 1, modulus, terms, cancel);
+    run_sequence(ctx, title, "lucas", summary, generate, |_| None).await
+}
+
+/// Recamán's sequence: step back when you can, forward when you must
+#[poise::command(slash_command)]
+pub async fn recaman(
+    ctx: Context<'_>,
+    #[description = "Most terms to compute, 100 by default"]
+    #[min = 1]
+    #[max = 10000]
+    terms: Option<u32>,
+    #[description = "Only show the summary, without the chart and the sequence"] summary: Option<
+        bool,
+    >,
+) -> Result<(), Error> {
+    let terms = max_terms(terms, 100);
+    let generate = move |cancel: &_| generators::recaman(terms, cancel);
+    let title = "Recamán's sequence".to_string();
+    run_sequence(ctx, title, "recaman", summary, generate, |_| None).await
+}
+
+/// Look-and-say sequence: 1, 11, 21, 1211, ...
+#[poise::command(slash_command, rename = "look-and-say")]
+pub async fn look_and_say(
+    ctx: Context<'_>,
+    #[description = "First term, 1 by default"] seed: Option<String>,
+    #[description = "Most terms to compute, 20 by default"]
+    #[min = 1]
+    #[max = 10000]
+    terms: Option<u32>,
+    #[description = "Only show the summary, without the chart and the sequence"] summary: Option<
+        bool,
+    >,
+) -> Result<(), Error> {
+    let seed = seed.unwrap_or_else(|| "1".to_string());
+    let seed = seed.trim();
+    if seed.is_empty()
+        || seed.len() > MAX_SEED_DIGITS
+        || !seed.bytes().all(|b| b.is_ascii_digit())
+        || (seed.starts_with('0') && seed.len() > 1)
+    {
+        ctx.say(format!(
+            "The seed must be a number of at most {} digits",
+            MAX_SEED_DIGITS
+        ))
+        .await?;
+        return Ok(());
+    }
+    let title = format!("Look-and-say sequence from {}", seed);
+    let seed: BigInt = seed.parse()?;
+    let terms = max_terms(terms, 20);
+    let generate = move |cancel: &_| generators::look_and_say(seed, terms, cancel);
+    run_sequence(ctx, title, "look_and_say", summary, generate, |_| None).await
+}
+
+/// Aliquot sequence: each term the sum of the proper divisors of the last
+#[poise::command(slash_command)]
+pub async fn aliquot(
+    ctx: Context<'_>,
+    #[description = "Starting number"]
+    #[min = 1]
+    n: u64,
+    #[description = "Most terms to compute, 1000 by default"]
+    #[min = 1]
+    #[max = 10000]
+    terms: Option<u32>,
+    #[description = "Only show the summary, without the chart and the sequence"] summary: Option<
+        bool,
+    >,
+) -> Result<(), Error> {
+    let terms = max_terms(terms, 1000);
+    let generate = move |cancel: &_| generators::aliquot(n, terms, cancel);
+    let verdict = move |run: &Run| match run.ending {
+        Ending::Terminated => Some(format!("{} ends at 0", n)),
+        Ending::Cycle {
+            start: 0,
+            length: 1,
+        } => Some(format!("{} is perfect", n)),
+        Ending::Cycle {
+            start: 0,
+            length: 2,
+        } => Some(format!("{} is amicable", n)),
+        Ending::Cycle { start: 0, .. } => Some(format!("{} is sociable", n)),
+        // Reaches a perfect number
+        Ending::Cycle { length: 1, .. } => Some(format!("{} is aspiring", n)),
+        Ending::Cycle { .. } => Some(format!("{} falls into a cycle", n)),
+        Ending::Limit(_) => None,
+    };
+    let title = format!("Aliquot sequence from {}", n);
+    run_sequence(ctx, title, "aliquot", summary, generate, verdict).await
+}
+
+/// Reverse and add until a palindrome comes up, to hunt for Lychrel numbers
+#[poise::command(slash_command)]
+pub async fn lychrel(
+    ctx: Context<'_>,
+    #[description = "Starting number"] n: u64,
+    #[description = "Most terms to compute, 500 by default"]
+    #[min = 1]
+    #[max = 10000]
+    terms: Option<u32>,
+    #[description = "Only show the summary, without the chart and the sequence"] summary: Option<
+        bool,
+    >,
+) -> Result<(), Error> {
+    let terms = max_terms(terms, 500);
+    let generate = move |cancel: &_| generators::lychrel(n, terms, cancel);
+    let verdict = move |run: &Run| {
+        Some(match run.ending {
+            Ending::Terminated => format!(
+                "{} reaches a palindrome in {} steps",
+                n,
+                run.terms.len() - 1
+            ),
+            _ => format!("{} may be a Lychrel number", n),
+        })
+    };
+    let title = format!("Reverse and add from {}", n);
+    run_sequence(ctx, title, "lychrel", summary, generate, verdict).await
+}
+
+/// Sum the squares of the digits until reaching 1, or not
+#[poise::command(slash_command)]
+pub async fn happy(
+    ctx: Context<'_>,
+    #[description = "Starting number"] n: u64,
+    #[description = "Only show the summary, without the chart and the sequence"] summary: Option<
+        bool,
+    >,
+) -> Result<(), Error> {
+    let generate = move |cancel: &_| generators::happy(n, MAX_TERMS as usize, cancel);
+    let verdict = move |run: &Run| match run.ending {
+        Ending::Terminated => Some(format!("{} is happy", n)),
+        Ending::Cycle { .. } => Some(format!("{} is unhappy", n)),
+        Ending::Limit(_) => None,
+    };
+    let title = format!("Happy number sequence from {}", n);
+    run_sequence(ctx, title, "happy", summary, generate, verdict).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sandbox::CancelToken;
+
+    #[test]
+    fn test_abbreviate() {
+        assert_eq!(abbreviate(&9232), "9232");
+        let n: BigInt = "1".repeat(50).parse().unwrap();
+        assert_eq!(
+            abbreviate(&n),
+            "11111111111111111111…11111111111111111111 (50 digits)"
+        );
+    }
+
+    #[test]
+    fn test_describe() {
+        let cancel = CancelToken::default();
+        let run = generators::happy(4, 100, &cancel);
+        assert_eq!(
+            describe(&run),
+            "Repeats from index 0 with period 8: 4 → 16 → 37 → 58 → 89 → 145 → 42 → 20 → …"
+        );
+        let run = generators::fibonacci(0, 1, Some(10), 1000, &cancel);
+        assert_eq!(describe(&run), "Repeats from index 0 with period 60");
+        let run = generators::aliquot(12, 100, &cancel);
+        assert_eq!(describe(&run), "Ends after 8 terms");
+    }
+
+    #[test]
+    fn test_sequence_reply() {
+        let terms = [3, 10, 5, 16, 8, 4, 2, 1];
+        let reply = sequence_reply("Test", "test", &terms, vec![], false);
+        assert_eq!(
+            reply.content.as_deref(),
+            Some("Test: [3, 10, 5, 16, 8, 4, 2, 1]")
+        );
+        assert_eq!(reply.attachments.len(), 1);
+        assert_eq!(reply.attachments[0].filename, "test.png");
+
+        let terms: Vec<u64> = (0..1000).collect();
+        let reply = sequence_reply("Test", "test", &terms, vec![], false);
+        assert_eq!(
+            reply.content.as_deref(),
+            Some("The sequence has 1000 numbers and is attached.")
+        );
+        assert_eq!(reply.attachments[1].filename, "test.txt");
+
+        let reply = sequence_reply("Test", "test", &terms, vec![], true);
+        assert!(reply.attachments.is_empty());
+        let empty: [u64; 0] = [];
+        let reply = sequence_reply("Test", "test", &empty, vec![], false);
+        assert_eq!(reply.content.as_deref(), Some("The sequence is empty"));
+    }
+
+    #[test]
+    fn test_huge_terms_chart_their_digits() {
+        let run = generators::look_and_say(1.into(), 60, &CancelToken::default());
+        let reply = sequence_reply("Test", "test", &run.terms, vec![], false);
+        // Far too long for f64, but still charted and attached
+        assert_eq!(reply.attachments.len(), 2);
+    }
+}
@@ -0,0 +1,419 @@
+use crate::sandbox::CancelToken;
+use num_bigint::BigInt;
+use num_traits::{Signed, Zero};
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+// About a thousand decimal digits
+const MAX_TERM_BITS: u64 = 3_322;
+// Aliquot terms are factored by trial division, so they must stay small
+const MAX_ALIQUOT_TERM: u64 = 1_000_000_000_000;
+
+/// How a sequence stopped
+#[derive(Debug, Clone, PartialEq)]
+pub enum Ending {
+    /// Reached a term that has no successor
+    Terminated,
+    /// Came back to the state it was in at term `start`, and would repeat
+    /// the `length` terms from there forever
+    Cycle { start: usize, length: usize },
+    /// Stopped early, for the reason given
+    Limit(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Run {
+    pub terms: Vec<BigInt>,
+    pub ending: Ending,
+}
+
+/// Step from `start` until `step` finds no successor, a state comes back
+/// or a limit is reached. `step` may also stop the sequence with a reason.
+///
+/// The state has to determine every later term, so sequences that depend on
+/// more than their last term carry the rest along in it.
+fn iterate<S: Clone + Eq + Hash>(
+    start: S,
+    max_terms: usize,
+    cancel: &CancelToken,
+    value: impl Fn(&S) -> BigInt,
+    mut step: impl FnMut(&S) -> Result<Option<S>, String>,
+) -> Run {
+    let mut seen: HashMap<S, usize> = HashMap::new();
+    let mut terms = Vec::new();
+    let mut state = start;
+    let ending = loop {
+        if let Some(&start) = seen.get(&state) {
+            break Ending::Cycle {
+                start,
+                length: terms.len() - start,
+            };
+        }
+        let term = value(&state);
+        if term.bits() > MAX_TERM_BITS {
+            break Ending::Limit("Terms grew past a thousand digits".to_string());
+        }
+        terms.push(term);
+        if cancel.is_cancelled() {
+            break Ending::Limit(format!("Ran out of time after {} terms", terms.len()));
+        }
+        seen.insert(state.clone(), terms.len() - 1);
+        state = match step(&state) {
+            Ok(Some(next)) => next,
+            Ok(None) => break Ending::Terminated,
+            Err(reason) => break Ending::Limit(reason),
+        };
+        if terms.len() >= max_terms {
+            break Ending::Limit(format!("Stopped after {} terms", terms.len()));
+        }
+    };
+    Run { terms, ending }
+}
+
+/// Collatz with `a`n + `b` for odd n, halving even n; it runs until it
+/// cycles, as 3n + 1 does through 4, 2, 1
+pub fn collatz_variant(n: i64, a: i64, b: i64, max_terms: usize, cancel: &CancelToken) -> Run {
+    iterate(
+        BigInt::from(n),
+        max_terms,
+        cancel,
+        BigInt::clone,
+        |x: &BigInt| {
+            Ok(Some(if (x % 2i32).is_zero() {
+                x / 2i32
+            } else {
+                x * a + b
+            }))
+        },
+    )
+}
+
+/// Each term the sum of the two before, from `first` and `second`, or that
+/// sum modulo `modulus`, which makes the sequence periodic
+pub fn fibonacci(
+    first: i64,
+    second: i64,
+    modulus: Option<u64>,
+    max_terms: usize,
+    cancel: &CancelToken,
+) -> Run {
+    let reduce = move |x: BigInt| match modulus {
+        Some(m) => ((x % m) + m) % m,
+        None => x,
+    };
+    let start = (reduce(first.into()), reduce(second.into()));
+    iterate(
+        start,
+        max_terms,
+        cancel,
+        |(a, _)| a.clone(),
+        |(a, b)| Ok(Some((b.clone(), reduce(a + b)))),
+    )
+}
+
+/// Recamán's sequence: step back by n when that lands on a new positive
+/// number, otherwise forward
+pub fn recaman(max_terms: usize, cancel: &CancelToken) -> Run {
+    let mut visited = HashSet::from([0u64]);
+    iterate(
+        (0u64, 0u64),
+        max_terms,
+        cancel,
+        |&(_, x)| x.into(),
+        |&(n, x)| {
+            let n = n + 1;
+            let next = match x.checked_sub(n) {
+                Some(back) if back > 0 && !visited.contains(&back) => back,
+                _ => x + n,
+            };
+            visited.insert(next);
+            Ok(Some((n, next)))
+        },
+    )
+}
+
+/// Each term reads out the digits of the one before: 1, 11, 21, 1211, ...
+pub fn look_and_say(seed: BigInt, max_terms: usize, cancel: &CancelToken) -> Run {
+    iterate(seed, max_terms, cancel, BigInt::clone, |x| {
+        let digits = x.to_string();
+        let mut said = String::new();
+        let mut rest = digits.as_str();
+        while let Some(c) = rest.chars().next() {
+            let run = rest.len() - rest.trim_start_matches(c).len();
+            said.push_str(&run.to_string());
+            said.push(c);
+            rest = &rest[run..];
+        }
+        said.parse()
+            .map(Some)
+            .map_err(|e| format!("Could not read {}: {}", said, e))
+    })
+}
+
+/// Sum of the divisors of `n` other than itself
+fn aliquot_sum(n: u64) -> u64 {
+    if n < 2 {
+        return 0;
+    }
+    // σ(n) as the product of 1 + p + ... + p^k over the prime powers of n
+    let (mut sigma, mut rest, mut p) = (1u64, n, 2u64);
+    while p * p <= rest {
+        let (mut power, mut sum) = (1u64, 1u64);
+        while rest.is_multiple_of(p) {
+            rest /= p;
+            power *= p;
+            sum += power;
+        }
+        sigma *= sum;
+        p += if p == 2 { 1 } else { 2 };
+    }
+    if rest > 1 {
+        sigma *= rest + 1;
+    }
+    sigma - n
+}
+
+/// Each term the sum of the proper divisors of the one before; it ends at
+/// 0, or cycles at perfect, amicable and sociable numbers
+pub fn aliquot(n: u64, max_terms: usize, cancel: &CancelToken) -> Run {
+    iterate(
+        n,
+        max_terms,
+        cancel,
+        |&x| x.into(),
+        |&x| match x {
+            0 => Ok(None),
+            x if x > MAX_ALIQUOT_TERM => Err(format!(
+                "Terms grew past {}, too large to factor",
+                MAX_ALIQUOT_TERM
+            )),
+            x => Ok(Some(aliquot_sum(x))),
+        },
+    )
+}
+
+fn reverse_digits(x: &BigInt) -> BigInt {
+    let reversed: String = x.abs().to_string().chars().rev().collect();
+    let reversed: BigInt = reversed.parse().unwrap_or_default();
+    if x.is_negative() {
+        -reversed
+    } else {
+        reversed
+    }
+}
+
+/// Add each term to its reverse until a palindrome comes up; numbers that
+/// never get there, like 196 is believed to be, are Lychrel numbers
+pub fn lychrel(n: u64, max_terms: usize, cancel: &CancelToken) -> Run {
+    iterate(
+        (false, BigInt::from(n)),
+        max_terms,
+        cancel,
+        |(_, x)| x.clone(),
+        |(stepped, x)| {
+            let reversed = reverse_digits(x);
+            if *stepped && reversed == *x {
+                Ok(None)
+            } else {
+                Ok(Some((true, x + reversed)))
+            }
+        },
+    )
+}
+
+/// Each term the sum of the squares of the digits of the one before; happy
+/// numbers end at 1, the others fall into the cycle through 4
+pub fn happy(n: u64, max_terms: usize, cancel: &CancelToken) -> Run {
+    iterate(
+        n,
+        max_terms,
+        cancel,
+        |&x| x.into(),
+        |&x| {
+            if x == 1 {
+                return Ok(None);
+            }
+            let mut sum = 0;
+            let mut rest = x;
+            while rest > 0 {
+                sum += (rest % 10).pow(2);
+                rest /= 10;
+            }
+            Ok(Some(sum))
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terms(run: &Run) -> Vec<i64> {
+        run.terms
+            .iter()
+            .map(|x| i64::try_from(x).unwrap())
+            .collect()
+    }
+
+    fn cancel() -> CancelToken {
+        CancelToken::default()
+    }
+
+    #[test]
+    fn test_collatz_variant() {
+        let run = collatz_variant(3, 3, 1, 100, &cancel());
+        assert_eq!(terms(&run), vec![3, 10, 5, 16, 8, 4, 2, 1]);
+        assert_eq!(
+            run.ending,
+            Ending::Cycle {
+                start: 5,
+                length: 3
+            }
+        );
+
+        // 3n - 1 has a cycle through 5
+        let run = collatz_variant(5, 3, -1, 100, &cancel());
+        assert_eq!(terms(&run), vec![5, 14, 7, 20, 10]);
+        assert_eq!(
+            run.ending,
+            Ending::Cycle {
+                start: 0,
+                length: 5
+            }
+        );
+
+        // 5n + 1 from 7 is thought to grow forever
+        let run = collatz_variant(7, 5, 1, 50, &cancel());
+        assert_eq!(run.terms.len(), 50);
+        assert_eq!(
+            run.ending,
+            Ending::Limit("Stopped after 50 terms".to_string())
+        );
+        let run = collatz_variant(7, 5, 1, 1_000_000, &cancel());
+        assert_eq!(
+            run.ending,
+            Ending::Limit("Terms grew past a thousand digits".to_string())
+        );
+    }
+
+    #[test]
+    fn test_fibonacci() {
+        let run = fibonacci(0, 1, None, 10, &cancel());
+        assert_eq!(terms(&run), vec![0, 1, 1, 2, 3, 5, 8, 13, 21, 34]);
+        let lucas = fibonacci(2, 1, None, 6, &cancel());
+        assert_eq!(terms(&lucas), vec![2, 1, 3, 4, 7, 11]);
+
+        // The Pisano period of 10 is 60
+        let run = fibonacci(0, 1, Some(10), 1000, &cancel());
+        assert_eq!(
+            run.ending,
+            Ending::Cycle {
+                start: 0,
+                length: 60
+            }
+        );
+        let run = fibonacci(-3, 1, Some(5), 1000, &cancel());
+        assert_eq!(terms(&run)[..3], [2, 1, 3]);
+    }
+
+    #[test]
+    fn test_recaman() {
+        let run = recaman(12, &cancel());
+        assert_eq!(terms(&run), vec![0, 1, 3, 6, 2, 7, 13, 20, 12, 21, 11, 22]);
+        // 42 comes up twice without being a cycle
+        let run = recaman(25, &cancel());
+        assert_eq!(terms(&run)[20], 42);
+        assert_eq!(terms(&run)[24], 42);
+        assert!(matches!(run.ending, Ending::Limit(_)));
+    }
+
+    #[test]
+    fn test_look_and_say() {
+        let run = look_and_say(1.into(), 6, &cancel());
+        assert_eq!(terms(&run), vec![1, 11, 21, 1211, 111221, 312211]);
+        let run = look_and_say(22.into(), 6, &cancel());
+        assert_eq!(
+            run.ending,
+            Ending::Cycle {
+                start: 0,
+                length: 1
+            }
+        );
+    }
+
+    #[test]
+    fn test_aliquot() {
+        assert_eq!(aliquot_sum(12), 16);
+        assert_eq!(aliquot_sum(13), 1);
+        assert_eq!(aliquot_sum(1), 0);
+
+        let run = aliquot(12, 100, &cancel());
+        assert_eq!(terms(&run), vec![12, 16, 15, 9, 4, 3, 1, 0]);
+        assert_eq!(run.ending, Ending::Terminated);
+        assert_eq!(
+            aliquot(6, 100, &cancel()).ending,
+            Ending::Cycle {
+                start: 0,
+                length: 1
+            }
+        );
+        let run = aliquot(220, 100, &cancel());
+        assert_eq!(terms(&run), vec![220, 284]);
+        assert_eq!(
+            run.ending,
+            Ending::Cycle {
+                start: 0,
+                length: 2
+            }
+        );
+        // Sociable numbers of order 5
+        assert_eq!(
+            aliquot(12496, 100, &cancel()).ending,
+            Ending::Cycle {
+                start: 0,
+                length: 5
+            }
+        );
+    }
+
+    #[test]
+    fn test_lychrel() {
+        let run = lychrel(87, 100, &cancel());
+        assert_eq!(terms(&run), vec![87, 165, 726, 1353, 4884]);
+        assert_eq!(run.ending, Ending::Terminated);
+        // A palindrome to begin with still takes a step
+        assert_eq!(terms(&lychrel(5, 100, &cancel())), vec![5, 10, 11]);
+        assert_eq!(lychrel(89, 100, &cancel()).terms.len(), 25);
+        assert!(matches!(
+            lychrel(196, 200, &cancel()).ending,
+            Ending::Limit(_)
+        ));
+    }
+
+    #[test]
+    fn test_happy() {
+        let run = happy(19, 100, &cancel());
+        assert_eq!(terms(&run), vec![19, 82, 68, 100, 1]);
+        assert_eq!(run.ending, Ending::Terminated);
+        let run = happy(4, 100, &cancel());
+        assert_eq!(
+            run.ending,
+            Ending::Cycle {
+                start: 0,
+                length: 8
+            }
+        );
+    }
+
+    #[test]
+    fn test_cancelled() {
+        let cancel = cancel();
+        cancel.cancel();
+        let run = recaman(100, &cancel);
+        assert_eq!(run.terms.len(), 1);
+        assert_eq!(
+            run.ending,
+            Ending::Limit("Ran out of time after 1 terms".to_string())
+        );
+    }
+}
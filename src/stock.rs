@@ -22,7 +22,6 @@ fn currency_symbol(currency: &str) -> String {
     .to_string()
 }
 
-
 /// Get stock information
 #[poise::command(slash_command)]
 pub async fn stock(
@@ -0,0 +1,109 @@
+use crate::context::Error;
+use poise::serenity_prelude as serenity;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Data of every guild, saved to a JSON file after each change
+pub struct JsonStore<T> {
+    path: PathBuf,
+    guilds: Mutex<HashMap<u64, T>>,
+}
+
+impl<T: Clone + Default + Serialize + DeserializeOwned> JsonStore<T> {
+    pub fn load(path: impl Into<PathBuf>) -> Result<Self, Error> {
+        let path = path.into();
+        let guilds = match std::fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(JsonStore {
+            path,
+            guilds: Mutex::new(guilds),
+        })
+    }
+
+    /// Read a guild's data
+    pub fn view<U>(&self, guild: serenity::GuildId, f: impl FnOnce(&T) -> U) -> U {
+        let guilds = self.guilds.lock().unwrap();
+        match guilds.get(&guild.get()) {
+            Some(data) => f(data),
+            None => f(&T::default()),
+        }
+    }
+
+    /// Apply `change` to a copy of a guild's data, keeping it only when the
+    /// change succeeds and is saved
+    pub fn update<U>(
+        &self,
+        guild: serenity::GuildId,
+        change: impl FnOnce(&mut T) -> Result<U, String>,
+    ) -> Result<U, String> {
+        let mut guilds = self.guilds.lock().unwrap();
+        let mut data = guilds.get(&guild.get()).cloned().unwrap_or_default();
+        let value = change(&mut data)?;
+        let previous = guilds.insert(guild.get(), data);
+        if let Err(e) = self.save(&guilds) {
+            match previous {
+                Some(previous) => guilds.insert(guild.get(), previous),
+                None => guilds.remove(&guild.get()),
+            };
+            return Err(format!("Could not save the changes: {}", e));
+        }
+        Ok(value)
+    }
+
+    fn save(&self, guilds: &HashMap<u64, T>) -> Result<(), Error> {
+        // Write a copy first so a crash never leaves a truncated file behind
+        let temp = self.path.with_extension("json.tmp");
+        std::fs::write(&temp, serde_json::to_string_pretty(guilds)?)?;
+        std::fs::rename(&temp, &self.path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failed_change_is_not_written() {
+        let path = std::env::temp_dir().join(format!("bytie-store-{}.json", std::process::id()));
+        let guild = serenity::GuildId::new(1);
+        let store = JsonStore::<Vec<u32>>::load(&path).unwrap();
+        let saved = store.update(guild, |values| {
+            values.push(1);
+            Ok(())
+        });
+        assert_eq!(saved, Ok(()));
+        let failed = store.update(guild, |values| {
+            values.push(2);
+            Err::<(), _>("Refused".to_string())
+        });
+        assert_eq!(failed, Err("Refused".to_string()));
+        assert_eq!(store.view(guild, |v| v.clone()), vec![1]);
+
+        let reloaded = JsonStore::<Vec<u32>>::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(reloaded.view(guild, |v| v.clone()), vec![1]);
+        assert!(reloaded.view(serenity::GuildId::new(2), |v| v.is_empty()));
+    }
+
+    #[test]
+    fn test_unsaved_change_is_undone() {
+        let dir = std::env::temp_dir().join(format!("bytie-store-missing-{}", std::process::id()));
+        let store = JsonStore::<Vec<u32>>::load(dir.join("store.json")).unwrap();
+        let guild = serenity::GuildId::new(1);
+        let result = store.update(guild, |values| {
+            values.push(1);
+            Ok(())
+        });
+        assert!(result
+            .unwrap_err()
+            .starts_with("Could not save the changes"));
+        assert!(store.view(guild, |v| v.is_empty()));
+    }
+}
@@ -4,7 +4,7 @@ use crate::context::{Context, Error};
 #[poise::command(slash_command)]
 pub async fn usdtry(ctx: Context<'_>) -> Result<(), Error> {
     let parity = get_usd_try().await;
-    let buy = parity.get(0).unwrap();
+    let buy = parity.first().unwrap();
     let sell = parity.get(1).unwrap();
     ctx.say(format!("{buy} - {sell}")).await?;
     Ok(())
@@ -19,14 +19,12 @@ pub async fn get_usd_try() -> Vec<String> {
     let kur_boxes = document.select(&kurboxes_selector);
     for kur_box in kur_boxes {
         let content = kur_box.text().collect::<Vec<_>>();
-        if content.len() >= 5 {
-            if content[1] == "1 ABD DOLARI" {
-                //println!("{:?}", content[3]);
-                //println!("{:?}", content[5]);
-                parity.push(String::from(content[3]));
-                parity.push(String::from(content[5]));
-            }
+        if content.len() >= 5 && content[1] == "1 ABD DOLARI" {
+            //println!("{:?}", content[3]);
+            //println!("{:?}", content[5]);
+            parity.push(String::from(content[3]));
+            parity.push(String::from(content[5]));
         }
     }
-    return parity;
+    parity
 }
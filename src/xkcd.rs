@@ -1,6 +1,5 @@
 use crate::context::{Context, Error};
 use poise::serenity_prelude as serenity;
-use reqwest;
 use serde_json::Value;
 
 struct XKCDComic {